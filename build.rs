@@ -0,0 +1,11 @@
+// Compiles the helpers used to recover from traps in JIT-compiled code (see `src/backend/trap.rs`)
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/backend/trap.c");
+
+    if std::env::var_os("CARGO_CFG_UNIX").is_some() {
+        cc::Build::new()
+            .file("src/backend/trap.c")
+            .compile("jitter_trap");
+    }
+}
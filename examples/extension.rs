@@ -12,6 +12,7 @@ fn transform_top_level(mut item: Item, inputs: Vec<&str>) -> ExtensionResult {
                         name: param.to_owned(),
                         ty: Type::i32,
                         is_public: true,
+                        attributes: Vec::new(),
                     }.nodify()
                 );
             }
@@ -13,17 +13,21 @@ fn print_u32(n: &u32) {
     println!("u32: {}", n);
 }
 
-#[derive(Debug)]
-#[repr(C)]
-struct JitterStruct {
-    a: u32,
-    b: i32,
+// Checked against the Jitter struct of the same name (see `layouts` below)
+JitterLayout! {
+    #[derive(Debug)]
+    #[repr(C)]
+    struct JitterStruct {
+        a: u32,
+        b: i32,
+    }
 }
 
 fn main() {
     let jitter = Jitter! {
         ["./tests/rewrite_test.jitter"] <- [print_i32, print_u32, hello_from_rust]
         extensions <- ["./examples"]
+        layouts <- [JitterStruct]
         where 
         [
             // Informs the lexer to replace left side with right side
@@ -66,17 +70,17 @@ fn main() {
         meta          = jitter::meta_usage          as fn(),
     }
 
-    ffi(&9);
-    println!("test() = {}", test().into());
-    println!("params(7, 123) = {}", params(&7, &123).into());
-    println!("structs(100, -70) = {}", structs(&100, &-70).into());
-    println!("struct_return(90, -1) = {:?}", struct_return(&90, &-1).into());
-    println!("function_call1() = {}", function_call1().into());
-    println!("function_call2() = {:?}", function_call2().into());
-    callback();
+    ffi.call((9,));
+    println!("test() = {}", test.call(()));
+    println!("params(7, 123) = {}", params.call((7, 123)));
+    println!("structs(100, -70) = {}", structs.call((100, -70)));
+    println!("struct_return(90, -1) = {:?}", struct_return.call((90, -1)));
+    println!("function_call1() = {}", function_call1.call(()));
+    println!("function_call2() = {:?}", function_call2.call(()));
+    callback.call(());
     println!("--preprocessing()--");
-    preprocessing();
+    preprocessing.call(());
     println!("--operators()--");
-    ops();
-    meta();
+    ops.call(());
+    meta.call(());
 }
\ No newline at end of file
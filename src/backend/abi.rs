@@ -0,0 +1,146 @@
+// Calling conventions used by compiled functions.
+//
+// By default, Jitter functions take the address of each argument and write their result into
+// a buffer provided by the caller. `CallingConvention::C` instead follows the System V x86-64
+// C ABI, so compiled functions are ordinary `extern "C"` functions (and extern functions are
+// expected to be ones too).
+//
+// Under the C ABI, a value is split into "eightbytes" which are each passed in a register:
+// a floating point register if everything within the eightbyte is a float, and an integer
+// register otherwise. Values larger than 16 bytes (or containing unaligned fields) are passed
+// in memory instead, as are values needing more registers than are left (see `ArgumentRegisters`).
+// Only return values can be passed in memory for now, as Cranelift can't pass arguments on the
+// stack by value. Integers narrower than 32 bits are extended, as C compilers expect.
+
+use cranelift::prelude::types;
+use cranelift::codegen::ir;
+
+use crate::frontend::validate::context::Context as ValidationContext;
+use crate::frontend::validate::types::Type;
+
+/// How compiled functions receive arguments and return values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CallingConvention {
+    /// Arguments are passed by address, and values returned through a caller-provided buffer
+    #[default]
+    Jitter,
+    /// System V x86-64 C ABI (`extern "C"`)
+    C,
+}
+
+/// How a value is passed under the C calling convention
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ArgumentClass {
+    /// Each eightbyte in its own register (in order). Unit uses none.
+    Registers(Vec<ir::Type>),
+    /// Passed in memory. Returned values are written into a caller-provided buffer (`sret`).
+    Memory,
+}
+
+/// The size of each register-passed chunk of a value
+pub(crate) const EIGHTBYTE: usize = 8;
+
+/// Classifies a value according to the System V x86-64 ABI
+pub(crate) fn classify(ty: &Type, context: &ValidationContext) -> ArgumentClass {
+    let size = context.types.size_of(ty);
+    if size > 2 * EIGHTBYTE {
+        return ArgumentClass::Memory;
+    }
+
+    let mut scalars = Vec::new();
+    if !flatten(ty, 0, context, &mut scalars) {
+        return ArgumentClass::Memory;
+    }
+
+    let registers = (0..size).step_by(EIGHTBYTE)
+        .map(|start| {
+            let bytes = std::cmp::min(EIGHTBYTE, size - start);
+
+            let mut overlapping = scalars.iter()
+                .filter(|(offset, scalar)| *offset < start + EIGHTBYTE && offset + context.types.size_of(scalar) > start)
+                .peekable();
+
+            let is_float = overlapping.peek().is_some()
+                && overlapping.all(|(_, scalar)| matches!(scalar, Type::f32 | Type::f64));
+
+            match (is_float, bytes) {
+                (true, 1..=4) => types::F32,
+                (true, _) => types::F64,
+                // Tails of 3, 5, 6, or 7 bytes are loaded piecewise (see `FunctionTranslator::load_registers`)
+                (false, 1) => types::I8,
+                (false, 2) => types::I16,
+                (false, 3..=4) => types::I32,
+                (false, _) => types::I64,
+            }
+        })
+        .collect();
+
+    ArgumentClass::Registers(registers)
+}
+
+/// A register-passed part of a value. Integer scalars narrower than 32 bits are extended by their signedness.
+pub(crate) fn abi_param(register: ir::Type, ty: &Type) -> ir::AbiParam {
+    let param = ir::AbiParam::new(register);
+
+    match ty {
+        Type::bool | Type::u8 | Type::u16 => param.uext(),
+        Type::i8 | Type::i16 => param.sext(),
+        _ => param,
+    }
+}
+
+/// Argument registers left while laying out a signature (6 integer and 8 SSE registers)
+pub(crate) struct ArgumentRegisters {
+    integer: usize,
+    float: usize,
+}
+
+impl ArgumentRegisters {
+    pub fn new() -> Self {
+        Self {
+            integer: 6,
+            float: 8,
+        }
+    }
+
+    /// Takes the registers a value is passed in. If there aren't enough left for all of them,
+    /// none are taken and `false` is returned: the whole value is then passed on the stack.
+    pub fn take(&mut self, registers: &[ir::Type]) -> bool {
+        let float = registers.iter().filter(|register| register.is_float()).count();
+        let integer = registers.len() - float;
+
+        if integer > self.integer || float > self.float {
+            return false;
+        }
+
+        self.integer -= integer;
+        self.float -= float;
+        true
+    }
+}
+
+/// Collects every scalar within a value along with its offset.
+/// Returns `false` if any of them are unaligned (as in `@packed` structs).
+fn flatten(ty: &Type, offset: usize, context: &ValidationContext, scalars: &mut Vec<(usize, Type)>) -> bool {
+    if !offset.is_multiple_of(context.types.alignment_of(ty)) {
+        return false;
+    }
+
+    match ty {
+        Type::User(name) => context.struct_fields(name)
+            .expect("validated struct")
+            .into_iter()
+            .all(|(field_offset, field_ty)| flatten(field_ty, offset + field_offset, context, scalars)),
+
+        // References are passed like any other pointer-sized integer
+        Type::Reference { .. } => {
+            scalars.push((offset, Type::usize));
+            true
+        }
+
+        _ => {
+            scalars.push((offset, ty.clone()));
+            true
+        }
+    }
+}
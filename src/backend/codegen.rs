@@ -4,7 +4,11 @@ use crate::frontend::validate::types::Type as CompilerType;
 
 use cranelift::prelude::*;
 use cranelift_module::Module; // for trait functions
-use cranelift::codegen::ir::StackSlot;
+use cranelift::codegen::ir::{StackSlot, SourceLoc, ValueLabel};
+
+use std::collections::HashSet;
+
+use super::abi::{self, ArgumentClass, CallingConvention};
 
 //////////// CLIF Translation ////////////
 
@@ -13,31 +17,126 @@ use cranelift::codegen::ir::StackSlot;
 // It might be possible to reuse this module for different targets
 // such as generating standalone executables
 
+// Scalars (integers up to 64 bits, floats, bools, and references) are held in SSA values,
+// leaving register allocation to Cranelift. Everything else lives in memory, and is
+// represented by its address. Scalar variables whose address is taken (`&x`) live in
+// memory too, so that the reference sees every assignment.
+
 /// Translates a function and its contents into Cranelift IR
-pub struct FunctionTranslator<'input> {
+pub struct FunctionTranslator<'input, M: Module> {
     pointer_type: &'input Type,
     fn_builder: FunctionBuilder<'input>,
-    module: &'input mut cranelift_simplejit::SimpleJITModule,
-    // Maps variable names to memory locations
+    module: &'input mut M,
+    // Map of function names to their declarations (symbols may differ, see `ModuleCompiler::symbol_prefix`)
+    functions: &'input std::collections::HashMap<String, cranelift_module::FuncId>,
+    // Maps variable bindings to memory locations
     data: super::MemoryMap,
     validation_context: &'input ValidationContext,
     // Map of already declared functions to their references
     declared_functions: std::collections::HashMap<cranelift_module::FuncId, cranelift::codegen::ir::entities::FuncRef>,
+    calling_convention: CallingConvention,
+    // Whether calls to other compiled functions may be direct (see `ModuleCompiler::colocated_calls`)
+    colocated_calls: bool,
+    // Set when the function's value is returned in registers (C calling convention)
+    return_registers: Option<Vec<Type>>,
+    // Spans of translated statements, indexed by their instructions' `SourceLoc`s (for debug info)
+    source_locations: Vec<crate::Span>,
+    // Scalar variables held in SSA values (for debug info)
+    variables: Vec<super::debug::Variable>,
+    // Address of the fuel counter, if the code is metered (see `consume_fuel`)
+    fuel_address: Option<usize>,
 }
 
-impl<'input> FunctionTranslator<'input> {
-    pub fn new(pointer_type: &'input Type, fn_builder: FunctionBuilder<'input>, module: &'input mut cranelift_simplejit::SimpleJITModule, validation_context: &'input ValidationContext) -> Self {
+impl<'input, M: Module> FunctionTranslator<'input, M> {
+    pub fn new(pointer_type: &'input Type, fn_builder: FunctionBuilder<'input>, module: &'input mut M, functions: &'input std::collections::HashMap<String, cranelift_module::FuncId>, validation_context: &'input ValidationContext, calling_convention: CallingConvention, fuel_address: Option<usize>) -> Self {
         Self {
             pointer_type,
             fn_builder,
             module,
+            functions,
             data: super::MemoryMap::new(),
             validation_context,
             declared_functions: std::collections::HashMap::new(),
+            calling_convention,
+            colocated_calls: true,
+            return_registers: None,
+            source_locations: Vec::new(),
+            variables: Vec::new(),
+            fuel_address,
+        }
+    }
+
+    /// Whether calls to other compiled functions may be direct (otherwise they go through an absolute address)
+    pub fn with_colocated_calls(mut self, colocated_calls: bool) -> Self {
+        self.colocated_calls = colocated_calls;
+        self
+    }
+
+    /// Spans (indexed by `SourceLoc`) and variables gathered while translating
+    pub fn into_debug_info(self) -> (Vec<crate::Span>, Vec<super::debug::Variable>) {
+        (self.source_locations, self.variables)
+    }
+
+    /// Attributes the instructions which follow to the given span
+    fn set_source_location(&mut self, span: crate::Span) {
+        self.fn_builder.set_srcloc(SourceLoc::new(self.source_locations.len() as u32));
+        self.source_locations.push(span);
+    }
+
+    /// Labels scalar variables, so that debuggers can find their values
+    fn value_label(binding: ast::BindingId) -> ValueLabel {
+        ValueLabel::new(binding.0 as usize)
+    }
+
+    /// Marks `value` as the variable's value from here on
+    fn label_value(&mut self, value: Value, binding: ast::BindingId) {
+        // Only collected for debug info
+        if self.fn_builder.func.dfg.values_labels.is_none() {
+            return;
+        }
+
+        // Cranelift starts tracking a label after the source location it was set at,
+        // so each label needs a source location of its own (after the value's definition)
+        let span = *self.source_locations.last().expect("source location");
+        self.set_source_location(span);
+        self.fn_builder.set_val_label(value, Self::value_label(binding));
+    }
+
+    /// Records a variable for debug info. Only scalars held in SSA values are labelled.
+    fn record_variable(&mut self, binding: ast::BindingId, name: &str, ty: &CompilerType) {
+        if self.is_in_memory(binding, ty) {
+            return;
+        }
+
+        if let Some(scalar_type) = self.scalar_type(ty) {
+            self.variables.push(super::debug::Variable {
+                label: Self::value_label(binding),
+                name: name.to_owned(),
+                ty: ty.clone(),
+                size: scalar_type.bytes(),
+            });
         }
     }
 
-    pub fn translate_function(&mut self, function: &ast::Function, has_return_value: bool) -> Result<(), String> {                        
+    /// Takes one unit of fuel (if metered), trapping with `FUEL_EXHAUSTED` once there is none left.  
+    /// Every function entry consumes fuel, so unbounded recursion is stopped.
+    // FIXME: Loop back-edges should consume fuel too, once there are loops
+    fn consume_fuel(&mut self) {
+        let fuel_address = match self.fuel_address {
+            Some(address) => address,
+            None => return,
+        };
+
+        // The counter is always valid (owned by the `JitterContext`), so these accesses can't trap
+        let address = self.fn_builder.ins().iconst(*self.pointer_type, fuel_address as i64);
+        let fuel = self.fn_builder.ins().load(types::I64, MemFlags::trusted(), address, 0);
+        self.fn_builder.ins().trapz(fuel, super::trap::FUEL_EXHAUSTED);
+
+        let fuel = self.fn_builder.ins().iadd_imm(fuel, -1);
+        self.fn_builder.ins().store(MemFlags::trusted(), fuel, address, 0);
+    }
+
+    pub fn translate_function(&mut self, function: &ast::Function) -> Result<(), String> {                        
         // TEMP: debug
         // crate::log!("--Generating function `{}`--", function.prototype.name);
         
@@ -49,96 +148,171 @@ impl<'input> FunctionTranslator<'input> {
         self.fn_builder.switch_to_block(entry_block);
         // No predecessors for entry blocks
         self.fn_builder.seal_block(entry_block);
+        self.set_source_location(function.prototype.span);
+        self.consume_fuel();
 
-        // Declare the function's parameters (entry block params)
-        for (index, param) in function.prototype.parameters.iter().enumerate() {                        
-            let param_address = self.fn_builder.block_params(entry_block)[index];
-            
-            let var = self.data.create_variable(&param.name);
-            // Address is passed in to the function rather than actual value
-            self.fn_builder.declare_var(var, *self.pointer_type);
-            self.fn_builder.def_var(var, param_address);
+        let mut address_taken = HashSet::new();
+        collect_address_taken_block(&function.body, &mut address_taken);
+        for binding in address_taken {
+            self.data.mark_address_taken(binding);
         }
 
-        // Create a stack pre-allocation for the returned data
-        if has_return_value {
-            // FIXME: Narrowing cast
-            let type_size = self.validation_context.types.size_of(&function.prototype.return_type) as u32;
+        let mut params = self.fn_builder.block_params(entry_block).to_vec().into_iter();
+        let has_return_value = !function.prototype.return_type.is_unit();
 
-            let return_slot = self.fn_builder.create_stack_slot(StackSlotData {
-                kind: StackSlotKind::StructReturnSlot,
-                size: type_size,
-                offset: None,
-            });
+        // Values are either returned in registers, or by copying them into a buffer provided by the caller (first parameter)
+        self.return_registers = self.registers(&function.prototype.return_type);
+        if has_return_value && self.return_registers.is_none() {
+            let return_address = params.next().expect("return address");
+            self.data.register_struct_return_address(return_address);
+        }
 
-            self.data.register_struct_return_slot(return_slot);
+        // Declare the function's parameters (entry block params)
+        for param in function.prototype.parameters.iter() {
+            let value = match self.registers(&param.ty) {
+                Some(registers) => {
+                    let values = params.by_ref().take(registers.len()).collect::<Vec<_>>();
+
+                    if self.scalar_type(&param.ty).is_some() {
+                        values[0]
+                    } else {
+                        // Aggregates passed in registers are stored so that they have an address like any other
+                        let address = self.allocate_registers(&param.ty);
+                        self.store_registers(address, &values);
+                        address
+                    }
+                }
+
+                // Address is passed in to the function rather than actual value
+                None => {
+                    let address = params.next().expect("parameter address");
+                    let value = self.load_value(address, &param.ty);
+
+                    // The caller's value mustn't change, so aggregates which may be changed get a copy
+                    let binding = param.binding.expect("validated parameter");
+                    if self.scalar_type(&param.ty).is_none() && (param.mutable || self.data.is_address_taken(binding)) {
+                        self.copy_value(value, &param.ty)
+                    } else {
+                        value
+                    }
+                }
+            };
+            
+            let binding = param.binding.expect("validated parameter");
+            self.record_variable(binding, &param.name, &param.ty);
+            self.declare_variable(binding, &param.ty, Some(value));
         }
         
-        for statement in &function.body.block.item {
-            self.translate_statement(statement);
-        }
+        self.translate_block(&function.body);
 
+        // Validation ensures that functions with return values always return,
+        // so only code following a block which returned can reach the end
         // FIXME: This doesn't allow users to end functions with `()` or `return;`
-        if !has_return_value {
-            self.fn_builder.ins().return_(&[]);
+        if !self.fn_builder.is_filled() {
+            if has_return_value {
+                self.fn_builder.ins().trap(TrapCode::UnreachableCodeReached);
+            } else {
+                self.fn_builder.ins().return_(&[]);
+            }
         }
                
         self.fn_builder.finalize();
-        
-        // TEMP: debug (prints function before optimizations)
-        // crate::log!("{}", self.fn_builder.display(self.module.isa()));
+        // NOTE: The generated IR can be inspected with `JitterContextBuilder::emit_clif`
 
         Ok(())
     }
 
+    /// Translates a block's statements, returning the value of its trailing expression (if any)
+    fn translate_block(&mut self, block: &ast::BlockExpression) -> Option<Value> {
+        let mut value = None;
+
+        for statement in &block.block.item {
+            // Anything after a `return` is unreachable (validation warns about it)
+            if self.fn_builder.is_filled() {
+                break;
+            }
+
+            if let ast::Statement::Let { ident, ty, binding: Some(binding), .. } = &statement.item {
+                self.record_variable(*binding, ident, ty);
+            }
+
+            self.set_source_location(statement.span);
+            match &statement.item {
+                ast::Statement::ImplicitReturn { expression, is_function_return: false } => {
+                    value = Some(self.translate_expression(expression));
+                }
+
+                statement => self.translate_statement(statement),
+            }
+        }
+
+        value
+    }
+
     fn translate_statement(&mut self, statement: &ast::Statement) {
         match statement {
-            ast::Statement::Let { ident, mutable: _, ty: _, value } => {
-                let var = self.data.create_variable(ident);
-                self.fn_builder.declare_var(var, *self.pointer_type);
-                
-                if let Some(assignment) = value {
-                    let value_address = self.translate_expression(assignment);
-                    self.fn_builder.def_var(var, value_address);
-                }
+            ast::Statement::Let { ident: _, mutable: _, ty, value, binding } => {
+                let value = value.as_ref().map(|value| {
+                    let translated = self.translate_expression(value);
+
+                    // Aggregates are represented by their address, so binding an existing one (`let q = p;`) needs a copy
+                    if self.scalar_type(ty).is_none() && !is_temporary(value) {
+                        self.copy_value(translated, ty)
+                    } else {
+                        translated
+                    }
+                });
+                self.declare_variable(binding.expect("validated binding"), ty, value);
             }
             
             ast::Statement::Assign { lhs, operator: _, expression } => {
-                let destination_address = self.translate_expression(lhs);
-                let target_address = self.translate_expression(expression);
-                
-                // Copy the data from target to destination
-                let size = self.validation_context.types.size_of(expression.get_type());
-                let size_value = self.fn_builder.ins().iconst(*self.pointer_type, size as i64);
-
-                self.fn_builder.call_memcpy(self.module.target_config(), destination_address, target_address, size_value);
+                let value = self.translate_expression(expression);
+
+                match &lhs.item {
+                    // Scalar variables are simply given a new value
+                    ast::Expression::Ident { ty, binding: Some(binding), .. } if !self.is_in_memory(*binding, ty) => {
+                        let var = self.data.get_variable(*binding);
+                        self.fn_builder.def_var(var, value);
+                        self.label_value(value, *binding);
+                    }
+
+                    _ => {
+                        let destination_address = self.translate_place(lhs);
+                        self.store_value(destination_address, value, expression.get_type());
+                    }
+                }
             }
 
             ast::Statement::ImplicitReturn { expression, is_function_return } => {
                 // FIXME: Desugar this during validation rather than cloning
-                if *is_function_return {
-                    self.translate_statement(&ast::Statement::Return {
-                        expression: expression.clone(),
-                    });
-                } else {
-                    todo!()
-                }
+                // NOTE: A block's value is produced by `translate_block`
+                assert!(*is_function_return, "block value outside of a block");
+                self.translate_statement(&ast::Statement::Return {
+                    expression: expression.clone(),
+                });
             }
 
             // TODO: Unit types
             ast::Statement::Return { expression } => {
-                let return_data_address = self.translate_expression(expression);
+                let value = self.translate_expression(expression);
+                let ty = expression.get_type();
                 
-                let return_slot = *self.data.get_struct_return_slot();
-                let return_slot_address = self.fn_builder.ins().stack_addr(*self.pointer_type, return_slot, 0);
+                if let Some(registers) = self.return_registers.clone() {
+                    let values = if self.scalar_type(ty).is_some() {
+                        vec![value]
+                    } else {
+                        self.load_registers(value, ty, &registers)
+                    };
+
+                    self.fn_builder.ins().return_(&values);
+                    return;
+                }
 
-                // Copy the data stored in the address into the proper return slot.
-                let size = self.validation_context.types.size_of(expression.get_type()) as i64;
-                let size_value = self.fn_builder.ins().iconst(*self.pointer_type, size);
-                
-                self.fn_builder.call_memcpy(self.module.target_config(), return_slot_address, return_data_address, size_value);
+                // Copy the value into the caller's buffer
+                let return_address = self.data.get_struct_return_address();
+                self.store_value(return_address, value, ty);
 
-                self.fn_builder.ins().return_(&[return_slot_address]);
+                self.fn_builder.ins().return_(&[]);
             }
 
             ast::Statement::Expression(expression) => {
@@ -147,18 +321,25 @@ impl<'input> FunctionTranslator<'input> {
         }
     }
 
+    /// Returns the value of a scalar expression, or the address of any other
     fn translate_expression(&mut self, expression: &ast::Expression) -> Value {
         match expression {
-            ast::Expression::Ident { name, ty: _ } => {
-                let var = self.data.get_variable(name);
-                self.fn_builder.use_var(var)
+            ast::Expression::Ident { name: _, ty, binding } => {
+                let binding = binding.expect("validated binding");
+                let var = self.data.get_variable(binding);
+                let value = self.fn_builder.use_var(var);
+
+                // Address-taken scalars hold their address instead
+                if self.data.is_address_taken(binding) {
+                    self.load_value(value, ty)
+                } else {
+                    value
+                }
             }
 
-            ast::Expression::FieldAccess { base_expr, field, ty: _ } => {
-                let base_address = self.translate_expression(base_expr);
-                let field_offset = self.validation_context.get_field_offset(base_expr.get_type(), field).unwrap();
-                // return the address of the desired field
-                self.fn_builder.ins().iadd_imm(base_address, field_offset as i64)
+            ast::Expression::FieldAccess { ty, .. } => {
+                let address = self.translate_place(expression);
+                self.load_value(address, ty)
             }
 
             ast::Expression::FieldConstructor { ty, fields } 
@@ -170,102 +351,228 @@ impl<'input> FunctionTranslator<'input> {
             ast::Expression::FunctionCall { name, inputs, ty } 
                 => self.translate_expression_function_call(name, inputs, ty),
 
-            ast::Expression::BinaryExpression { lhs, op, rhs, ty } => {
-                todo!()
+            ast::Expression::BinaryExpression { lhs, op, rhs, ty } 
+                => self.translate_expression_binary(lhs, op, rhs, ty),
+
+            ast::Expression::UnaryExpression { op, expr, ty } => match op.item {
+                ast::UnaryOp::Reference { .. } => self.translate_place(expr),
+
+                ast::UnaryOp::Dereference => {
+                    let address = self.translate_expression(expr);
+                    self.load_value(address, ty)
+                }
+
+                ast::UnaryOp::Negate => {
+                    let value = self.translate_expression(expr);
+                    let value = self.numeric_value(value, ty);
+
+                    let negated = if ty.is_float() {
+                        self.fn_builder.ins().fneg(value)
+                    } else if self.scalar_type(ty).is_some() {
+                        self.fn_builder.ins().ineg(value)
+                    } else {
+                        // NOTE: Cranelift can't legalize `ineg` (or any constant) for 128-bit integers
+                        let half = self.fn_builder.ins().iconst(types::I64, 0);
+                        let zero = self.fn_builder.ins().iconcat(half, half);
+                        self.fn_builder.ins().isub(zero, value)
+                    };
+                    self.numeric_result(negated, ty)
+                }
+
+                // Booleans are held as 0 or 1
+                ast::UnaryOp::Not => {
+                    let value = self.translate_expression(expr);
+                    self.fn_builder.ins().bxor_imm(value, 1)
+                }
+
+                ast::UnaryOp::Custom(_) => unreachable!("custom operators are converted into function calls during validation"),
             }
 
-            ast::Expression::UnaryExpression { op, expr, ty } => {
-                todo!()
+            ast::Expression::Block(block) => {
+                let value = self.translate_block(block);
+
+                // The block returned from the function, so whatever follows it is unreachable
+                if self.fn_builder.is_filled() {
+                    let unreachable = self.fn_builder.create_block();
+                    self.fn_builder.switch_to_block(unreachable);
+                    self.fn_builder.seal_block(unreachable);
+                }
+
+                // Blocks without a value are of the unit type, which is never read
+                value.unwrap_or_else(|| Value::new(0))
+            }
+        }
+    }
+
+    /// Returns the address of a place expression (`x`, `x.y`, `*x`).
+    /// Anything else is a temporary, which is given memory of its own.
+    fn translate_place(&mut self, expression: &ast::Expression) -> Value {
+        match expression {
+            ast::Expression::Ident { ty, binding: Some(binding), .. } if self.is_in_memory(*binding, ty) => {
+                let var = self.data.get_variable(*binding);
+                self.fn_builder.use_var(var)
             }
 
-            ast::Expression::Block(_) => {
-                todo!()
+            ast::Expression::FieldAccess { base_expr, field, ty: _ } => {
+                // Fields are reached through references too
+                let (base_address, base_type) = match base_expr.get_type() {
+                    CompilerType::Reference { ty, .. } => (self.translate_expression(base_expr), &**ty),
+                    ty => (self.translate_place(base_expr), ty),
+                };
+
+                let field_offset = self.validation_context.get_field_offset(base_type, field).unwrap();
+                // return the address of the desired field
+                self.fn_builder.ins().iadd_imm(base_address, field_offset as i64)
+            }
+
+            ast::Expression::UnaryExpression { op, expr, ty: _ } if matches!(op.item, ast::UnaryOp::Dereference) => {
+                self.translate_expression(expr)
+            }
+
+            _ => {
+                let value = self.translate_expression(expression);
+                self.spill(value, expression.get_type())
             }
         }
     }
 
-    fn translate_expression_function_call(&mut self, name: &str, inputs: &Vec<ast::Node<ast::Expression>>, ty: &CompilerType) -> Value {
-        let func_id = if let cranelift_module::FuncOrDataId::Func(id) = self.module.declarations().get_name(name).unwrap() {
-            id
-        } else {
-            unreachable!()
-        };
+    fn translate_expression_function_call(&mut self, name: &str, inputs: &[ast::Node<ast::Expression>], ty: &CompilerType) -> Value {
+        let func_id = *self.functions.get(name).expect("declared function");
         
         // If a function has already been declared, don't declare it again
         // If it is new, save the reference for future use
         let func_ref = if !self.declared_functions.contains_key(&func_id) {
             let func_ref = self.module.declare_func_in_func(func_id, &mut self.fn_builder.func);
+
+            if !self.colocated_calls {
+                self.fn_builder.func.dfg.ext_funcs[func_ref].colocated = false;
+            }
+
             self.declared_functions.insert(func_id, func_ref);
             func_ref
         } else {
             *self.declared_functions.get(&func_id).unwrap()
         };
 
+        // Returned values are either written into a buffer owned by the caller, or returned in registers
+        let return_registers = self.registers(ty);
+        let return_address = if ty.is_unit() || return_registers.is_some() {
+            None
+        } else {
+            Some(self.allocate(ty))
+        };
+
         let mut passed_params = Vec::new();
+        passed_params.extend(return_address);
         for input in inputs {
-            passed_params.push(
-                self.translate_expression(input)
-            );
+            let value = self.translate_expression(input);
+            let input_type = input.get_type();
+
+            match self.registers(input_type) {
+                Some(_) if self.scalar_type(input_type).is_some() => passed_params.push(value),
+                Some(registers) => {
+                    let values = self.load_registers(value, input_type, &registers);
+                    passed_params.extend(values);
+                }
+
+                // Scalars need an address to be passed by
+                None => {
+                    let address = self.spill(value, input_type);
+                    passed_params.push(address);
+                }
+            }
         }
 
         let call = self.fn_builder.ins().call(func_ref, &passed_params);
 
-        // Cranelift allows multiple returns, but Jitter only allows one
-        let maybe_multiple_return = self.fn_builder.inst_results(call);
-
-        // Either one value is returned or none
-        if let Some(return_address) = maybe_multiple_return.get(0).map(|v| *v) {
-            // FIXME: Copy shouldn't be needed, but I don't know what else is wrong
-            //        that causes function calls as arguments to other functions
-            //        resulting in garbage being passed instead.
-            //
-            //        Should simply be able to pass along the returned address (`return_address` in this case)
-            let size = self.validation_context.types.size_of(ty) as u32;
-            let size_value = self.fn_builder.ins().iconst(*self.pointer_type, size as i64);
-            let slot = self.create_explicit_stack_allocation(size);
-            let slot_address = self.fn_builder.ins().stack_addr(*self.pointer_type, slot, 0);
-            self.fn_builder.call_memcpy(self.module.target_config(), slot_address, return_address, size_value);
-            slot_address
-        } else {
+        match (return_registers, return_address) {
+            (Some(_), _) if ty.is_unit() => Value::new(0),
+            (Some(_), _) if self.scalar_type(ty).is_some() => self.fn_builder.inst_results(call)[0],
+            (Some(_), _) => {
+                let values = self.fn_builder.inst_results(call).to_vec();
+                let address = self.allocate_registers(ty);
+                self.store_registers(address, &values);
+                address
+            }
+
+            (None, Some(address)) => self.load_value(address, ty),
+
             // If nothing is returned, just return an arbitrary value.
             // Assignments to unit types will ignore this anyway.
-            Value::new(0)
+            (None, None) => Value::new(0),
+        }
+    }
+
+    fn translate_expression_binary(&mut self, lhs: &ast::Expression, op: &ast::BinaryOp, rhs: &ast::Expression, ty: &CompilerType) -> Value {
+        // Both operands have the expression's type
+        let lhs = self.translate_expression(lhs);
+        let lhs = self.numeric_value(lhs, ty);
+        let rhs = self.translate_expression(rhs);
+        let rhs = self.numeric_value(rhs, ty);
+
+        let ins = self.fn_builder.ins();
+        let value = match (op, ty.is_float()) {
+            (ast::BinaryOp::Add, true) => ins.fadd(lhs, rhs),
+            (ast::BinaryOp::Add, false) => ins.iadd(lhs, rhs),
+            (ast::BinaryOp::Subtract, true) => ins.fsub(lhs, rhs),
+            (ast::BinaryOp::Subtract, false) => ins.isub(lhs, rhs),
+            (ast::BinaryOp::Multiply, true) => ins.fmul(lhs, rhs),
+            (ast::BinaryOp::Multiply, false) => ins.imul(lhs, rhs),
+            (ast::BinaryOp::Divide, true) => ins.fdiv(lhs, rhs),
+            // Traps on division by zero (see `trap.rs`)
+            (ast::BinaryOp::Divide, false) if ty.is_signed_integer() => ins.sdiv(lhs, rhs),
+            (ast::BinaryOp::Divide, false) => ins.udiv(lhs, rhs),
+
+            (ast::BinaryOp::Custom(_), _) => unreachable!("custom operators are converted into function calls during validation"),
+        };
+
+        self.numeric_result(value, ty)
+    }
+
+    /// The SSA value of a number (wider integers are loaded from memory)
+    fn numeric_value(&mut self, value: Value, ty: &CompilerType) -> Value {
+        match self.scalar_type(ty) {
+            Some(_) => value,
+            None => self.fn_builder.ins().load(ty.ir_type(self.pointer_type), MemFlags::new(), value, 0),
+        }
+    }
+
+    /// Inverse of `numeric_value` (wider integers are stored into new memory)
+    fn numeric_result(&mut self, value: Value, ty: &CompilerType) -> Value {
+        match self.scalar_type(ty) {
+            Some(_) => value,
+            None => {
+                let address = self.allocate(ty);
+                self.fn_builder.ins().store(MemFlags::new(), value, address, 0);
+                address
+            }
         }
     }
 
     fn translate_field_constructor(&mut self, ty: &CompilerType, fields: &std::collections::HashMap<String, ast::Node<ast::Expression>>) -> Value {
         // 1. Allocate memory for the object
-        // FIXME: Narrowing cast
-        let size = self.validation_context.types.size_of(ty) as u32;
-        let slot = self.create_explicit_stack_allocation(size);
-        let slot_address = self.fn_builder.ins().stack_addr(*self.pointer_type, slot, 0);
-
-        // TODO: Is there any way to write to a given address
-        //       rather than needing to copy like this?
+        let address = self.allocate(ty);
 
         for (field, expression) in fields {
-            // 2.1 Obtain the address containing the corresponding field's data
-            let field_value_address = self.translate_expression(expression);
+            // 2. Write each field's value into its slot
+            let value = self.translate_expression(expression);
             let field_offset = self.validation_context.get_field_offset(ty, field).unwrap() as i64;
-            let destination_address = self.fn_builder.ins().iadd_imm(slot_address, field_offset);
+            let destination_address = self.fn_builder.ins().iadd_imm(address, field_offset);
             
-            let field_size = self.validation_context.types.size_of(expression.get_type()) as i64;
-            let field_size_value = self.fn_builder.ins().iconst(*self.pointer_type, field_size);
-
-            // 2.2 Copy that data's bytes into the assigned slot
-            self.fn_builder.call_memcpy(self.module.target_config(), destination_address, field_value_address, field_size_value);
+            self.store_value(destination_address, value, expression.get_type());
         }
 
         // 3. Return the address of the newly instantiated object
-        self.fn_builder.ins().stack_addr(*self.pointer_type, slot, 0)
+        address
     }
 
-    // Allocate the data on the stack, fill it, and return the address
     fn translate_expression_literal(&mut self, literal: &ast::Literal, ty: &CompilerType) -> Value {
+        let ir_type = self.scalar_type(ty).unwrap_or_else(|| ty.ir_type(self.pointer_type));
+
         // FIXME: Narrowing casts
         let value = match literal {
             ast::Literal::Integer(integer) => {
-                self.fn_builder.ins().iconst(ty.ir_type(self.pointer_type), *integer as i64)
+                self.fn_builder.ins().iconst(ir_type, *integer as i64)
             }
             
             ast::Literal::Float(float) => {
@@ -282,6 +589,11 @@ impl<'input> FunctionTranslator<'input> {
             }
         };
 
+        if self.scalar_type(ty).is_some() {
+            return value;
+        }
+
+        // Wider integers live in memory
         let size = self.validation_context.types.size_of(ty) as u32;
         let allocation = self.create_explicit_stack_allocation(size);
 
@@ -289,6 +601,196 @@ impl<'input> FunctionTranslator<'input> {
         self.fn_builder.ins().stack_addr(*self.pointer_type, allocation, 0)      
     }
 
+    /// The type of the SSA values holding a type, if it is a scalar
+    fn scalar_type(&self, ty: &CompilerType) -> Option<Type> {
+        match ty {
+            CompilerType::u128
+            | CompilerType::i128
+            | CompilerType::User(_)
+            | CompilerType::Tuple(_)
+            | CompilerType::Unit => None,
+
+            // Held as a byte (0 or 1), exactly as in memory
+            CompilerType::bool => Some(types::I8),
+
+            _ => Some(ty.ir_type(self.pointer_type)),
+        }
+    }
+
+    /// Whether a variable holds an address (rather than the value itself)
+    fn is_in_memory(&self, binding: ast::BindingId, ty: &CompilerType) -> bool {
+        self.scalar_type(ty).is_none() || self.data.is_address_taken(binding)
+    }
+
+    /// Declares a variable, given its initial value (see `translate_expression`)
+    fn declare_variable(&mut self, binding: ast::BindingId, ty: &CompilerType, value: Option<Value>) {
+        let var = self.data.create_variable(binding);
+
+        match self.scalar_type(ty) {
+            Some(scalar_type) if !self.data.is_address_taken(binding) => {
+                self.fn_builder.declare_var(var, scalar_type);
+                if let Some(value) = value {
+                    self.fn_builder.def_var(var, value);
+                    self.label_value(value, binding);
+                }
+            }
+
+            // Address-taken scalars get memory of their own
+            Some(_) => {
+                let address = self.allocate(ty);
+                if let Some(value) = value {
+                    self.store_value(address, value, ty);
+                }
+
+                self.fn_builder.declare_var(var, *self.pointer_type);
+                self.fn_builder.def_var(var, address);
+            }
+
+            None => {
+                // Assigned later on -> needs somewhere to be copied into
+                let address = value.unwrap_or_else(|| self.allocate(ty));
+                self.fn_builder.declare_var(var, *self.pointer_type);
+                self.fn_builder.def_var(var, address);
+            }
+        }
+    }
+
+    /// Reads a value from memory (scalars are loaded, while anything else is represented by its address)
+    fn load_value(&mut self, address: Value, ty: &CompilerType) -> Value {
+        match self.scalar_type(ty) {
+            Some(scalar_type) => self.fn_builder.ins().load(scalar_type, MemFlags::new(), address, 0),
+            None => address,
+        }
+    }
+
+    /// Writes a value (see `translate_expression`) into memory
+    fn store_value(&mut self, address: Value, value: Value, ty: &CompilerType) {
+        if self.scalar_type(ty).is_some() {
+            self.fn_builder.ins().store(MemFlags::new(), value, address, 0);
+            return;
+        }
+
+        let size = self.validation_context.types.size_of(ty);
+        if size > 0 {
+            let size_value = self.fn_builder.ins().iconst(*self.pointer_type, size as i64);
+            self.fn_builder.call_memcpy(self.module.target_config(), address, value, size_value);
+        }
+    }
+
+    /// Copies a value into new memory, returning its address
+    fn copy_value(&mut self, value: Value, ty: &CompilerType) -> Value {
+        let address = self.allocate(ty);
+        self.store_value(address, value, ty);
+        address
+    }
+
+    /// Gives a value an address (scalars are copied into new memory)
+    fn spill(&mut self, value: Value, ty: &CompilerType) -> Value {
+        if self.scalar_type(ty).is_none() {
+            return value;
+        }
+
+        self.copy_value(value, ty)
+    }
+
+    /// Allocates stack memory for a value of the given type, returning its address.  
+    /// Addresses honour the type's alignment (such as that of an `@align(N)` struct).
+    fn allocate(&mut self, ty: &CompilerType) -> Value {
+        // FIXME: Narrowing casts
+        let size = self.validation_context.types.size_of(ty) as u32;
+        let alignment = self.validation_context.types.alignment_of(ty) as u32;
+        self.allocate_bytes(size, alignment)
+    }
+
+    /// Allocates stack memory for a value passed in registers.
+    /// Whole registers are stored, so the size is rounded up to a multiple of their size.
+    fn allocate_registers(&mut self, ty: &CompilerType) -> Value {
+        let eightbyte = abi::EIGHTBYTE as u32;
+
+        // FIXME: Narrowing casts
+        let size = self.validation_context.types.size_of(ty) as u32;
+        let alignment = self.validation_context.types.alignment_of(ty) as u32;
+        self.allocate_bytes(size.div_ceil(eightbyte) * eightbyte, alignment)
+    }
+
+    fn allocate_bytes(&mut self, size: u32, alignment: u32) -> Value {
+        // Cranelift only guarantees that stack slots are word-aligned
+        if alignment <= self.pointer_type.bytes() {
+            let slot = self.create_explicit_stack_allocation(size);
+            return self.fn_builder.ins().stack_addr(*self.pointer_type, slot, 0);
+        }
+
+        // Over-allocate, then round the address up to the next aligned one
+        let slot = self.create_explicit_stack_allocation(size + alignment - 1);
+        let slot_address = self.fn_builder.ins().stack_addr(*self.pointer_type, slot, 0);
+        let address = self.fn_builder.ins().iadd_imm(slot_address, alignment as i64 - 1);
+        self.fn_builder.ins().band_imm(address, -(alignment as i64))
+    }
+
+    /// The registers a value is passed in, if any (see `abi.rs`)
+    fn registers(&self, ty: &CompilerType) -> Option<Vec<Type>> {
+        match self.calling_convention {
+            CallingConvention::Jitter => None,
+            CallingConvention::C => match abi::classify(ty, self.validation_context) {
+                ArgumentClass::Registers(registers) => Some(registers),
+                ArgumentClass::Memory => None,
+            },
+        }
+    }
+
+    /// Loads each eightbyte of a value into its register
+    fn load_registers(&mut self, address: Value, ty: &CompilerType, registers: &[Type]) -> Vec<Value> {
+        let size = self.validation_context.types.size_of(ty);
+
+        registers.iter()
+            .enumerate()
+            .map(|(index, register)| {
+                let offset = index * abi::EIGHTBYTE;
+                let bytes = std::cmp::min(abi::EIGHTBYTE, size - offset);
+
+                if register.is_float() || bytes.is_power_of_two() {
+                    self.fn_builder.ins().load(*register, MemFlags::new(), address, offset as i32)
+                } else {
+                    self.load_tail(address, offset, bytes, *register)
+                }
+            })
+            .collect()
+    }
+
+    /// Loads an integer tail of 3, 5, 6, or 7 bytes piece by piece, as its register is wider than the value
+    /// (which may be followed by unrelated memory, or none at all)
+    fn load_tail(&mut self, address: Value, offset: usize, bytes: usize, register: Type) -> Value {
+        let mut result = self.fn_builder.ins().iconst(register, 0);
+        let mut loaded = 0;
+
+        for piece_bytes in [4, 2, 1] {
+            if bytes - loaded < piece_bytes {
+                continue;
+            }
+
+            // Little-endian, so later bytes are more significant
+            let piece_offset = (offset + loaded) as i32;
+            let value = match piece_bytes {
+                4 => self.fn_builder.ins().uload32(MemFlags::new(), address, piece_offset),
+                2 => self.fn_builder.ins().uload16(register, MemFlags::new(), address, piece_offset),
+                _ => self.fn_builder.ins().uload8(register, MemFlags::new(), address, piece_offset),
+            };
+            let value = self.fn_builder.ins().ishl_imm(value, (loaded * 8) as i64);
+            result = self.fn_builder.ins().bor(result, value);
+            loaded += piece_bytes;
+        }
+
+        result
+    }
+
+    /// Stores registers holding a value (one eightbyte each) into memory
+    fn store_registers(&mut self, address: Value, values: &[Value]) {
+        for (index, value) in values.iter().enumerate() {
+            let offset = (index * abi::EIGHTBYTE) as i32;
+            self.fn_builder.ins().store(MemFlags::new(), *value, address, offset);
+        }
+    }
+
     fn create_explicit_stack_allocation(&mut self, size: u32) -> StackSlot {
         self.fn_builder.create_stack_slot(StackSlotData {
             kind: StackSlotKind::ExplicitSlot,
@@ -296,4 +798,76 @@ impl<'input> FunctionTranslator<'input> {
             offset: None,
         })
     }
-}
\ No newline at end of file
+}
+
+
+/// Whether an expression's value is held in memory of its own (rather than being a place, such as `x.y`)
+fn is_temporary(expression: &ast::Expression) -> bool {
+    matches!(expression, ast::Expression::FieldConstructor { .. } | ast::Expression::FunctionCall { .. })
+}
+
+/// Finds the variables whose address is taken (`&x`, `&mut x.y`, ..)
+fn collect_address_taken_block(block: &ast::BlockExpression, bindings: &mut HashSet<ast::BindingId>) {
+    for statement in &block.block.item {
+        match &statement.item {
+            ast::Statement::Let { value, .. } => {
+                if let Some(value) = value {
+                    collect_address_taken(value, bindings);
+                }
+            }
+
+            ast::Statement::Assign { lhs, expression, .. } => {
+                collect_address_taken(lhs, bindings);
+                collect_address_taken(expression, bindings);
+            }
+
+            ast::Statement::Return { expression }
+            | ast::Statement::ImplicitReturn { expression, .. }
+            | ast::Statement::Expression(expression) => collect_address_taken(expression, bindings),
+        }
+    }
+}
+
+fn collect_address_taken(expression: &ast::Expression, bindings: &mut HashSet<ast::BindingId>) {
+    match expression {
+        ast::Expression::UnaryExpression { op, expr, .. } => {
+            if let ast::UnaryOp::Reference { .. } = op.item {
+                // `&x.y` takes the address of (part of) `x`
+                let mut place = &expr.item;
+                while let ast::Expression::FieldAccess { base_expr, .. } = place {
+                    place = &base_expr.item;
+                }
+
+                if let ast::Expression::Ident { binding: Some(binding), .. } = place {
+                    bindings.insert(*binding);
+                }
+            }
+
+            collect_address_taken(expr, bindings);
+        }
+
+        ast::Expression::BinaryExpression { lhs, rhs, .. } => {
+            collect_address_taken(lhs, bindings);
+            collect_address_taken(rhs, bindings);
+        }
+
+        ast::Expression::FieldConstructor { fields, .. } => {
+            for field in fields.values() {
+                collect_address_taken(field, bindings);
+            }
+        }
+
+        ast::Expression::FieldAccess { base_expr, .. } => collect_address_taken(base_expr, bindings),
+
+        ast::Expression::FunctionCall { inputs, .. } => {
+            for input in inputs {
+                collect_address_taken(input, bindings);
+            }
+        }
+
+        ast::Expression::Block(block) => collect_address_taken_block(block, bindings),
+
+        ast::Expression::Literal { .. }
+        | ast::Expression::Ident { .. } => {}
+    }
+}
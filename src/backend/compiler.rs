@@ -0,0 +1,359 @@
+// Drives code generation for a whole program into any Cranelift `Module`.
+//
+// The JIT (`jit.rs`) compiles into a `SimpleJITModule`, while `object.rs` compiles into an
+// `ObjectModule` for some (possibly foreign) target. Everything up to linking is shared.
+
+use crate::frontend::parse::ast;
+use crate::frontend::validate::context::Context as ValidationContext;
+use crate::frontend::validate::types::Type as CompilerType;
+use crate::frontend::validate::FunctionDefinition;
+use crate::backend::codegen::FunctionTranslator;
+use crate::backend::abi::{self, ArgumentClass, ArgumentRegisters, CallingConvention};
+use crate::backend::debug::FunctionDebugInfo;
+use crate::backend::trap::{TrapSite, TrapSiteSink};
+
+use cranelift::prelude::*;
+use cranelift_module::{Module, Linkage, FuncId};
+
+use std::collections::HashMap;
+
+pub(crate) struct ModuleCompiler<M: Module> {
+    // FIXME: How is `Context` related to functions?
+    fn_builder_context: FunctionBuilderContext,
+    fn_context: codegen::Context,
+
+    pub(crate) module: M,
+
+    // TODO: Store additional information such as (id, is_defined, param_count, etc.)
+    pub(crate) functions: HashMap<String, FuncId>,
+
+    /// Target architecture's pointer type
+    pointer_type: Type,
+
+    /// How compiled (and extern) functions are called
+    pub(crate) calling_convention: CallingConvention,
+
+    /// Whether IR is simplified before code generation (see `CompileOptions::preopt`)
+    pub(crate) preopt: bool,
+
+    /// Linkage of compiled functions. Object files export them, so that they can be linked against.
+    pub(crate) linkage: Linkage,
+
+    /// Whether calls between compiled functions may be direct (PC-relative), rather than through an absolute address
+    pub(crate) colocated_calls: bool,
+
+    /// Prepended to the symbol of every compiled (non-extern) function.  
+    /// Executables need this, as their entry point is also called `main`.
+    pub(crate) symbol_prefix: String,
+
+    /// Whether the IR of each function is kept (see `clif`)
+    pub(crate) emit_clif: bool,
+    /// IR of each compiled function, before and after optimization
+    pub(crate) clif: HashMap<String, String>,
+    /// Size of each compiled function's machine code, in bytes
+    pub(crate) code_sizes: HashMap<String, u32>,
+
+    /// Whether source locations and variables are kept (see `debug_info`)
+    pub(crate) emit_debug_info: bool,
+    /// Debug info of each compiled function
+    pub(crate) debug_info: Vec<FunctionDebugInfo>,
+
+    /// Instructions which may trap, within each compiled function
+    pub(crate) trap_sites: HashMap<String, Vec<TrapSite>>,
+
+    /// Address of the fuel counter, if compiled functions are metered (see `JitterContextBuilder::with_fuel`)
+    pub(crate) fuel_address: Option<usize>,
+}
+
+impl<M: Module> ModuleCompiler<M> {
+    pub fn new(module: M) -> Self {
+        let pointer_type = module.target_config().pointer_type();
+        // crate::log!("Pointer type is: {}\n", pointer_type);
+
+        Self {
+            fn_builder_context: FunctionBuilderContext::new(),
+            fn_context: module.make_context(),
+            module,
+            functions: HashMap::new(),
+            pointer_type,
+            calling_convention: CallingConvention::default(),
+            preopt: true,
+            linkage: Linkage::Local,
+            colocated_calls: true,
+            symbol_prefix: String::new(),
+            emit_clif: false,
+            clif: HashMap::new(),
+            code_sizes: HashMap::new(),
+            emit_debug_info: false,
+            debug_info: Vec::new(),
+            trap_sites: HashMap::new(),
+            fuel_address: None,
+        }
+    }
+
+    /// Declares and defines every function (linking is left to the caller).  
+    /// All code represented by the validation context is assumed to be valid.
+    pub fn compile(&mut self, validation_context: &ValidationContext) -> Result<(), String> {
+        if self.calling_convention == CallingConvention::C {
+            let triple = self.module.isa().triple();
+            if triple.architecture != target_lexicon::Architecture::X86_64 || triple.operating_system == target_lexicon::OperatingSystem::Windows {
+                return Err(format!("The C calling convention is only supported on System V x86-64 targets (not `{}`)", triple));
+            }
+        }
+
+        // Begin by forward-declaring all possible functions
+        // TODO: Do same for all constant values
+        for (name, definition) in &validation_context.functions.functions {
+            self.forward_declare_function(name, definition, validation_context)?;
+        }
+
+        // Translate everything to IR
+        // NOTE: Structs define layouts. They do not need translation.
+        //       Similarly, ExternBlocks are accounted for as functions
+        for function in &validation_context.ast.functions {
+            self.generate_function(function, validation_context)?;
+        }
+        for trait_ in &validation_context.ast.traits {
+            todo!()
+        }
+        for impl_ in &validation_context.ast.impls {
+            todo!()
+        }
+
+        Ok(())
+    }
+
+    fn forward_declare_function(&mut self, name: &str, definition: &FunctionDefinition, validation_context: &ValidationContext) -> Result<FuncId, String> {
+        if self.functions.contains_key(name) {
+            return Err(format!("Function `{}` was already defined", name));
+        }
+
+        let parameters = definition.parameters.iter()
+            .map(|(_, ty, _)| ty)
+            .collect::<Vec<_>>();
+        let signature = self.make_signature(name, &parameters, &definition.return_type, validation_context)?;
+
+        let (symbol, linkage) = if definition.is_extern {
+            (name.to_owned(), Linkage::Import)
+        } else {
+            (format!("{}{}", self.symbol_prefix, name), self.linkage)
+        };
+
+        let func_id = self.module.declare_function(&symbol, linkage, &signature)
+            .map_err(|e| e.to_string())?;
+
+        // TODO: Store additional information in `functions`
+        self.functions.insert(name.to_string(), func_id);
+
+        Ok(func_id)
+    }
+
+    /// Jitter's calling convention:
+    /// - Returned values are copied into a buffer provided by the caller (the first parameter, if any)
+    /// - All other inputs are the addresses of the values being passed
+    ///
+    /// See `abi.rs` for the C calling convention
+    fn make_signature(&self, name: &str, parameters: &[&CompilerType], return_type: &CompilerType, validation_context: &ValidationContext) -> Result<codegen::ir::Signature, String> {
+        let mut signature = self.module.make_signature();
+
+        match self.calling_convention {
+            CallingConvention::Jitter => {
+                // Unit return type -> nothing returned
+                // NOTE: The buffer is an ordinary pointer, as not every backend supports `sret` parameters (e.g. AArch64)
+                if !return_type.is_unit() {
+                    signature.params.push(AbiParam::new(self.pointer_type));
+                }
+
+                for _ in parameters {
+                    signature.params.push(AbiParam::new(self.pointer_type));
+                }
+            }
+
+            CallingConvention::C => {
+                let struct_return = AbiParam::special(self.pointer_type, codegen::ir::ArgumentPurpose::StructReturn);
+                let mut available = ArgumentRegisters::new();
+
+                match abi::classify(return_type, validation_context) {
+                    ArgumentClass::Registers(registers) => signature.returns.extend(registers.into_iter().map(|register| abi::abi_param(register, return_type))),
+                    ArgumentClass::Memory => {
+                        available.take(&[self.pointer_type]);
+                        signature.params.push(struct_return);
+                    }
+                }
+
+                for ty in parameters {
+                    match abi::classify(ty, validation_context) {
+                        // Single registers go on the stack by themselves (as Cranelift does), but a value
+                        // split across registers must go there whole, which Cranelift can't do
+                        ArgumentClass::Registers(registers) if !available.take(&registers) && registers.len() > 1 => return Err(format!(
+                            "Function `{}` takes a `{}` by value after the argument registers run out, so the C calling convention passes it on the stack. \
+                            Only structs passed in registers are supported by value. Consider taking a `&{}` instead, or moving it before other parameters.",
+                            name, ty, ty
+                        )),
+
+                        ArgumentClass::Registers(registers) => signature.params.extend(registers.into_iter().map(|register| abi::abi_param(register, ty))),
+
+                        // FIXME: Cranelift can't copy arguments onto the stack (by value) yet
+                        ArgumentClass::Memory => return Err(format!(
+                            "Function `{}` takes a `{}` ({} bytes) by value, which the C calling convention passes on the stack. \
+                            Only structs of up to 16 bytes (without unaligned fields) are supported by value, although larger ones can be returned. \
+                            Consider taking a `&{}` instead.",
+                            name, ty, validation_context.types.size_of(ty), ty
+                        )),
+                    }
+                }
+            }
+        }
+
+        Ok(signature)
+    }
+
+    // TODO: Consider moving this into codegen.rs to put all codegen in one place
+    fn generate_function(&mut self, function: &ast::Function, validation_context: &ValidationContext) -> Result<(), String> {
+        let func_id = self.functions.get(&function.prototype.name)
+            .ok_or(format!("Attempted to translate an unregistered function: {}", function.prototype.name))?;
+
+        // Same signature as was declared
+        self.fn_context.func.signature = self.module.declarations().get_function_decl(*func_id).signature.clone();
+        if self.emit_debug_info {
+            self.fn_context.func.collect_debug_info();
+        }
+
+        let mut function_translator = FunctionTranslator::new(
+            &self.pointer_type,
+            FunctionBuilder::new(&mut self.fn_context.func, &mut self.fn_builder_context),
+            &mut self.module,
+            &self.functions,
+            validation_context,
+            self.calling_convention,
+            self.fuel_address,
+        ).with_colocated_calls(self.colocated_calls);
+
+        // Generates IR, then finalizes the function, making it ready for the module
+        function_translator.translate_function(function)?;
+        let (spans, variables) = function_translator.into_debug_info();
+
+        let unoptimized = if self.emit_clif {
+            Some(self.fn_context.func.display(self.module.isa()).to_string())
+        } else {
+            None
+        };
+
+        // Performs constant folding (I'm not sure what else is done elsewhere)
+        if self.preopt {
+            cranelift_preopt::optimize(&mut self.fn_context, self.module.isa()).expect("Optimize");
+        }
+
+        // Define the function
+        let mut trap_sink = TrapSiteSink::default();
+        let compiled = self.module
+            .define_function(*func_id, &mut self.fn_context, &mut trap_sink)
+            .map_err(|e| e.to_string())?;
+
+        self.code_sizes.insert(function.prototype.name.clone(), compiled.size);
+
+        // Source locations are indices into `spans` (see `FunctionTranslator::set_source_location`)
+        let trap_sites = trap_sink.sites.into_iter()
+            .map(|(offset, source_location, code)| TrapSite {
+                offset,
+                kind: code.into(),
+                span: if source_location.is_default() { None } else { spans.get(source_location.bits() as usize).copied() },
+            })
+            .collect();
+        self.trap_sites.insert(function.prototype.name.clone(), trap_sites);
+
+        if self.emit_debug_info {
+            let debug_info = FunctionDebugInfo::new(&function.prototype.name, &self.fn_context, self.module.isa(), spans, variables)?;
+            self.debug_info.push(debug_info);
+        }
+
+        // Compiling rewrites the IR in place (optimized, legalized, and with registers assigned)
+        if let Some(unoptimized) = unoptimized {
+            let optimized = self.fn_context.func.display(self.module.isa()).to_string();
+            let name = &function.prototype.name;
+            self.clif.insert(
+                name.clone(),
+                format!("; `{}` before optimization\n{}\n; `{}` after optimization\n{}", name, unoptimized, name, optimized),
+            );
+        }
+
+        // Reset the context for the next function
+        self.module.clear_context(&mut self.fn_context);
+
+        Ok(())
+    }
+
+    /// Defines a C `main` (`fn() -> i32`) which calls the Jitter `main`, returning its value as the exit code.  
+    /// Jitter's `main` must take no parameters, and return either `()` (exit code 0) or an integer.
+    pub fn define_entry_point(&mut self, validation_context: &ValidationContext) -> Result<(), String> {
+        let definition = validation_context.functions.functions.get("main")
+            .filter(|definition| !definition.is_extern)
+            .ok_or("No `main` function to call")?;
+
+        let return_type = &definition.return_type;
+        let returns_integer = return_type.is_integer() && !matches!(return_type, CompilerType::i128 | CompilerType::u128);
+        if !definition.parameters.is_empty() || !(return_type.is_unit() || returns_integer) {
+            return Err(format!(
+                "`main` must take no parameters and return either `()` or an integer (found `{}` parameter(s) returning `{}`)",
+                definition.parameters.len(), return_type
+            ));
+        }
+
+        let main_id = self.functions["main"];
+        let pointer_type = self.pointer_type;
+
+        // The platform's default calling convention, as expected by the C runtime
+        let mut signature = self.module.make_signature();
+        signature.returns.push(AbiParam::new(types::I32));
+
+        let entry_id = self.module.declare_function("main", Linkage::Export, &signature)
+            .map_err(|e| e.to_string())?;
+        self.fn_context.func.signature = signature;
+
+        let mut builder = FunctionBuilder::new(&mut self.fn_context.func, &mut self.fn_builder_context);
+        let block = builder.create_block();
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+
+        let callee = self.module.declare_func_in_func(main_id, builder.func);
+
+        let exit_code = if return_type.is_unit() {
+            builder.ins().call(callee, &[]);
+            builder.ins().iconst(types::I32, 0)
+        } else {
+            let ir_type = return_type.ir_type(&pointer_type);
+
+            let value = match self.calling_convention {
+                CallingConvention::Jitter => {
+                    let slot = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, ir_type.bytes()));
+                    let address = builder.ins().stack_addr(pointer_type, slot, 0);
+
+                    builder.ins().call(callee, &[address]);
+                    builder.ins().stack_load(ir_type, slot, 0)
+                }
+
+                CallingConvention::C => {
+                    let call = builder.ins().call(callee, &[]);
+                    builder.inst_results(call)[0]
+                }
+            };
+
+            match ir_type.bits() {
+                32 => value,
+                bits if bits > 32 => builder.ins().ireduce(types::I32, value),
+                _ if return_type.is_signed_integer() => builder.ins().sextend(types::I32, value),
+                _ => builder.ins().uextend(types::I32, value),
+            }
+        };
+
+        builder.ins().return_(&[exit_code]);
+        builder.finalize();
+
+        self.module
+            .define_function(entry_id, &mut self.fn_context, &mut codegen::binemit::NullTrapSink{})
+            .map_err(|e| e.to_string())?;
+        self.module.clear_context(&mut self.fn_context);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+// Debug information for JIT-compiled code.
+//
+// While translating, each statement's instructions are tagged with a `SourceLoc` (an index into
+// the function's spans) and scalar variables with a `ValueLabel` (see `FunctionTranslator`).
+// Once compiled, these become a DWARF line table and variable locations, which are wrapped in an
+// in-memory ELF image and registered with debuggers through the GDB JIT interface:
+// https://sourceware.org/gdb/current/onlinedocs/gdb/JIT-Interface.html
+
+use crate::frontend::validate::types::Type as CompilerType;
+use crate::source_map::{self, SourceMap};
+use crate::Span;
+
+use cranelift::codegen::{self, ir::{ValueLabel, ValueLoc}, isa::TargetIsa};
+use gimli::write::{self, Address, AttributeValue, DwarfUnit, EndianVec, Expression, LineProgram, LineString, Location, LocationList, Range, RangeList, Sections};
+use object::{SectionKind, SymbolFlags, SymbolKind, SymbolScope};
+use object::write::{Object, Symbol, SymbolSection};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A scalar variable held in SSA values (labelled with `label`)
+pub(crate) struct Variable {
+    pub label: ValueLabel,
+    pub name: String,
+    pub ty: CompilerType,
+    /// Size in bytes
+    pub size: u32,
+}
+
+/// Code offsets (start, end) between which a variable is held in a (DWARF) register
+type RegisterRange = (u32, u32, u16);
+
+/// Source information for a compiled function, with code offsets relative to its start
+pub(crate) struct FunctionDebugInfo {
+    pub name: String,
+    /// Where the function is declared
+    pub span: Span,
+    /// Code offset at which each statement begins
+    pub lines: Vec<(u32, Span)>,
+    /// Variables along with the registers holding them
+    pub variables: Vec<(Variable, Vec<RegisterRange>)>,
+}
+
+impl FunctionDebugInfo {
+    /// Gathers debug info from a function which was just compiled (and is still held by `context`).
+    /// `spans` are indexed by each instruction's `SourceLoc`.
+    pub fn new(name: &str, context: &codegen::Context, isa: &dyn TargetIsa, spans: Vec<Span>, variables: Vec<Variable>) -> Result<Self, String> {
+        // FIXME: The new (`MachBackend`) backends record code offsets differently
+        if isa.get_mach_backend().is_some() {
+            return Err(format!("Debug info is not supported for `{}`", isa.triple()));
+        }
+
+        let function = &context.func;
+        let encoding_info = isa.encoding_info();
+
+        let mut lines: Vec<(u32, Span)> = Vec::new();
+        for block in function.layout.blocks() {
+            for (offset, instruction, _size) in function.inst_offsets(block, &encoding_info) {
+                let source_location = function.srclocs[instruction];
+                if source_location.is_default() {
+                    continue;
+                }
+
+                // Only the first instruction of each statement is needed
+                let span = spans[source_location.bits() as usize];
+                if lines.last().is_none_or(|(_, last)| (last.file, last.start_line) != (span.file, span.start_line)) {
+                    lines.push((offset, span));
+                }
+            }
+        }
+
+        let ranges = context.build_value_labels_ranges(isa)
+            .map_err(|error| error.to_string())?;
+
+        let variables = variables.into_iter()
+            .map(|variable| {
+                // FIXME: Values spilled onto the stack are not described
+                let registers = ranges.get(&variable.label)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|range| match range.loc {
+                        ValueLoc::Reg(register) => isa.map_dwarf_register(register).ok()
+                            .map(|register| (range.start, range.end, register)),
+                        _ => None,
+                    })
+                    .collect();
+
+                (variable, registers)
+            })
+            .collect();
+
+        Ok(Self {
+            name: name.to_owned(),
+            span: spans[0],
+            lines,
+            variables,
+        })
+    }
+}
+
+/// Writes an ELF image describing functions compiled at the given addresses, as (debug info, address, size)
+pub(crate) fn write_image(functions: &[(&FunctionDebugInfo, u64, u32)], source_map: &SourceMap, triple: &target_lexicon::Triple) -> Result<Vec<u8>, String> {
+    let architecture = match triple.architecture {
+        target_lexicon::Architecture::X86_64 => object::Architecture::X86_64,
+        target_lexicon::Architecture::Aarch64(_) => object::Architecture::Aarch64,
+        _ => return Err(format!("Debug info is not supported for `{}`", triple)),
+    };
+
+    let encoding = gimli::Encoding {
+        format: gimli::Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+
+    let comp_dir = std::env::current_dir()
+        .map(|directory| directory.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let comp_name = source_map.path(source_map::FileId(0)).to_owned();
+
+    // Line table
+    let mut line_program = LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        LineString::String(comp_dir.clone().into_bytes()),
+        LineString::String(comp_name.clone().into_bytes()),
+        None,
+    );
+
+    let mut files = HashMap::new();
+    let mut line_file = |line_program: &mut LineProgram, file: source_map::FileId| *files.entry(file).or_insert_with(|| {
+        let path = std::path::Path::new(source_map.path(file));
+        let directory = match path.parent().map(|parent| parent.to_string_lossy().into_owned()) {
+            Some(parent) if !parent.is_empty() => line_program.add_directory(LineString::String(parent.into_bytes())),
+            _ => line_program.default_directory(),
+        };
+
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        line_program.add_file(LineString::String(name.into_bytes()), directory, None)
+    });
+
+    let mut declaration_files = Vec::new();
+    for (function, address, size) in functions {
+        declaration_files.push(line_file(&mut line_program, function.span.file));
+
+        line_program.begin_sequence(Some(Address::Constant(*address)));
+        for (offset, span) in &function.lines {
+            let file = line_file(&mut line_program, span.file);
+
+            let row = line_program.row();
+            row.address_offset = *offset as u64;
+            row.file = file;
+            row.line = span.start_line as u64;
+            row.column = span.start_column as u64;
+            line_program.generate_row();
+        }
+        line_program.end_sequence(*size as u64);
+    }
+
+    let mut dwarf = DwarfUnit::new(encoding);
+    dwarf.unit.line_program = line_program;
+
+    // Compile unit
+    let ranges = functions.iter()
+        .map(|(_, address, size)| Range::StartLength {
+            begin: Address::Constant(*address),
+            length: *size as u64,
+        })
+        .collect();
+    let ranges = dwarf.unit.ranges.add(RangeList(ranges));
+
+    let root = dwarf.unit.root();
+    let unit = dwarf.unit.get_mut(root);
+    unit.set(gimli::DW_AT_producer, AttributeValue::String(b"jitter".to_vec()));
+    unit.set(gimli::DW_AT_name, AttributeValue::String(comp_name.into_bytes()));
+    unit.set(gimli::DW_AT_comp_dir, AttributeValue::String(comp_dir.into_bytes()));
+    unit.set(gimli::DW_AT_low_pc, AttributeValue::Address(Address::Constant(0)));
+    unit.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+
+    // Functions, and their variables
+    let mut base_types = HashMap::new();
+    for ((function, address, size), file) in functions.iter().zip(declaration_files) {
+        let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(subprogram);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(function.name.clone().into_bytes()));
+        entry.set(gimli::DW_AT_external, AttributeValue::Flag(true));
+        entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(Address::Constant(*address)));
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(*size as u64));
+        entry.set(gimli::DW_AT_decl_file, AttributeValue::FileIndex(Some(file)));
+        entry.set(gimli::DW_AT_decl_line, AttributeValue::Udata(function.span.start_line as u64));
+
+        for (variable, registers) in &function.variables {
+            let base_type = *base_types.entry(variable.ty.to_string()).or_insert_with(|| {
+                let encoding = match &variable.ty {
+                    CompilerType::bool => gimli::DW_ATE_boolean,
+                    ty if ty.is_float() => gimli::DW_ATE_float,
+                    ty if ty.is_signed_integer() => gimli::DW_ATE_signed,
+                    ty if ty.is_reference() => gimli::DW_ATE_address,
+                    _ => gimli::DW_ATE_unsigned,
+                };
+
+                let base_type = dwarf.unit.add(root, gimli::DW_TAG_base_type);
+                let entry = dwarf.unit.get_mut(base_type);
+                entry.set(gimli::DW_AT_name, AttributeValue::String(variable.ty.to_string().into_bytes()));
+                entry.set(gimli::DW_AT_encoding, AttributeValue::Encoding(encoding));
+                entry.set(gimli::DW_AT_byte_size, AttributeValue::Data1(variable.size as u8));
+                base_type
+            });
+
+            let locations = registers.iter()
+                .map(|(start, end, register)| {
+                    let mut expression = Expression::new();
+                    expression.op_reg(gimli::Register(*register));
+
+                    Location::StartEnd {
+                        begin: Address::Constant(address + *start as u64),
+                        end: Address::Constant(address + *end as u64),
+                        data: expression,
+                    }
+                })
+                .collect();
+            let locations = dwarf.unit.locations.add(LocationList(locations));
+
+            let entry = dwarf.unit.add(subprogram, gimli::DW_TAG_variable);
+            let entry = dwarf.unit.get_mut(entry);
+            entry.set(gimli::DW_AT_name, AttributeValue::String(variable.name.clone().into_bytes()));
+            entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(base_type));
+            entry.set(gimli::DW_AT_location, AttributeValue::LocationListRef(locations));
+        }
+    }
+
+    let mut sections = Sections::new(EndianVec::new(gimli::RunTimeEndian::Little));
+    dwarf.write(&mut sections)
+        .map_err(|error| error.to_string())?;
+
+    // ELF image holding the DWARF sections, along with a symbol for each function
+    let mut image = Object::new(object::BinaryFormat::Elf, architecture, object::Endianness::Little);
+    sections.for_each(|id, data| -> Result<(), write::Error> {
+        if !data.slice().is_empty() {
+            let section = image.add_section(Vec::new(), id.name().as_bytes().to_vec(), SectionKind::Debug);
+            image.append_section_data(section, data.slice(), 1);
+        }
+
+        Ok(())
+    }).map_err(|error| error.to_string())?;
+
+    for (function, address, size) in functions {
+        image.add_symbol(Symbol {
+            name: function.name.clone().into_bytes(),
+            value: *address,
+            size: *size as u64,
+            kind: SymbolKind::Text,
+            scope: SymbolScope::Compilation,
+            weak: false,
+            section: SymbolSection::Absolute,
+            flags: SymbolFlags::None,
+        });
+    }
+
+    image.write()
+        .map_err(|error| error.to_string())
+}
+
+
+//////////// GDB JIT Interface ////////////
+
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *mut JitCodeEntry,
+    prev_entry: *mut JitCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JitCodeEntry,
+    first_entry: *mut JitCodeEntry,
+}
+
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+// Debuggers break on this function, then read the descriptor
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keeps the (otherwise empty) function from being optimized out
+    unsafe { std::ptr::read_volatile(&0u8) };
+}
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: JitDescriptor = JitDescriptor {
+    version: 1,
+    action_flag: 0,
+    relevant_entry: std::ptr::null_mut(),
+    first_entry: std::ptr::null_mut(),
+};
+
+/// Guards `__jit_debug_descriptor` (shared by every `JitterContext`)
+static DESCRIPTOR_LOCK: Mutex<()> = Mutex::new(());
+
+/// A debug image registered with debuggers. It is unregistered when dropped.
+pub(crate) struct GdbJitRegistration {
+    entry: Box<JitCodeEntry>,
+    image: Vec<u8>,
+}
+
+impl GdbJitRegistration {
+    pub fn register(image: Vec<u8>) -> Self {
+        let mut entry = Box::new(JitCodeEntry {
+            next_entry: std::ptr::null_mut(),
+            prev_entry: std::ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        });
+
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        unsafe {
+            let descriptor = std::ptr::addr_of_mut!(__jit_debug_descriptor);
+            let entry_pointer: *mut JitCodeEntry = &mut *entry;
+
+            // Entries form a doubly linked list, with new ones at the front
+            entry.next_entry = (*descriptor).first_entry;
+            if !entry.next_entry.is_null() {
+                (*entry.next_entry).prev_entry = entry_pointer;
+            }
+
+            (*descriptor).first_entry = entry_pointer;
+            (*descriptor).relevant_entry = entry_pointer;
+            (*descriptor).action_flag = JIT_REGISTER_FN;
+            __jit_debug_register_code();
+        }
+
+        Self {
+            entry,
+            image,
+        }
+    }
+
+    /// The registered ELF image
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+}
+
+impl Drop for GdbJitRegistration {
+    fn drop(&mut self) {
+        let _lock = DESCRIPTOR_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        unsafe {
+            let descriptor = std::ptr::addr_of_mut!(__jit_debug_descriptor);
+            let entry_pointer: *mut JitCodeEntry = &mut *self.entry;
+
+            if self.entry.prev_entry.is_null() {
+                (*descriptor).first_entry = self.entry.next_entry;
+            } else {
+                (*self.entry.prev_entry).next_entry = self.entry.next_entry;
+            }
+            if !self.entry.next_entry.is_null() {
+                (*self.entry.next_entry).prev_entry = self.entry.prev_entry;
+            }
+
+            (*descriptor).relevant_entry = entry_pointer;
+            (*descriptor).action_flag = JIT_UNREGISTER_FN;
+            __jit_debug_register_code();
+        }
+    }
+}
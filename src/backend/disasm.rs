@@ -0,0 +1,36 @@
+// Disassembles compiled machine code, for inspecting what was generated.
+// See `JitterContextBuilder::emit_disasm` (only available with the `disasm` feature).
+
+use capstone::prelude::*;
+use target_lexicon::{Architecture, Triple};
+
+/// Disassembles `code` (located at `address`), one instruction per line
+pub(crate) fn disassemble(triple: &Triple, code: &[u8], address: u64) -> Result<String, String> {
+    let capstone = match triple.architecture {
+        Architecture::X86_64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .build(),
+
+        Architecture::Aarch64(_) => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .build(),
+
+        _ => return Err(format!("Disassembly is not supported for `{}`", triple)),
+    }.map_err(|error| error.to_string())?;
+
+    let instructions = capstone.disasm_all(code, address)
+        .map_err(|error| error.to_string())?;
+
+    let lines = instructions.iter()
+        .map(|instruction| format!(
+            "{:#x}: {} {}",
+            instruction.address(),
+            instruction.mnemonic().unwrap_or("?"),
+            instruction.op_str().unwrap_or(""),
+        ).trim_end().to_owned())
+        .collect::<Vec<String>>();
+
+    Ok(lines.join("\n"))
+}
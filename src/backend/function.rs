@@ -0,0 +1,276 @@
+// Typed access to compiled functions.
+//
+// Compiled functions are untyped pointers underneath. Rather than transmuting them blindly
+// (where a wrong signature is undefined behaviour), the Rust types a function is requested
+// with are first checked against the signature it was compiled with.
+
+use std::marker::PhantomData;
+use std::mem::{MaybeUninit, transmute};
+
+use crate::frontend::validate::types::Type;
+use crate::layout::StructLayout;
+use super::abi::CallingConvention;
+use super::jit::JitterContext;
+use super::trap::{catch_traps, JitterTrap, TrapKind};
+
+/// Rust types which have an equivalent Jitter type
+pub trait JitterType {
+    fn jitter_type() -> Type;
+
+    /// Layout of a struct type (or of the struct a reference points to), as compared with
+    /// the Jitter struct of the same name. `JitterLayout!` structs provide this.
+    fn layout() -> Option<StructLayout> {
+        None
+    }
+}
+
+macro_rules! impl_jitter_type {
+    ( $($rust:ty => $jitter:expr),+ $(,)? ) => {
+        $(
+            impl JitterType for $rust {
+                fn jitter_type() -> Type {
+                    $jitter
+                }
+            }
+        )+
+    };
+}
+
+impl_jitter_type! {
+    u8 => Type::u8,
+    u16 => Type::u16,
+    u32 => Type::u32,
+    u64 => Type::u64,
+    u128 => Type::u128,
+    usize => Type::usize,
+    i8 => Type::i8,
+    i16 => Type::i16,
+    i32 => Type::i32,
+    i64 => Type::i64,
+    i128 => Type::i128,
+    isize => Type::isize,
+    f32 => Type::f32,
+    f64 => Type::f64,
+    bool => Type::bool,
+    () => Type::Unit,
+}
+
+impl<T: JitterType> JitterType for &T {
+    fn jitter_type() -> Type {
+        Type::Reference {
+            ty: Box::new(T::jitter_type()),
+            mutable: false,
+        }
+    }
+
+    fn layout() -> Option<StructLayout> {
+        T::layout()
+    }
+}
+
+impl<T: JitterType> JitterType for &mut T {
+    fn jitter_type() -> Type {
+        Type::Reference {
+            ty: Box::new(T::jitter_type()),
+            mutable: true,
+        }
+    }
+
+    fn layout() -> Option<StructLayout> {
+        T::layout()
+    }
+}
+
+/// Parameter lists, written as tuples of `JitterType`s (`()`, `(i32,)`, `(i32, Vec3)`, ..)
+pub trait JitterParams {
+    fn jitter_types() -> Vec<Type>;
+
+    /// Layouts of the struct parameters (see `JitterType::layout`)
+    fn layouts() -> Vec<StructLayout>;
+
+    /// Calls a compiled function with these arguments.
+    ///
+    /// # Safety
+    /// `pointer` must be a compiled function whose signature matches `fn(Self..) -> R`
+    #[doc(hidden)]
+    unsafe fn call<R>(self, pointer: *const u8, calling_convention: CallingConvention, returns_value: bool) -> R;
+}
+
+macro_rules! impl_jitter_params {
+    ( $($param:ident),* ) => {
+        impl<$($param: JitterType),*> JitterParams for ($($param,)*) {
+            fn jitter_types() -> Vec<Type> {
+                vec![$($param::jitter_type()),*]
+            }
+
+            fn layouts() -> Vec<StructLayout> {
+                let layouts: Vec<Option<StructLayout>> = vec![$($param::layout()),*];
+                layouts.into_iter().flatten().collect()
+            }
+
+            // Under Jitter's calling convention, arguments are passed by address, so are kept alive
+            // (in `self`) for the duration of the call. The return value is copied into `output`.
+            #[allow(non_snake_case)]
+            unsafe fn call<R>(self, pointer: *const u8, calling_convention: CallingConvention, returns_value: bool) -> R {
+                let ($($param,)*) = self;
+
+                if calling_convention == CallingConvention::C {
+                    let function = transmute::<*const u8, extern "C" fn($($param),*) -> R>(pointer);
+                    return function($($param),*);
+                }
+
+                let mut output = MaybeUninit::<R>::uninit();
+
+                if returns_value {
+                    let function = transmute::<*const u8, extern "C" fn(*mut R, $(&$param),*)>(pointer);
+                    function(output.as_mut_ptr(), $(&$param),*);
+                } else {
+                    let function = transmute::<*const u8, extern "C" fn($(&$param),*)>(pointer);
+                    function($(&$param),*);
+                }
+
+                // NOTE: Unit is zero-sized, so needs no initialization
+                output.assume_init()
+            }
+        }
+    };
+}
+
+impl_jitter_params!();
+impl_jitter_params!(A);
+impl_jitter_params!(A, B);
+impl_jitter_params!(A, B, C);
+impl_jitter_params!(A, B, C, D);
+impl_jitter_params!(A, B, C, D, E);
+impl_jitter_params!(A, B, C, D, E, F);
+impl_jitter_params!(A, B, C, D, E, F, G);
+impl_jitter_params!(A, B, C, D, E, F, G, H);
+
+/// Parameter and return types of a function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub parameters: Vec<Type>,
+    pub return_type: Type,
+}
+
+impl Signature {
+    /// Describes how a requested signature differs from this one
+    pub(crate) fn differences(&self, requested: &Signature) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.parameters.len() != requested.parameters.len() {
+            differences.push(format!("takes {} parameters, not {}", self.parameters.len(), requested.parameters.len()));
+        } else {
+            for (index, (actual, requested)) in self.parameters.iter().zip(&requested.parameters).enumerate() {
+                if actual != requested {
+                    differences.push(format!("parameter #{} is `{}`, not `{}`", index, actual, requested));
+                }
+            }
+        }
+
+        if self.return_type != requested.return_type {
+            differences.push(format!("returns `{}`, not `{}`", self.return_type, requested.return_type));
+        }
+
+        differences
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters = self.parameters.iter()
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>();
+
+        write!(f, "fn({})", parameters.join(", "))?;
+        if !self.return_type.is_unit() {
+            write!(f, " -> {}", self.return_type)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A compiled function whose signature is known to be `fn(P..) -> R`.
+/// Obtained through `JitterContext::get_typed_fn`, and borrows the context, as the code lives there.
+///
+/// Usage:
+/// ```ignore
+/// let length = jitter.get_typed_fn::<(Vec3,), f32>("length")?;
+/// let result: f32 = length.call((Vec3 { x: 1.0, y: 2.0, z: 2.0 },));
+/// ```
+pub struct JitterFn<'ctx, P, R> {
+    name: &'ctx str,
+    pointer: *const u8,
+    calling_convention: CallingConvention,
+    /// Unit-returning functions take no return buffer
+    returns_value: bool,
+    /// The context's fuel counter, if its code is metered
+    fuel: Option<*mut u64>,
+    _signature: PhantomData<fn(P) -> R>,
+    _context: PhantomData<&'ctx JitterContext>,
+}
+
+impl<'ctx, P: JitterParams, R: JitterType> JitterFn<'ctx, P, R> {
+    /// NOTE: The signature must already have been checked
+    pub(crate) fn new(name: &'ctx str, pointer: *const u8, calling_convention: CallingConvention, fuel: Option<*mut u64>) -> Self {
+        Self {
+            name,
+            pointer,
+            calling_convention,
+            returns_value: !R::jitter_type().is_unit(),
+            fuel,
+            _signature: PhantomData,
+            _context: PhantomData,
+        }
+    }
+
+    /// Calls the function with a tuple of arguments, returning its result by value.  
+    /// Panics if the function traps (see `try_call`).
+    pub fn call(&self, args: P) -> R {
+        self.try_call(args).unwrap_or_else(|trap| panic!("{}", trap))
+    }
+
+    /// Calls the function, returning an error instead of crashing if it traps
+    /// (e.g. dereferencing an invalid reference).  
+    /// A trap skips the destructors of any Rust frames between this call and the trap
+    /// (such as an extern function which calls back into Jitter).
+    pub fn try_call(&self, args: P) -> Result<R, JitterTrap> {
+        // SAFETY: The signature was checked when the handle was created
+        catch_traps(|| unsafe { args.call(self.pointer, self.calling_convention, self.returns_value) })
+    }
+
+    /// Calls the function with a budget of its own, trapping with `TrapKind::FuelExhausted`
+    /// if it needs more than `fuel`. The context's remaining fuel is left as it was.  
+    /// Errors with `TrapKind::NotMetered` (without calling it) if the context was built without fuel.
+    pub fn try_call_with_fuel(&self, args: P, fuel: u64) -> Result<R, JitterTrap> {
+        let counter = self.fuel.ok_or_else(|| JitterTrap {
+            kind: TrapKind::NotMetered,
+            function: self.name.to_owned(),
+            span: None,
+        })?;
+
+        // SAFETY: The counter lives as long as the context (as does the compiled code)
+        let remaining = unsafe { counter.replace(fuel) };
+        let result = self.try_call(args);
+        unsafe { counter.write(remaining) };
+
+        result
+    }
+
+    /// The compiled code. With `CallingConvention::C`, this is an `extern "C" fn(P..) -> R`.
+    /// Otherwise it takes the address of a return buffer (unless it returns `()`),
+    /// followed by the address of each argument.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.pointer
+    }
+}
+
+// Derives would require `P` and `R` to be `Clone` too
+impl<P, R> Clone for JitterFn<'_, P, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P, R> Copy for JitterFn<'_, P, R> {}
@@ -3,45 +3,79 @@
 // https://github.com/bytecodealliance/wasmtime/blob/main/cranelift/simplejit/examples/simplejit-minimal.rs
 // https://github.com/CraneStation/kaleidoscope-cranelift
 
-use crate::frontend::parse::ast;
 use crate::frontend::validate::context::Context as ValidationContext;
 use crate::frontend::LexerCallback;
-use crate::backend::codegen::FunctionTranslator;
+use crate::backend::compiler::ModuleCompiler;
+use crate::backend::function::{JitterFn, JitterParams, JitterType, Signature};
+use crate::backend::abi::CallingConvention;
+use crate::backend::options::CompileOptions;
+use crate::backend::debug::{self, GdbJitRegistration};
+use crate::backend::trap::TrapRegistration;
+use crate::source_map::SourceMap;
+use crate::layout::{JitterLayout, StructLayout};
 
 use cranelift::prelude::*;
-use cranelift_module::{Module, Linkage, DataContext};
+use cranelift_module::Module;
 use cranelift_simplejit::{SimpleJITBuilder, SimpleJITModule};
 
+use std::cell::Cell;
 use std::collections::HashMap;
 
 
 /// Builder for creating a `JitterContext`. Enables FFI linking
 pub struct JitterContextBuilder<'a> {
-    simple_jit_builder: SimpleJITBuilder,
+    /// Rust functions to link, as (alias, pointer)
+    symbols: Vec<(String, *const u8)>,
+    compile_options: CompileOptions,
     source_path: &'a str,
     extension_path: &'a str,
 
     lexer_callbacks: Vec<LexerCallback<'a>>,
+    /// Rust layouts to check against the compiled structs
+    rust_layouts: Vec<StructLayout>,
+    /// How compiled (and extern) functions are called
+    calling_convention: CallingConvention,
+
+    /// Whether errors are rendered with ANSI colour codes
+    colored_errors: bool,
+    /// Whether errors are reported as JSON lines
+    json_errors: bool,
+
+    /// Whether each function's IR is kept (see `JitterContext::clif_for`)
+    emit_clif: bool,
+    /// Whether each function's machine code is disassembled (see `JitterContext::disasm_for`)
+    #[cfg(feature = "disasm")]
+    emit_disasm: bool,
+    /// Whether compiled code is described to debuggers (see `with_debug_info`)
+    debug_info: bool,
+    /// Whether compiled functions are written to the perf map (see `with_perf_map`)
+    perf_map: bool,
+    /// Whether compiled functions are written to the jitdump (see `with_jitdump`)
+    jitdump: bool,
+    /// Initial fuel, if compiled code is metered (see `with_fuel`)
+    fuel: Option<u64>,
 }
 
-// TODO: Allow optimization settings to be passed in
 // TODO: Accept/determine target ISA
 impl<'a> JitterContextBuilder<'a> {
     pub fn new() -> Self {
-        let mut settings = settings::builder();
-        // TODO: Determine options here
-        settings.set("opt_level", "speed_and_size").expect("Optimization");
-        
-        let isa_builder = isa::lookup(target_lexicon::Triple::host()).expect("isa");
-        let isa = isa_builder.finish(settings::Flags::new(settings));
-
-        let simple_jit_builder = SimpleJITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
-
         Self {
-            simple_jit_builder,
+            symbols: Vec::new(),
+            compile_options: CompileOptions::default(),
             source_path: "",
             extension_path: ".",
             lexer_callbacks: Vec::new(),
+            rust_layouts: Vec::new(),
+            calling_convention: CallingConvention::default(),
+            colored_errors: false,
+            json_errors: false,
+            emit_clif: false,
+            #[cfg(feature = "disasm")]
+            emit_disasm: false,
+            debug_info: false,
+            perf_map: false,
+            jitdump: false,
+            fuel: None,
         }
     }
 
@@ -49,7 +83,13 @@ impl<'a> JitterContextBuilder<'a> {
     /// Usage:  
     /// `context.with_function("function_name", function_name as _)`
     pub fn with_function(mut self, alias: &str, pointer: *const u8) -> Self {
-        self.simple_jit_builder.symbol(alias, pointer);
+        self.symbols.push((alias.to_owned(), pointer));
+        self
+    }
+
+    /// Sets the optimization level and other Cranelift settings (see `CompileOptions`)
+    pub fn with_compile_options(mut self, options: CompileOptions) -> Self {
+        self.compile_options = options;
         self
     }
 
@@ -64,38 +104,132 @@ impl<'a> JitterContextBuilder<'a> {
         self
     }
 
+    /// Checks that the Jitter struct of the same name is laid out like `T` once compiled.  
+    /// See `JitterLayout!`.
+    pub fn with_layout<T: JitterLayout>(mut self) -> Self {
+        self.rust_layouts.push(T::layout());
+        self
+    }
+
+    /// Compiles functions (and calls extern ones) using the given calling convention.  
+    /// With `CallingConvention::C`, compiled functions can be called as `extern "C"` functions.
+    pub fn with_calling_convention(mut self, calling_convention: CallingConvention) -> Self {
+        self.calling_convention = calling_convention;
+        self
+    }
+
     pub fn with_source_path(mut self, path: &'a str) -> Self {
         self.source_path = path;
         self
     }
 
+    /// Renders errors with ANSI colour codes (for terminals). Errors are plain text by default.
+    pub fn with_colored_errors(mut self, colored: bool) -> Self {
+        self.colored_errors = colored;
+        self
+    }
+
+    /// Reports errors as one JSON object per line (see `Diagnostic::to_json`)
+    pub fn with_json_errors(mut self, json: bool) -> Self {
+        self.json_errors = json;
+        self
+    }
+
+    /// Keeps the Cranelift IR of each function, before and after optimization (see `JitterContext::clif_for`)
+    pub fn emit_clif(mut self, emit: bool) -> Self {
+        self.emit_clif = emit;
+        self
+    }
+
+    /// Keeps the disassembled machine code of each function (see `JitterContext::disasm_for`)
+    #[cfg(feature = "disasm")]
+    pub fn emit_disasm(mut self, emit: bool) -> Self {
+        self.emit_disasm = emit;
+        self
+    }
+
+    /// Generates DWARF debug info (line tables and variable locations) for compiled functions,
+    /// and registers it with debuggers through the GDB JIT interface.  
+    /// Breaking within a Jitter function (in gdb) then shows its source. See `JitterContext::debug_image`.
+    pub fn with_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    /// Writes every compiled function to `/tmp/perf-<pid>.map` (see `perf::perf_map_path`),
+    /// so that `perf` names them (as `module::function`) rather than showing unknown addresses.
+    pub fn with_perf_map(mut self, perf_map: bool) -> Self {
+        self.perf_map = perf_map;
+        self
+    }
+
+    /// Writes every compiled function, including its code, to `/tmp/jit-<pid>.dump` (see `perf::jitdump_path`),
+    /// so that `perf annotate` can show its instructions. Record with `perf record -k mono`, then run `perf inject --jit`.
+    pub fn with_jitdump(mut self, jitdump: bool) -> Self {
+        self.jitdump = jitdump;
+        self
+    }
+
+    /// Meters compiled code, so that runaway scripts can be stopped: every function call consumes
+    /// one unit of fuel, and calls trap with `TrapKind::FuelExhausted` once none is left.  
+    /// See `JitterContext::set_fuel` and `JitterFn::try_call_with_fuel`.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
     // TODO: Compile multiple files instead of just one
     //       also allow context without source (include standard library)
     // pub fn add_source_path...
 
     pub fn build(self) -> Result<JitterContext, String> {
-        let mut jit_context = JitterContext::new(self.simple_jit_builder);
+        let isa_builder = isa::lookup(target_lexicon::Triple::host()).map_err(|error| error.to_string())?;
+        let isa = isa_builder.finish(self.compile_options.flags()?);
+
+        let mut simple_jit_builder = SimpleJITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        for (alias, pointer) in self.symbols {
+            simple_jit_builder.symbol(alias, pointer);
+        }
+
+        let mut jit_context = JitterContext::new(simple_jit_builder);
+        jit_context.compiler.preopt = self.compile_options.preopt;
+        jit_context.compiler.calling_convention = self.calling_convention;
+        jit_context.compiler.emit_clif = self.emit_clif;
+        #[cfg(feature = "disasm")]
+        {
+            jit_context.emit_disasm = self.emit_disasm;
+        }
+        jit_context.compiler.emit_debug_info = self.debug_info;
+        jit_context.perf_map = self.perf_map;
+        jit_context.jitdump = self.jitdump;
+
+        // Boxed, as compiled code refers to the counter by its address
+        jit_context.fuel = self.fuel.map(|fuel| Box::new(Cell::new(fuel)));
+        jit_context.compiler.fuel_address = jit_context.fuel.as_ref().map(|fuel| fuel.as_ptr() as usize);
         
         if self.source_path != "" {
-            // Lex
-            let input = &std::fs::read_to_string(self.source_path).expect("Read input");
-            let mut lexer = crate::frontend::lex::Lexer::new(self.source_path.to_owned(), input.to_owned(), true);
-            lexer.parse_callbacks(self.lexer_callbacks);
-
-            let tokens = lexer.lex();
-            // Parse
-            let mut parser = crate::frontend::parse::Parser::new(self.source_path, tokens);
-            parser.set_extension_path(self.extension_path.to_string());
-            let ast = parser.parse_ast(String::from(""));
-            // println!("AST: {:#?}", ast);
-
-            // Analyze
-            let mut validation_context = crate::frontend::validate::context::Context::new();
-            validation_context.validate(ast)?;
+            let (validation_context, warnings, source_map) = crate::frontend::validate_source(
+                self.source_path,
+                self.extension_path,
+                self.lexer_callbacks,
+                jit_context.target(),
+                self.colored_errors,
+                self.json_errors,
+            )?;
+
+            jit_context.warnings = warnings;
+            jit_context.source_map = source_map;
             // Codegen
             jit_context.translate(validation_context)?;
         }
 
+        // Rust and Jitter must agree on the layout of any struct shared between them
+        for rust_layout in &self.rust_layouts {
+            jit_context.struct_layout(&rust_layout.name)
+                .ok_or_else(|| format!("Expected a Jitter struct named `{}` (to match the Rust struct)", rust_layout.name))?
+                .compare(rust_layout)?;
+        }
+
         Ok(jit_context)
     }
 }
@@ -103,19 +237,36 @@ impl<'a> JitterContextBuilder<'a> {
 
 /// Contains all information needed to JIT compile and run the generated code
 pub struct JitterContext {
-    // FIXME: How is `Context` related to functions?
-    fn_builder_context: FunctionBuilderContext,
-    fn_context: codegen::Context,
-    
-    data_context: DataContext,
-
-    module: SimpleJITModule,
-
-    // TODO: Store additional information such as (id, is_defined, param_count, etc.)
-    functions: HashMap<String, cranelift_module::FuncId>,
-
-    /// Target architecture's pointer type
-    pointer_type: Type,
+    compiler: ModuleCompiler<SimpleJITModule>,
+
+    /// Rendered warnings from compiling the source
+    warnings: Vec<String>,
+
+    /// Layouts of every compiled struct
+    struct_layouts: HashMap<String, StructLayout>,
+
+    /// Signatures of compiled (non-extern) functions, for checking typed handles
+    signatures: HashMap<String, Signature>,
+
+    /// Whether compiled functions are disassembled (into `disasm`)
+    #[cfg(feature = "disasm")]
+    emit_disasm: bool,
+    /// Disassembled machine code of each compiled function
+    #[cfg(feature = "disasm")]
+    disasm: HashMap<String, String>,
+
+    /// Source files the compiled code came from (for debug info)
+    source_map: SourceMap,
+    /// Debug info registered with debuggers, if enabled
+    debug_registration: Option<GdbJitRegistration>,
+    /// Whether compiled functions are written to the perf map
+    perf_map: bool,
+    /// Whether compiled functions are written to the jitdump
+    jitdump: bool,
+    /// Trap sites of the compiled functions, so that their traps can be caught
+    trap_registration: Option<TrapRegistration>,
+    /// Fuel left for metered code (decremented by compiled code)
+    fuel: Option<Box<Cell<u64>>>,
 }
 
 impl Default for JitterContext {
@@ -127,138 +278,206 @@ impl Default for JitterContext {
 
 impl JitterContext {
     pub fn new(builder: SimpleJITBuilder) -> Self {
-        let module = SimpleJITModule::new(builder);
-
-        let pointer_type = module.target_config().pointer_type();
-        // crate::log!("Pointer type is: {}\n", pointer_type);
-
         Self {
-            fn_builder_context: FunctionBuilderContext::new(),
-            fn_context: module.make_context(),
-            data_context: DataContext::new(),
-            module,
-            functions: HashMap::new(),
-            pointer_type,
+            compiler: ModuleCompiler::new(SimpleJITModule::new(builder)),
+            warnings: Vec::new(),
+            struct_layouts: HashMap::new(),
+            signatures: HashMap::new(),
+            #[cfg(feature = "disasm")]
+            emit_disasm: false,
+            #[cfg(feature = "disasm")]
+            disasm: HashMap::new(),
+            source_map: SourceMap::new(),
+            debug_registration: None,
+            perf_map: false,
+            jitdump: false,
+            trap_registration: None,
+            fuel: None,
         }
     }
 
-    // TODO: Need a way to verify signature
-    pub fn get_fn(&self, id: &str) -> *const u8 {
-        let func_id = self.functions.get(id).expect("no such function");
-        self.module.get_finalized_function(*func_id)
+    /// Warnings found while compiling the source (formatted like errors)
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
-    // NOTE:
-    // All code represented by the validation context is assumed to be valid
-    pub fn translate(&mut self, validation_context: ValidationContext) -> Result<(), String> {
-        // Begin by forward-declaring all possible functions
-        // TODO: Do same for all constant values
-        for (name, definition) in &validation_context.functions.functions {
-            self.forward_declare_function(name, definition)?;
-        }
+    /// Describes the target being compiled for (used to lay out types)
+    pub fn target(&self) -> crate::frontend::validate::target::TargetInfo {
+        crate::frontend::validate::target::TargetInfo::from_triple(self.compiler.module.isa().triple())
+    }
 
-        // Translate everything to IR
-        // NOTE: Structs define layouts. They do not need translation.
-        //       Similarly, ExternBlocks are accounted for as functions
-        for function in &validation_context.ast.functions {
-            self.generate_function(function, &validation_context)?;
-        }
-        for trait_ in &validation_context.ast.traits {
-            todo!()
-        }
-        for impl_ in &validation_context.ast.impls {
-            todo!()
-        }
+    /// Describes how a compiled struct is laid out in memory
+    pub fn struct_layout(&self, name: &str) -> Option<&StructLayout> {
+        self.struct_layouts.get(name)
+    }
 
-        // Performs linking
-        self.module.finalize_definitions();
+    /// Names of every compiled (non-extern) function, in alphabetical order
+    pub fn function_names(&self) -> Vec<&str> {
+        let mut names = self.signatures.keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
 
-        Ok(())
+        names.sort_unstable();
+        names
     }
 
-    fn forward_declare_function(&mut self, name: &str, definition: &crate::frontend::validate::FunctionDefinition) -> Result<cranelift_module::FuncId, String> {
-        if self.functions.contains_key(name) {
-            return Err(format!("Function `{}` was already defined", name));
-        }
+    /// Cranelift IR of a compiled function, before and after optimization.  
+    /// Only available with `JitterContextBuilder::emit_clif`.
+    pub fn clif_for(&self, name: &str) -> Option<&str> {
+        self.compiler.clif.get(name).map(|clif| clif.as_str())
+    }
 
-        let mut signature = self.module.make_signature();
+    /// Disassembled machine code of a compiled function (as it is in memory).  
+    /// Only available with `JitterContextBuilder::emit_disasm` (and the `disasm` feature).
+    #[cfg(feature = "disasm")]
+    pub fn disasm_for(&self, name: &str) -> Option<&str> {
+        self.disasm.get(name).map(|disasm| disasm.as_str())
+    }
 
-        // All function inputs are the value's stack locations
-        for _ in &definition.parameters {
-            signature.params.push(AbiParam::new(self.pointer_type));
-        }
+    /// ELF image holding the DWARF debug info of compiled functions, as registered with debuggers.  
+    /// Only available with `JitterContextBuilder::with_debug_info`.
+    pub fn debug_image(&self) -> Option<&[u8]> {
+        self.debug_registration.as_ref().map(|registration| registration.image())
+    }
 
-        // Unit return type -> nothing returned
-        if !definition.return_type.is_unit() {
-            // Return value is the address of stack pre-allocation
-            signature.returns.push(
-                AbiParam::special(
-                    self.pointer_type, 
-                    codegen::ir::ArgumentPurpose::StructReturn
-                )
-            );
-        }
+    /// Refuels metered code (see `JitterContextBuilder::with_fuel`).
+    /// Errors if the context was built without fuel.
+    pub fn set_fuel(&self, fuel: u64) -> Result<(), String> {
+        let counter = self.fuel.as_ref()
+            .ok_or("Compiled code isn't metered, so has no fuel to set (see `JitterContextBuilder::with_fuel`)")?;
+
+        counter.set(fuel);
+        Ok(())
+    }
 
-        let linkage = if definition.is_extern {
-            Linkage::Import
-        } else {
-            Linkage::Local
+    /// Fuel left for metered code, or `None` if the context was built without fuel
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel.as_ref().map(|fuel| fuel.get())
+    }
+
+    /// Returns a compiled function with the signature `fn(P..) -> R`, provided that is its actual signature.
+    ///
+    /// Usage: `jitter.get_typed_fn::<(i32, Vec3), f32>("name")`
+    pub fn get_typed_fn<P: JitterParams, R: JitterType>(&self, name: &str) -> Result<JitterFn<'_, P, R>, String> {
+        let (name, signature) = self.signatures.get_key_value(name)
+            .ok_or_else(|| format!("No compiled function named `{}`", name))?;
+
+        let requested = Signature {
+            parameters: P::jitter_types(),
+            return_type: R::jitter_type(),
         };
 
-        let func_id = self.module.declare_function(name, linkage, &signature)
-            .map_err(|e| e.to_string())?;
+        let differences = signature.differences(&requested);
+        if !differences.is_empty() {
+            return Err(format!(
+                "Function `{}` has signature `{}`, but was requested as `{}`:\n  - {}",
+                name, signature, requested, differences.join("\n  - ")
+            ));
+        }
+
+        // Structs of the same name must also be laid out the same (or the call would misread them)
+        for rust_layout in P::layouts().iter().chain(R::layout().as_ref()) {
+            self.struct_layout(&rust_layout.name)
+                .ok_or_else(|| format!("Function `{}` was requested with Rust struct `{}`, which has no Jitter equivalent", name, rust_layout.name))?
+                .compare(rust_layout)
+                .map_err(|error| format!("Function `{}` can't be called with these Rust types. {}", name, error))?;
+        }
 
-        // TODO: Store additional information in `functions`
-        self.functions.insert(name.to_string(), func_id);
+        let fuel = self.fuel.as_ref().map(|fuel| fuel.as_ptr());
+        Ok(JitterFn::new(name, self.get_fn(name), self.compiler.calling_convention, fuel))
+    }
 
-        Ok(func_id)
+    // NOTE: The signature is not checked here (see `get_typed_fn`)
+    pub fn get_fn(&self, id: &str) -> *const u8 {
+        let func_id = self.compiler.functions.get(id).expect("no such function");
+        self.compiler.module.get_finalized_function(*func_id)
     }
 
-    // TODO: Consider moving this into codegen.rs to put all codegen in one place
-    fn generate_function(&mut self, function: &ast::Function, validation_context: &ValidationContext) -> Result<(), String> {
-        let func_id = self.functions.get(&function.prototype.name)
-            .ok_or(format!("Attempted to translate an unregistered function: {}", function.prototype.name))?;
+    // NOTE:
+    // All code represented by the validation context is assumed to be valid
+    pub fn translate(&mut self, validation_context: ValidationContext) -> Result<(), String> {
+        self.compiler.compile(&validation_context)?;
 
-        // Define the function parameters (passed in by stack address)
-        for _ in &function.prototype.parameters.item {            
-            self.fn_context.func.signature.params.push(
-                AbiParam::new(self.pointer_type)
-            );
+        // Extern functions are provided by Rust, so have no compiled code to hand out
+        for (name, definition) in &validation_context.functions.functions {
+            if !definition.is_extern {
+                self.signatures.insert(name.clone(), Signature {
+                    parameters: definition.parameters.iter().map(|(_, ty, _)| ty.clone()).collect(),
+                    return_type: definition.return_type.clone(),
+                });
+            }
         }
-        
-        let has_return_value = !function.prototype.return_type.is_unit();
-
-        // Returns the stack pre-allocation address
-        if has_return_value {
-            self.fn_context.func.signature.returns.push(
-                AbiParam::special(
-                    self.pointer_type, 
-                    cranelift::codegen::ir::ArgumentPurpose::StructReturn
-                )
-            );
+
+        // Structs only need their layouts kept (for comparing with Rust types)
+        for struct_ in &validation_context.ast.structs {
+            if let Some(layout) = validation_context.struct_layout(&struct_.name) {
+                self.struct_layouts.insert(struct_.name.clone(), layout);
+            }
         }
-        
-        let mut function_translator = FunctionTranslator::new(
-            &self.pointer_type,
-            FunctionBuilder::new(&mut self.fn_context.func, &mut self.fn_builder_context),
-            &mut self.module,
-            validation_context,
-        );
-        
-        // Generates IR, then finalizes the function, making it ready for the module
-        function_translator.translate_function(function, has_return_value)?;
 
-        // Performs constant folding (I'm not sure what else is done elsewhere)
-        cranelift_preopt::optimize(&mut self.fn_context, self.module.isa()).expect("Optimize");
-        
-        // Define the function
-        self.module
-            .define_function(*func_id, &mut self.fn_context, &mut codegen::binemit::NullTrapSink{})
-            .map_err(|e| e.to_string())?;
+        // Performs linking
+        self.compiler.module.finalize_definitions();
+
+        let functions = self.compiler.code_sizes.iter()
+            .map(|(name, size)| {
+                let sites = self.compiler.trap_sites.get(name).cloned().unwrap_or_default();
+                (self.get_fn(name) as usize, *size, name.clone(), sites)
+            })
+            .collect();
+        self.trap_registration = Some(TrapRegistration::register(functions));
+
+        // Only finalized code has its calls (and other relocations) resolved
+        #[cfg(feature = "disasm")]
+        if self.emit_disasm {
+            for (name, size) in &self.compiler.code_sizes {
+                let pointer = self.get_fn(name);
+                let code = unsafe { std::slice::from_raw_parts(pointer, *size as usize) };
+
+                let disasm = super::disasm::disassemble(self.compiler.module.isa().triple(), code, pointer as u64)?;
+                self.disasm.insert(name.clone(), disasm);
+            }
+        }
 
-        // Reset the context for the next function
-        self.module.clear_context(&mut self.fn_context);
+        if self.compiler.emit_debug_info && !self.compiler.debug_info.is_empty() {
+            let functions = self.compiler.debug_info.iter()
+                .map(|function| (function, self.get_fn(&function.name) as u64, self.compiler.code_sizes[&function.name]))
+                .collect::<Vec<_>>();
+
+            let image = debug::write_image(&functions, &self.source_map, self.compiler.module.isa().triple())?;
+            self.debug_registration = Some(GdbJitRegistration::register(image));
+        }
+
+        if self.perf_map || self.jitdump {
+            // Functions are qualified by the module (source file) they were compiled from
+            let module = self.source_map.file(crate::source_map::FileId(0))
+                .and_then(|file| std::path::Path::new(&file.path).file_stem())
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("jitter"));
+
+            let mut functions = self.compiler.code_sizes.iter()
+                .map(|(name, size)| (self.get_fn(name) as u64, *size, format!("{}::{}", module, name)))
+                .collect::<Vec<_>>();
+            functions.sort_unstable();
+
+            if self.perf_map {
+                super::perf::write_perf_map(&functions)?;
+            }
+
+            if self.jitdump {
+                let machine = match self.compiler.module.isa().triple().architecture {
+                    target_lexicon::Architecture::X86_64 => 62,
+                    target_lexicon::Architecture::Aarch64(_) => 183,
+                    architecture => return Err(format!("Jitdumps are not supported on `{}`", architecture)),
+                };
+
+                let functions = functions.into_iter()
+                    .map(|(address, size, name)| (address, unsafe { std::slice::from_raw_parts(address as *const u8, size as usize) }, name))
+                    .collect::<Vec<_>>();
+
+                super::perf::write_jitdump(&functions, machine)?;
+            }
+        }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
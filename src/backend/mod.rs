@@ -1,23 +1,45 @@
 /// Contains functionality for translating an AST into CLIF
 mod codegen;
+/// Contains the code generation shared by every backend
+mod compiler;
 /// Contains the JIT driver
 pub mod jit;
+/// Contains the object file (ahead-of-time) driver
+pub mod object;
+/// Contains typed handles to compiled functions
+pub mod function;
+/// Contains the calling conventions compiled functions can use
+pub mod abi;
+/// Contains the settings used to compile Jitter code
+pub mod options;
+/// Contains the disassembler used to inspect compiled functions
+#[cfg(feature = "disasm")]
+mod disasm;
+/// Contains DWARF debug info generation and debugger (GDB JIT) registration
+mod debug;
+/// Contains perf map generation, for naming compiled functions in profilers
+pub mod perf;
+/// Contains trap handling, which turns faults in compiled code into `JitterTrap` errors
+pub mod trap;
 
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use cranelift::prelude::{Value, Variable, EntityRef};
-use cranelift::codegen::ir::StackSlot;
+
+use crate::frontend::parse::ast::BindingId;
 
 
 /// Maps variables to their in-memory representations
 pub struct MemoryMap {
-    /// Map of (variable name -> cranelift variable index)
-    variables: HashMap<String, Variable>,
+    /// Map of (variable binding -> cranelift variable index)
+    variables: HashMap<BindingId, Variable>,
     index: usize,
+    /// Scalar variables which live in memory, as their address is taken
+    address_taken: HashSet<BindingId>,
 
-    /// Special StructReturnSlot. If a function returns a value, it must be stored here.
-    struct_return_slot: Option<StackSlot>,
+    /// Caller-provided buffer (`sret` parameter). If a function returns a value, it must be stored here.
+    struct_return_address: Option<Value>,
 }
 
 impl MemoryMap {
@@ -26,32 +48,41 @@ impl MemoryMap {
         Self {
             variables: HashMap::new(),
             index: 0,
-            struct_return_slot: None,
+            address_taken: HashSet::new(),
+            struct_return_address: None,
         }
     }
 
     // TODO: Overwrite check?
-    pub fn register_struct_return_slot(&mut self, slot: StackSlot) {
-        self.struct_return_slot = Some(slot);
+    pub fn register_struct_return_address(&mut self, address: Value) {
+        self.struct_return_address = Some(address);
     }
 
-    pub fn get_struct_return_slot(&self) -> &StackSlot {
+    pub fn get_struct_return_address(&self) -> Value {
         // TODO: Error check?
-        self.struct_return_slot.as_ref().expect("get_struct_return_slot")
+        self.struct_return_address.expect("get_struct_return_address")
     }
 
-    pub fn create_variable(&mut self, name: &str) -> Variable {
+    pub fn create_variable(&mut self, binding: BindingId) -> Variable {
         let variable = Variable::new(self.index);
         self.index += 1;
 
-        // TODO: Duplicate checking?
-        self.variables.insert(name.into(), variable);
+        // NOTE: Bindings are unique within a function
+        self.variables.insert(binding, variable);
 
         variable
     }
 
-    pub fn get_variable(&self, name: &str) -> Variable {
+    pub fn mark_address_taken(&mut self, binding: BindingId) {
+        self.address_taken.insert(binding);
+    }
+
+    pub fn is_address_taken(&self, binding: BindingId) -> bool {
+        self.address_taken.contains(&binding)
+    }
+
+    pub fn get_variable(&self, binding: BindingId) -> Variable {
         // TODO: Error check?
-        *self.variables.get(name).expect("get_variable")
+        *self.variables.get(&binding).expect("get_variable")
     }
 }
\ No newline at end of file
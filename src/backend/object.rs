@@ -0,0 +1,191 @@
+// Ahead-of-time compilation into relocatable object files.
+//
+// Unlike the JIT, the target does not have to be the host: any triple supported by both
+// `target-lexicon` and Cranelift can be compiled for, e.g. `aarch64-unknown-linux-gnu` from x86-64.
+// Compiled functions are exported (by name), while extern functions are left for the linker.
+//
+// Executables are linked by the system's C compiler (`cc`, or `$CC`), which also provides the
+// runtime: `_start`, `memcpy` (used for copying values), and the rest of libc (`malloc`, `abort`, ..)
+// for extern functions to use.
+
+use crate::frontend::LexerCallback;
+use crate::frontend::validate::target::TargetInfo;
+use crate::backend::compiler::ModuleCompiler;
+use crate::backend::abi::CallingConvention;
+use crate::backend::options::CompileOptions;
+
+use cranelift::prelude::*;
+use cranelift_module::{Module, Linkage};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use target_lexicon::Triple;
+
+/// Builder for compiling a source file into an object file for some target.
+///
+/// Usage:
+/// ```ignore
+/// let bytes = JitterObjectBuilder::new("aarch64-unknown-linux-gnu".parse().unwrap())
+///     .with_source_path("main.jitter")
+///     .build()?;
+///
+/// std::fs::write("main.o", bytes)?;
+/// ```
+pub struct JitterObjectBuilder<'a> {
+    triple: Triple,
+    compile_options: CompileOptions,
+    source_path: &'a str,
+    extension_path: &'a str,
+
+    lexer_callbacks: Vec<LexerCallback<'a>>,
+    /// How compiled (and extern) functions are called
+    calling_convention: CallingConvention,
+    /// Whether a C `main` which calls the Jitter `main` is included (see `with_entry_point`)
+    entry_point: bool,
+
+    /// Whether errors are rendered with ANSI colour codes
+    colored_errors: bool,
+    /// Whether errors are reported as JSON lines
+    json_errors: bool,
+}
+
+impl<'a> JitterObjectBuilder<'a> {
+    pub fn new(triple: Triple) -> Self {
+        Self {
+            triple,
+            compile_options: CompileOptions::default(),
+            source_path: "",
+            extension_path: ".",
+            lexer_callbacks: Vec::new(),
+            calling_convention: CallingConvention::default(),
+            entry_point: false,
+            colored_errors: false,
+            json_errors: false,
+        }
+    }
+
+    /// Sets the optimization level and other Cranelift settings (see `CompileOptions`)
+    pub fn with_compile_options(mut self, options: CompileOptions) -> Self {
+        self.compile_options = options;
+        self
+    }
+
+    pub fn with_extension_path(mut self, path: &'a str) -> Self {
+        self.extension_path = path;
+        self
+    }
+
+    /// Defines a callback for use when either Lexing or Parsing
+    pub fn with_lexer_callback(mut self, callback: LexerCallback<'a>) -> Self {
+        self.lexer_callbacks.push(callback);
+        self
+    }
+
+    /// Compiles functions (and calls extern ones) using the given calling convention.  
+    /// With `CallingConvention::C`, the object can be linked against C code.
+    pub fn with_calling_convention(mut self, calling_convention: CallingConvention) -> Self {
+        self.calling_convention = calling_convention;
+        self
+    }
+
+    /// Includes a C `main` which calls the Jitter `main` and returns its value as the exit code.  
+    /// Compiled functions are then prefixed with `jitter_` (e.g. `jitter_main`) to avoid conflicts.
+    pub fn with_entry_point(mut self, entry_point: bool) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    pub fn with_source_path(mut self, path: &'a str) -> Self {
+        self.source_path = path;
+        self
+    }
+
+    /// Renders errors with ANSI colour codes (for terminals). Errors are plain text by default.
+    pub fn with_colored_errors(mut self, colored: bool) -> Self {
+        self.colored_errors = colored;
+        self
+    }
+
+    /// Reports errors as one JSON object per line (see `Diagnostic::to_json`)
+    pub fn with_json_errors(mut self, json: bool) -> Self {
+        self.json_errors = json;
+        self
+    }
+
+    /// Compiles the source file, returning the contents of the object file
+    pub fn build(self) -> Result<Vec<u8>, String> {
+        let isa_builder = isa::lookup(self.triple.clone())
+            .map_err(|error| format!("Unsupported target `{}`: {}", self.triple, error))?;
+        // Executables are position independent by default on most platforms
+        let mut compile_options = self.compile_options.clone();
+        if self.entry_point {
+            compile_options = compile_options.with_flag("is_pic", "true");
+        }
+        let isa = isa_builder.finish(compile_options.flags()?);
+
+        // Named after the source file (shows up as the object's file symbol)
+        let name = std::path::Path::new(self.source_path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("jitter");
+
+        let object_builder = ObjectBuilder::new(isa, name, cranelift_module::default_libcall_names())
+            .map_err(|error| error.to_string())?;
+
+        let mut compiler = ModuleCompiler::new(ObjectModule::new(object_builder));
+        compiler.preopt = self.compile_options.preopt;
+        compiler.calling_convention = self.calling_convention;
+        compiler.linkage = Linkage::Export;
+        // FIXME: `cranelift-object` can't relocate direct AArch64 calls (`bl`) yet, so calls go through an absolute address
+        if let target_lexicon::Architecture::Aarch64(_) = self.triple.architecture {
+            compiler.colocated_calls = false;
+        }
+        if self.entry_point {
+            compiler.symbol_prefix = String::from("jitter_");
+        }
+
+        if self.source_path != "" {
+            let (validation_context, _warnings, _source_map) = crate::frontend::validate_source(
+                self.source_path,
+                self.extension_path,
+                self.lexer_callbacks,
+                TargetInfo::from_triple(compiler.module.isa().triple()),
+                self.colored_errors,
+                self.json_errors,
+            )?;
+
+            // Codegen
+            compiler.compile(&validation_context)?;
+
+            if self.entry_point {
+                compiler.define_entry_point(&validation_context)?;
+            }
+        }
+
+        compiler.module.finish()
+            .emit()
+            .map_err(|error| format!("Failed to write object file: {}", error))
+    }
+
+    /// Compiles the source file into an executable at `output_path` (see `with_entry_point`).  
+    /// The object file is linked by `$CC` if set, or `cc` otherwise.
+    pub fn build_executable(self, output_path: &str) -> Result<(), String> {
+        let object = self.with_entry_point(true).build()?;
+
+        let object_path = format!("{}.o", output_path);
+        std::fs::write(&object_path, object)
+            .map_err(|error| format!("Failed to write `{}`: {}", object_path, error))?;
+
+        let linker = std::env::var("CC").unwrap_or_else(|_| String::from("cc"));
+        let output = std::process::Command::new(&linker)
+            .args([&object_path, "-o", output_path])
+            .output();
+
+        // The object file is only an intermediate
+        let _ = std::fs::remove_file(&object_path);
+
+        match output {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => Err(format!("Failed to link `{}`:\n{}", output_path, String::from_utf8_lossy(&output.stderr))),
+            Err(error) => Err(format!("Failed to run the linker (`{}`): {}", linker, error)),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// Settings used to compile Jitter code.
+//
+// Most of these map directly onto Cranelift's shared settings (see `cranelift_codegen::settings`).
+// Anything not covered can still be set through `CompileOptions::flags`.
+
+use cranelift::prelude::{settings, Configurable};
+
+/// How hard Cranelift tries to optimize the generated code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    None,
+    Speed,
+    SpeedAndSize,
+}
+
+impl OptLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed_and_size",
+        }
+    }
+}
+
+/// Usage:
+/// ```ignore
+/// let jitter = JitterContextBuilder::new()
+///     .with_compile_options(CompileOptions {
+///         opt_level: OptLevel::Speed,
+///         ..CompileOptions::default()
+///     })
+///     ...
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub opt_level: OptLevel,
+    /// Checks the generated IR for errors (slower to compile)
+    pub verifier: bool,
+    /// Makes NaN results deterministic across platforms
+    pub nan_canonicalization: bool,
+    /// Calls a stack probe for functions with large stack frames
+    pub probestack: bool,
+    /// Whether IR is simplified (constant folding, ..) before code generation
+    pub preopt: bool,
+    /// Any other Cranelift settings, as (name, value). These are applied last.
+    pub flags: Vec<(String, String)>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevel::SpeedAndSize,
+            verifier: true,
+            nan_canonicalization: false,
+            probestack: true,
+            preopt: true,
+            flags: Vec::new(),
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Compiles as quickly as possible (no optimizations), while still verifying the generated IR
+    pub fn debug() -> Self {
+        Self {
+            opt_level: OptLevel::None,
+            preopt: false,
+            ..Self::default()
+        }
+    }
+
+    /// Sets any Cranelift setting by name (see `CompileOptions::flags`)
+    pub fn with_flag(mut self, name: &str, value: &str) -> Self {
+        self.flags.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Builds Cranelift's shared settings
+    pub(crate) fn flags(&self) -> Result<settings::Flags, String> {
+        let mut builder = settings::builder();
+
+        let bool_str = |value: bool| if value { "true" } else { "false" };
+        let settings = [
+            ("opt_level", self.opt_level.as_str()),
+            ("enable_verifier", bool_str(self.verifier)),
+            ("enable_nan_canonicalization", bool_str(self.nan_canonicalization)),
+            ("enable_probestack", bool_str(self.probestack)),
+        ];
+
+        let flags = settings.iter()
+            .map(|(name, value)| (*name, *value))
+            .chain(self.flags.iter().map(|(name, value)| (name.as_str(), value.as_str())));
+
+        for (name, value) in flags {
+            builder.set(name, value)
+                .map_err(|error| format!("Invalid Cranelift setting `{} = {}`: {}", name, value, error))?;
+        }
+
+        Ok(settings::Flags::new(builder))
+    }
+}
@@ -0,0 +1,150 @@
+// Perf map and jitdump support, so that profilers such as `perf` can name JIT-compiled functions.
+// See https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jit-interface.txt
+//
+// Each line of `/tmp/perf-<pid>.map` is `START SIZE name` (in hex). The file is only ever appended
+// to, and perf uses the latest entry for an address, so building a new context (reloading code)
+// updates the entries of any reused addresses.
+//
+// `/tmp/jit-<pid>.dump` also records the code itself, so that `perf annotate` can show the
+// instructions of compiled functions. See
+// https://github.com/torvalds/linux/blob/master/tools/perf/Documentation/jitdump-specification.txt
+// Samples are matched to the code by timestamp, which requires `perf record -k mono`, followed by
+// `perf inject --jit` to turn the dump into ELF images.
+// NOTE: perf paths are fixed, so neither file honours `TMPDIR`
+
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Guards the perf map (shared by every `JitterContext`)
+static PERF_MAP_LOCK: Mutex<()> = Mutex::new(());
+
+/// Path of this process' perf map
+pub fn perf_map_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("/tmp/perf-{}.map", std::process::id()))
+}
+
+/// Appends functions to the perf map, as (address, size, name)
+pub(crate) fn write_perf_map(functions: &[(u64, u32, String)]) -> Result<(), String> {
+    let mut entries = String::new();
+    for (address, size, name) in functions {
+        entries.push_str(&format!("{:x} {:x} {}\n", address, size, name));
+    }
+
+    let path = perf_map_path();
+    let _lock = PERF_MAP_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // Written all at once, so that entries from other writers are not interleaved
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(entries.as_bytes()))
+        .map_err(|error| format!("Failed to write the perf map (`{}`): {}", path.display(), error))
+}
+
+/// `JiTD`, written in the process' byte order (which tells perf how to read the rest)
+const JITDUMP_MAGIC: u32 = 0x4A69_5444;
+const JITDUMP_VERSION: u32 = 1;
+const JITDUMP_HEADER_SIZE: u32 = 40;
+/// Record id of `JIT_CODE_LOAD`
+const JIT_CODE_LOAD: u32 = 0;
+
+/// The open jitdump (shared by every `JitterContext`)
+struct JitDump {
+    file: std::fs::File,
+    /// The process the dump was created by (a forked child needs a dump of its own)
+    pid: u32,
+    /// Unique index of the next function written
+    code_index: u64,
+}
+
+static JITDUMP: Mutex<Option<JitDump>> = Mutex::new(None);
+
+/// Path of this process' jitdump
+pub fn jitdump_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("/tmp/jit-{}.dump", std::process::id()))
+}
+
+/// Appends a `JIT_CODE_LOAD` record for each function, as (address, code, name).
+/// The dump is created on first use, for the target with the given ELF machine (`e_machine`).
+pub(crate) fn write_jitdump(functions: &[(u64, &[u8], String)], machine: u32) -> Result<(), String> {
+    let path = jitdump_path();
+    let error = |error: std::io::Error| format!("Failed to write the jitdump (`{}`): {}", path.display(), error);
+
+    let mut jitdump = JITDUMP.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let pid = std::process::id();
+    if jitdump.as_ref().is_none_or(|jitdump| jitdump.pid != pid) {
+        *jitdump = Some(JitDump::create(&path, machine).map_err(error)?);
+    }
+    let jitdump = jitdump.as_mut().unwrap();
+
+    let mut records = Vec::new();
+    for (address, code, name) in functions {
+        let total_size = 56 + name.len() + 1 + code.len();
+
+        // Record header
+        records.extend_from_slice(&JIT_CODE_LOAD.to_ne_bytes());
+        records.extend_from_slice(&(total_size as u32).to_ne_bytes());
+        records.extend_from_slice(&timestamp().to_ne_bytes());
+
+        records.extend_from_slice(&pid.to_ne_bytes());
+        records.extend_from_slice(&(unsafe { libc::syscall(libc::SYS_gettid) } as u32).to_ne_bytes());
+        // Virtual address, then code address (identical for JIT-compiled code)
+        records.extend_from_slice(&address.to_ne_bytes());
+        records.extend_from_slice(&address.to_ne_bytes());
+        records.extend_from_slice(&(code.len() as u64).to_ne_bytes());
+        records.extend_from_slice(&jitdump.code_index.to_ne_bytes());
+        records.extend_from_slice(name.as_bytes());
+        records.push(0);
+        records.extend_from_slice(code);
+
+        jitdump.code_index += 1;
+    }
+
+    jitdump.file.write_all(&records).map_err(error)
+}
+
+impl JitDump {
+    fn create(path: &std::path::Path, machine: u32) -> std::io::Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+
+        let mut header = Vec::with_capacity(JITDUMP_HEADER_SIZE as usize);
+        header.extend_from_slice(&JITDUMP_MAGIC.to_ne_bytes());
+        header.extend_from_slice(&JITDUMP_VERSION.to_ne_bytes());
+        header.extend_from_slice(&JITDUMP_HEADER_SIZE.to_ne_bytes());
+        header.extend_from_slice(&machine.to_ne_bytes());
+        header.extend_from_slice(&0u32.to_ne_bytes()); // padding
+        header.extend_from_slice(&std::process::id().to_ne_bytes());
+        header.extend_from_slice(&timestamp().to_ne_bytes());
+        header.extend_from_slice(&0u64.to_ne_bytes()); // flags
+        file.write_all(&header)?;
+
+        // perf finds the dump through an executable mapping of it, which is kept for the life of the process
+        use std::os::unix::io::AsRawFd;
+        let mapping = unsafe {
+            libc::mmap(std::ptr::null_mut(), JITDUMP_HEADER_SIZE as usize, libc::PROT_READ | libc::PROT_EXEC, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if mapping == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            file,
+            pid: std::process::id(),
+            code_index: 0,
+        })
+    }
+}
+
+/// Nanoseconds of `CLOCK_MONOTONIC` (as used by `perf record -k mono`)
+fn timestamp() -> u64 {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
@@ -0,0 +1,27 @@
+// Recovers from traps in JIT-compiled code (see `trap.rs`).
+//
+// `sigsetjmp` returns twice, which Rust can't express, so the jump buffer lives in this frame
+// instead. The signal handler (in Rust) jumps back here through `jitter_unwind`.
+
+#include <setjmp.h>
+
+typedef void (*jitter_callback)(void *);
+
+// Calls `callback(payload)`, with `*buffer` pointing to a jump buffer until it returns.
+// Returns 1 if the callback returned, or 0 if it was unwound by `jitter_unwind`.
+int jitter_catch_traps(void **buffer, jitter_callback callback, void *payload) {
+    sigjmp_buf jump_buffer;
+
+    // The signal mask is saved too, as the trap's signal is blocked while it is handled
+    if (sigsetjmp(jump_buffer, 1) != 0) {
+        return 0;
+    }
+
+    *buffer = &jump_buffer;
+    callback(payload);
+    return 1;
+}
+
+void jitter_unwind(void *buffer) {
+    siglongjmp(*(sigjmp_buf *)buffer, 1);
+}
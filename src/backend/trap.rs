@@ -0,0 +1,401 @@
+// Recovering from traps in JIT-compiled code.
+//
+// Cranelift reports every instruction which may trap (memory accesses, `trap` instructions, ..)
+// while emitting code. These trap sites are registered along with the address range of their
+// function, so that a signal raised by one of them (SIGSEGV, SIGBUS, SIGILL, or SIGFPE) can be
+// traced back to its function and span. Guarded calls (`catch_traps`) then unwind back to the host
+// through `trap.c`, which holds the jump buffer (`sigsetjmp` can't be called from Rust).
+//
+// Signal handlers may only use async-signal-safe operations, so the registered functions are kept
+// in an immutable table which is replaced (rather than locked) whenever they change.
+//
+// Signals raised anywhere else are passed on to whichever handler was installed before.
+//
+// `siglongjmp` skips every frame between the guarded call and the trap without running their
+// destructors. Only JIT-compiled frames (which own nothing) normally sit in between, but an extern
+// Rust function which calls back into trapping code has its locals leaked (and any locks held).
+
+use crate::Span;
+
+use cranelift::codegen::{binemit, ir::{SourceLoc, TrapCode}};
+
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// What caused a trap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapKind {
+    StackOverflow,
+    /// Invalid (or misaligned) memory access
+    MemoryOutOfBounds,
+    IntegerOverflow,
+    IntegerDivisionByZero,
+    BadConversionToInteger,
+    Unreachable,
+    Interrupt,
+    /// Ran out of fuel (see `JitterContextBuilder::with_fuel`)
+    FuelExhausted,
+    /// Given a fuel budget, but compiled without fuel (so never called)
+    NotMetered,
+    /// Any other trap (`TrapCode::User`)
+    User(u16),
+}
+
+/// Raised by metered code once its fuel runs out
+pub(crate) const FUEL_EXHAUSTED: TrapCode = TrapCode::User(0);
+
+impl From<TrapCode> for TrapKind {
+    fn from(code: TrapCode) -> Self {
+        match code {
+            TrapCode::StackOverflow => TrapKind::StackOverflow,
+            TrapCode::HeapOutOfBounds
+            | TrapCode::HeapMisaligned
+            | TrapCode::TableOutOfBounds
+            | TrapCode::IndirectCallToNull
+            | TrapCode::BadSignature => TrapKind::MemoryOutOfBounds,
+            TrapCode::IntegerOverflow => TrapKind::IntegerOverflow,
+            TrapCode::IntegerDivisionByZero => TrapKind::IntegerDivisionByZero,
+            TrapCode::BadConversionToInteger => TrapKind::BadConversionToInteger,
+            TrapCode::UnreachableCodeReached => TrapKind::Unreachable,
+            TrapCode::Interrupt => TrapKind::Interrupt,
+            FUEL_EXHAUSTED => TrapKind::FuelExhausted,
+            TrapCode::User(code) => TrapKind::User(code),
+        }
+    }
+}
+
+impl std::fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrapKind::StackOverflow => write!(f, "stack overflow"),
+            TrapKind::MemoryOutOfBounds => write!(f, "invalid memory access"),
+            TrapKind::IntegerOverflow => write!(f, "integer overflow"),
+            TrapKind::IntegerDivisionByZero => write!(f, "integer division by zero"),
+            TrapKind::BadConversionToInteger => write!(f, "invalid conversion to integer"),
+            TrapKind::Unreachable => write!(f, "unreachable code reached"),
+            TrapKind::Interrupt => write!(f, "interrupted"),
+            TrapKind::FuelExhausted => write!(f, "ran out of fuel"),
+            TrapKind::NotMetered => write!(f, "not metered, so can't be given fuel (see `JitterContextBuilder::with_fuel`)"),
+            TrapKind::User(code) => write!(f, "trap #{}", code),
+        }
+    }
+}
+
+/// A trap raised by JIT-compiled code (see `JitterFn::try_call`)
+#[derive(Debug, Clone)]
+pub struct JitterTrap {
+    pub kind: TrapKind,
+    /// The function which trapped
+    pub function: String,
+    /// The statement which trapped, if known
+    pub span: Option<Span>,
+}
+
+impl std::fmt::Display for JitterTrap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Function `{}` trapped: {}", self.function, self.kind)?;
+
+        if let Some(span) = &self.span {
+            write!(f, " (line {}, column {})", span.start_line, span.start_column)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for JitterTrap {}
+
+/// An instruction which may trap, within a compiled function
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TrapSite {
+    /// Code offset of the instruction
+    pub offset: u32,
+    pub kind: TrapKind,
+    pub span: Option<Span>,
+}
+
+/// Records trap sites while a function is emitted
+#[derive(Default)]
+pub(crate) struct TrapSiteSink {
+    pub sites: Vec<(binemit::CodeOffset, SourceLoc, TrapCode)>,
+}
+
+impl binemit::TrapSink for TrapSiteSink {
+    fn trap(&mut self, offset: binemit::CodeOffset, source_location: SourceLoc, code: TrapCode) {
+        self.sites.push((offset, source_location, code));
+    }
+}
+
+struct CompiledFunction {
+    start: usize,
+    end: usize,
+    name: Arc<str>,
+    /// Sorted by offset
+    sites: Vec<TrapSite>,
+}
+
+/// Functions of every live `JitterContext`, sorted by start address
+type TrapTable = Vec<Arc<CompiledFunction>>;
+
+/// The current `TrapTable`, which signal handlers read without locking (see `resolve`).
+/// It is never modified, only replaced (see `update_table`).
+static TABLE: AtomicPtr<TrapTable> = AtomicPtr::new(null_mut());
+/// Signal handlers currently reading `TABLE`
+static READERS: AtomicUsize = AtomicUsize::new(0);
+/// Serializes replacements of `TABLE`
+static UPDATE: Mutex<()> = Mutex::new(());
+
+/// Replaces `TABLE` with an updated copy
+fn update_table(update: impl FnOnce(&mut TrapTable)) {
+    let _guard = UPDATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let current = TABLE.load(Ordering::SeqCst);
+    // SAFETY: Tables are only freed here, while holding `UPDATE`
+    let mut table = unsafe { current.as_ref() }.cloned().unwrap_or_default();
+    update(&mut table);
+    table.sort_unstable_by_key(|function| function.start);
+
+    let previous = TABLE.swap(Box::into_raw(Box::new(table)), Ordering::SeqCst);
+
+    // Handlers which started reading before the swap may still be using the previous table
+    while READERS.load(Ordering::SeqCst) != 0 {
+        std::hint::spin_loop();
+    }
+
+    if !previous.is_null() {
+        // SAFETY: No longer reachable through `TABLE`, and no longer read
+        drop(unsafe { Box::from_raw(previous) });
+    }
+}
+
+/// A trap resolved by the signal handler (see `resolve`)
+#[cfg(unix)]
+struct ResolvedTrap {
+    kind: TrapKind,
+    function: Arc<str>,
+    span: Option<Span>,
+}
+
+#[cfg(unix)]
+impl From<ResolvedTrap> for JitterTrap {
+    fn from(trap: ResolvedTrap) -> Self {
+        Self {
+            kind: trap.kind,
+            function: trap.function.to_string(),
+            span: trap.span,
+        }
+    }
+}
+
+/// Finds the trap raised at `address` (if it is a trap site).
+/// Called from signal handlers, so it neither locks nor allocates.
+#[cfg(unix)]
+fn resolve(address: usize) -> Option<ResolvedTrap> {
+    READERS.fetch_add(1, Ordering::SeqCst);
+
+    // SAFETY: Tables aren't freed while being read (see `update_table`)
+    let table = unsafe { TABLE.load(Ordering::SeqCst).as_ref() };
+    let trap = table.and_then(|table| {
+        let index = table.partition_point(|function| function.start <= address).checked_sub(1)?;
+        let function = &table[index];
+        if address >= function.end {
+            return None;
+        }
+
+        let offset = (address - function.start) as u32;
+        let site = function.sites.binary_search_by_key(&offset, |site| site.offset).ok()?;
+
+        Some(ResolvedTrap {
+            kind: function.sites[site].kind,
+            function: function.name.clone(),
+            span: function.sites[site].span,
+        })
+    });
+
+    READERS.fetch_sub(1, Ordering::SeqCst);
+    trap
+}
+
+/// Compiled functions whose traps can be caught. They are unregistered when dropped.
+pub(crate) struct TrapRegistration {
+    starts: Vec<usize>,
+}
+
+impl TrapRegistration {
+    /// Registers functions as (address, size, name, trap sites)
+    pub fn register(functions: Vec<(usize, u32, String, Vec<TrapSite>)>) -> Self {
+        #[cfg(unix)]
+        signals::install_handlers();
+
+        let functions = functions.into_iter()
+            .map(|(start, size, name, mut sites)| {
+                sites.sort_unstable_by_key(|site| site.offset);
+
+                Arc::new(CompiledFunction {
+                    start,
+                    end: start + size as usize,
+                    name: name.into(),
+                    sites,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let starts = functions.iter()
+            .map(|function| function.start)
+            .collect();
+
+        update_table(|table| table.extend(functions));
+
+        Self {
+            starts,
+        }
+    }
+}
+
+impl Drop for TrapRegistration {
+    fn drop(&mut self) {
+        update_table(|table| table.retain(|function| !self.starts.contains(&function.start)));
+    }
+}
+
+/// Calls `function`, returning the trap raised by JIT-compiled code (if any) instead of crashing.
+/// Frames unwound by a trap don't run their destructors (see above).
+#[cfg(unix)]
+pub(crate) fn catch_traps<T>(function: impl FnOnce() -> T) -> Result<T, JitterTrap> {
+    signals::catch_traps(function)
+}
+
+// FIXME: Traps are only caught on Unix-like systems
+#[cfg(not(unix))]
+pub(crate) fn catch_traps<T>(function: impl FnOnce() -> T) -> Result<T, JitterTrap> {
+    Ok(function())
+}
+
+#[cfg(unix)]
+mod signals {
+    use super::{resolve, JitterTrap, ResolvedTrap};
+
+    use libc::{c_int, c_void, siginfo_t};
+
+    use std::cell::Cell;
+    use std::ptr::{addr_of, addr_of_mut, null_mut};
+
+    extern "C" {
+        fn jitter_catch_traps(buffer: *mut *mut c_void, callback: extern "C" fn(*mut c_void), payload: *mut c_void) -> c_int;
+        fn jitter_unwind(buffer: *mut c_void) -> !;
+    }
+
+    thread_local! {
+        /// Jump buffer of the innermost guarded call (null outside of one)
+        static JUMP_BUFFER: Cell<*mut c_void> = const { Cell::new(null_mut()) };
+        /// The trap which unwound the innermost guarded call (resolved by the signal handler)
+        static TRAPPED: Cell<Option<ResolvedTrap>> = const { Cell::new(None) };
+    }
+
+    const SIGNALS: [c_int; 4] = [libc::SIGSEGV, libc::SIGBUS, libc::SIGILL, libc::SIGFPE];
+
+    /// Handlers installed before ours (indexed like `SIGNALS`)
+    static mut PREVIOUS_HANDLERS: [libc::sigaction; 4] = unsafe { std::mem::zeroed() };
+
+    static INSTALL_HANDLERS: std::sync::Once = std::sync::Once::new();
+
+    pub(super) fn install_handlers() {
+        INSTALL_HANDLERS.call_once(|| unsafe {
+            for (index, signal) in SIGNALS.iter().enumerate() {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = handle_signal as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                let previous = addr_of_mut!(PREVIOUS_HANDLERS[index]);
+                if libc::sigaction(*signal, &action, previous) != 0 {
+                    panic!("Failed to install a handler for signal {}", signal);
+                }
+            }
+        });
+    }
+
+    extern "C" fn call_closure<F: FnMut()>(payload: *mut c_void) {
+        unsafe { (*(payload as *mut F))() }
+    }
+
+    /// The callback (and its payload) which `jitter_catch_traps` calls
+    fn callback<F: FnMut()>(closure: &mut F) -> (extern "C" fn(*mut c_void), *mut c_void) {
+        (call_closure::<F>, closure as *mut F as *mut c_void)
+    }
+
+    pub(super) fn catch_traps<T>(function: impl FnOnce() -> T) -> Result<T, JitterTrap> {
+        let mut function = Some(function);
+        let mut result = None;
+        let mut closure = || result = Some((function.take().expect("called once"))());
+        let (callback, payload) = callback(&mut closure);
+
+        // Accessed before any trap, so that the signal handler doesn't initialize it (which may allocate)
+        TRAPPED.with(|trapped| trapped.take());
+
+        // Guarded calls may be nested (Jitter -> Rust -> Jitter)
+        let previous = JUMP_BUFFER.with(|buffer| buffer.get());
+        let returned = JUMP_BUFFER.with(|buffer| unsafe { jitter_catch_traps(buffer.as_ptr(), callback, payload) });
+        JUMP_BUFFER.with(|buffer| buffer.set(previous));
+
+        if returned != 0 {
+            return Ok(result.expect("returned"));
+        }
+
+        let trap = TRAPPED.with(|trapped| trapped.take()).expect("trap resolved before unwinding");
+        Err(trap.into())
+    }
+
+    unsafe extern "C" fn handle_signal(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+        let buffer = JUMP_BUFFER.with(|buffer| buffer.get());
+
+        if !buffer.is_null() {
+            if let Some(trap) = program_counter(context).and_then(resolve) {
+                TRAPPED.with(|trapped| trapped.set(Some(trap)));
+                jitter_unwind(buffer);
+            }
+        }
+
+        // Not raised by JIT-compiled code, so whoever handled the signal before does so now
+        let index = SIGNALS.iter().position(|handled| *handled == signal).expect("handled signal");
+        let previous = &*addr_of!(PREVIOUS_HANDLERS[index]);
+
+        match previous.sa_sigaction {
+            // Raised again (with the original disposition) once this returns
+            libc::SIG_DFL | libc::SIG_IGN => {
+                libc::sigaction(signal, previous, null_mut());
+            }
+
+            handler if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+                let handler: extern "C" fn(c_int, *mut siginfo_t, *mut c_void) = std::mem::transmute(handler);
+                handler(signal, info, context);
+            }
+
+            handler => {
+                let handler: extern "C" fn(c_int) = std::mem::transmute(handler);
+                handler(signal);
+            }
+        }
+    }
+
+    /// Address of the instruction which raised a signal
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    unsafe fn program_counter(context: *mut c_void) -> Option<usize> {
+        let context = &*(context as *const libc::ucontext_t);
+        Some(context.uc_mcontext.gregs[libc::REG_RIP as usize] as usize)
+    }
+
+    /// Address of the instruction which raised a signal
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    unsafe fn program_counter(context: *mut c_void) -> Option<usize> {
+        let context = &*(context as *const libc::ucontext_t);
+        Some(context.uc_mcontext.pc as usize)
+    }
+
+    // FIXME: Other platforms keep the program counter elsewhere, so their traps aren't caught yet
+    #[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+    unsafe fn program_counter(_context: *mut c_void) -> Option<usize> {
+        None
+    }
+}
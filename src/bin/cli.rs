@@ -1,31 +1,92 @@
 // TODO: CLI interface for the compiler (isolated JIT code, no embedded env.)
 
-fn main() {
-    let input: Vec<String> = std::env::args().collect();
-
-    println!("
+const USAGE: &str = "
 Usage:
   jitter INPUT_PATH --FLAGS       Run a jitter file
+  jitter build INPUT_PATH --FLAGS Compile a jitter file into an executable (linked by `$CC` or `cc`)
   jitter                          Open jitter REPL session
 
 Flags:
   --help FLAG                     Display a flag's help information
-  --output OUTPUT_PATH            Specify file output path
+  --output, -o OUTPUT_PATH        Specify file output path
+  --target TRIPLE                 Compile for another target (e.g. `aarch64-unknown-linux-gnu`)
+  --error-format FORMAT           Report errors as `human` (default) or `json` lines
+  --color                         Use ANSI colours for `human` errors
   --CLIF                          Output Cranelift IR to a file
   --AST                           Output the jitter AST to a file
 ...
 
 Run 'jitter --help FLAG' for more detailed information
-");
+";
+
+fn main() {
+    // Skip the executable path
+    let mut input: Vec<String> = std::env::args().skip(1).collect();
+
+    let build = input.first().map(|command| command == "build").unwrap_or(false);
+    if build {
+        input.remove(0);
+    }
+
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut target = target_lexicon::Triple::host();
+    let mut json_errors = false;
+    let mut colored_errors = false;
+    let mut emit_clif = false;
 
     let mut i = 0;
     while i < input.len() {
         match input[i].as_str() {
-            // TODO: this
             "--help" => {
+                // TODO: Help messages for the following flag
+                println!("{}", USAGE);
+                return;
+            }
+
+            "--error-format" => {
+                i += 1;
+
+                match input.get(i).map(|format| format.as_str()) {
+                    Some("json") => json_errors = true,
+                    Some("human") => json_errors = false,
+                    _ => {
+                        eprintln!("Expected `human` or `json` after `--error-format`");
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            "--color" => colored_errors = true,
+
+            "--CLIF" => emit_clif = true,
+
+            "--output" | "-o" => {
                 i += 1;
 
-                // TODO: Help messages
+                match input.get(i) {
+                    Some(path) => output_path = Some(path.to_owned()),
+                    None => {
+                        eprintln!("Expected a path after `{}`", input[i - 1]);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            "--target" => {
+                i += 1;
+
+                match input.get(i).map(|triple| triple.parse()) {
+                    Some(Ok(triple)) => target = triple,
+                    _ => {
+                        eprintln!("Expected a target triple after `--target`");
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            path if !path.starts_with("--") && source_path.is_none() => {
+                source_path = Some(path.to_owned());
             }
 
             _ => {
@@ -35,4 +96,75 @@ Run 'jitter --help FLAG' for more detailed information
 
         i += 1;
     }
-}
\ No newline at end of file
+
+    let source_path = match source_path {
+        Some(path) => path,
+        None => {
+            // TODO: REPL
+            println!("{}", USAGE);
+            return;
+        }
+    };
+
+    // JSON goes to stdout so that tools can consume it directly
+    let report = |message: &str| if json_errors {
+        println!("{}", message);
+    } else {
+        eprintln!("{}", message);
+    };
+
+    if build {
+        // `main.jitter` -> `main`
+        let output_path = output_path.unwrap_or_else(|| {
+            std::path::Path::new(&source_path).with_extension("").to_string_lossy().into_owned()
+        });
+
+        let result = jitter::backend::object::JitterObjectBuilder::new(target)
+            .with_source_path(&source_path)
+            .with_json_errors(json_errors)
+            .with_colored_errors(colored_errors)
+            .build_executable(&output_path);
+
+        if let Err(errors) = result {
+            report(&errors);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let context = jitter::backend::jit::JitterContextBuilder::new()
+        .with_source_path(&source_path)
+        .with_json_errors(json_errors)
+        .with_colored_errors(colored_errors)
+        .emit_clif(emit_clif)
+        .build();
+
+    // TODO: Run `main`
+    let context = match context {
+        Ok(context) => context,
+        Err(errors) => {
+            report(&errors);
+            std::process::exit(1);
+        }
+    };
+
+    context.warnings().iter().for_each(|warning| report(warning));
+
+    if emit_clif {
+        // `main.jitter` -> `main.clif`
+        let clif_path = output_path.unwrap_or_else(|| {
+            std::path::Path::new(&source_path).with_extension("clif").to_string_lossy().into_owned()
+        });
+
+        let clif = context.function_names().into_iter()
+            .filter_map(|name| context.clif_for(name))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if let Err(error) = std::fs::write(&clif_path, clif) {
+            eprintln!("Failed to write `{}`: {}", clif_path, error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use crate::Span;
+use crate::source_map::{SourceMap, FileId, ExpansionKind};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+
+// ANSI escape codes used when rendering with colour
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Labels spanning more lines than this only display their first and last lines
+const MAX_LABEL_LINES: usize = 4;
+
+/// line number -> (start column, end column, label index) for each label touching that line
+type AnnotatedLines = BTreeMap<usize, Vec<(usize, usize, usize)>>;
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span of source code annotated with a message
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// Primary labels point at the cause of the diagnostic (`^^^`).
+    /// Secondary labels add context (`---`).
+    pub is_primary: bool,
+}
+
+/// A machine-applicable fix: replace the code at `span` with `replacement`.
+/// Empty spans (`start == end`) insert text.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+/// An error/warning along with everything needed to explain it
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier for the kind of diagnostic (see `codes`)
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String) -> Self {
+        Self {
+            severity,
+            code: None,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: String) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    /// Points at the cause of the diagnostic. `message` may be empty.
+    pub fn with_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label {
+            span,
+            message,
+            is_primary: true,
+        });
+        self
+    }
+
+    /// Points at related code. `message` may be empty.
+    pub fn with_secondary_label(mut self, span: Span, message: String) -> Self {
+        self.labels.push(Label {
+            span,
+            message,
+            is_primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_suggestion(mut self, span: Span, replacement: String, message: String) -> Self {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement,
+            message,
+        });
+        self
+    }
+
+    /// Points the diagnostic at `span` unless it already has a location
+    pub fn at(self, span: Span) -> Self {
+        if self.labels.is_empty() {
+            self.with_label(span, String::new())
+        } else {
+            self
+        }
+    }
+
+    /// Span of the first primary label (if any)
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter()
+            .find(|label| label.is_primary)
+            .map(|label| label.span)
+    }
+
+    /// Renders the diagnostic like rustc does:
+    /// ```text
+    /// error: Expected `;` to terminate a statement. Found `let`
+    ///  --> path/file.jitter:11:5
+    ///    |
+    /// 11 |     let b: i32 = 2;
+    ///    |     ^^^
+    /// ```
+    /// Source lines are looked up in `sources` using each label's file.
+    /// ANSI colour codes are only emitted if `use_color` is set (plain text otherwise).
+    pub fn render(&self, sources: &SourceMap, use_color: bool) -> String {
+        // Returns the escape code only when colour is enabled
+        let paint = |code: &'static str| if use_color { code } else { "" };
+
+        // Labels are displayed per file, starting with the primary label's file
+        let mut files: Vec<FileId> = Vec::new();
+        let primary_file = self.primary_span().map(|span| span.file);
+        for file in primary_file.into_iter().chain(self.labels.iter().map(|label| label.span.file)) {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+
+        let mut annotated_files: Vec<(FileId, AnnotatedLines)> = Vec::new();
+        for file in &files {
+            let mut annotated_lines = AnnotatedLines::new();
+
+            for (index, label) in self.labels.iter().enumerate().filter(|(_, label)| label.span.file == *file) {
+                let span = label.span;
+
+                for line in span.start_line..=span.end_line.max(span.start_line) {
+                    let is_first = line == span.start_line;
+                    let is_last = line >= span.end_line;
+
+                    // Skip the middle of long spans
+                    if !is_first && !is_last && line - span.start_line >= MAX_LABEL_LINES - 1 {
+                        continue;
+                    }
+
+                    let text = sources.line(*file, line).unwrap_or("");
+
+                    let start = if is_first {
+                        span.start_column
+                    } else {
+                        // Skip indentation
+                        text.chars().take_while(|c| c.is_whitespace()).count() + 1
+                    };
+                    let end = if is_last {
+                        span.end_column
+                    } else {
+                        text.chars().count() + 1
+                    };
+
+                    annotated_lines.entry(line).or_default().push((start, end, index));
+                }
+            }
+
+            annotated_files.push((*file, annotated_lines));
+        }
+
+        // Width of the line number gutter
+        let gutter = annotated_files.iter()
+            .filter_map(|(_, lines)| lines.keys().last())
+            .max()
+            .map(|line| line.to_string().len())
+            .unwrap_or(1);
+        let empty_gutter = " ".repeat(gutter);
+
+        let mut output = String::new();
+
+        // Header
+        let code = self.code.map(|code| format!("[{}]", code)).unwrap_or_default();
+        let _ = writeln!(output, "{}{}{}{}: {}{}{}",
+            paint(self.severity.color()), self.severity, code, paint(RESET),
+            paint(BOLD), self.message, paint(RESET));
+
+        for (file_index, (file, annotated_lines)) in annotated_files.iter().enumerate() {
+            // Location (`-->` for the main file, `:::` for others)
+            let location = self.labels.iter()
+                .filter(|label| label.span.file == *file)
+                .min_by_key(|label| !label.is_primary)
+                .map(|label| label.span)
+                .expect("Files are only listed for labels");
+            let arrow = if file_index == 0 { "-->" } else { ":::" };
+            let _ = writeln!(output, "{}{}{}{} {}:{}:{}",
+                empty_gutter, paint(BLUE), arrow, paint(RESET), sources.path(*file), location.start_line, location.start_column);
+
+            // Source snippet
+            let _ = writeln!(output, "{} {}|{}", empty_gutter, paint(BLUE), paint(RESET));
+
+            let mut previous_line = None;
+            for (line, markers) in annotated_lines {
+                // Indicate skipped lines
+                if let Some(previous) = previous_line {
+                    if line - previous > 1 {
+                        let _ = writeln!(output, "{}...{}", paint(BLUE), paint(RESET));
+                    }
+                }
+                previous_line = Some(*line);
+
+                let text = sources.line(*file, *line).unwrap_or("");
+                let _ = writeln!(output, "{}{:>width$} |{} {}", paint(BLUE), line, paint(RESET), text, width = gutter);
+
+                for (start, end, index) in markers {
+                    let label = &self.labels[*index];
+
+                    // Reuse the line's own whitespace so that tabs stay aligned
+                    let padding = text.chars()
+                        .take(start.saturating_sub(1))
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect::<String>();
+
+                    let (marker, color) = if label.is_primary {
+                        ('^', self.severity.color())
+                    } else {
+                        ('-', BLUE)
+                    };
+                    let underline = marker.to_string().repeat(end.saturating_sub(*start).max(1));
+
+                    // The message goes at the end of the label's final line
+                    let message = if *line >= label.span.end_line && !label.message.is_empty() {
+                        format!(" {}", label.message)
+                    } else {
+                        String::new()
+                    };
+
+                    let _ = writeln!(output, "{} {}|{} {}{}{}{}{}",
+                        empty_gutter, paint(BLUE), paint(RESET),
+                        padding, paint(color), underline, message, paint(RESET));
+                }
+            }
+        }
+
+        // Notes (explaining where the primary span came from, then the user's notes)
+        let notes = self.origin_notes(sources).into_iter()
+            .chain(self.notes.iter().cloned())
+            .collect::<Vec<String>>();
+
+        if !notes.is_empty() {
+            let _ = writeln!(output, "{} {}|{}", empty_gutter, paint(BLUE), paint(RESET));
+        }
+        for note in &notes {
+            let _ = writeln!(output, "{} {}={} {}note{}: {}",
+                empty_gutter, paint(BLUE), paint(RESET), paint(BOLD), paint(RESET), note);
+        }
+        for suggestion in &self.suggestions {
+            let _ = writeln!(output, "{} {}={} {}help{}: {}: `{}`",
+                empty_gutter, paint(BLUE), paint(RESET), paint(BOLD), paint(RESET), suggestion.message, suggestion.replacement);
+        }
+
+        output
+    }
+
+    /// Serializes the diagnostic as a single line of JSON (for editors and CI):
+    /// ```text
+    /// {"file":"main.jitter","start":120,"end":123,"line":11,"column":5,"end_line":11,"end_column":8,
+    ///  "severity":"error","code":"E0100","message":"..","labels":[..],"notes":[..],"suggestions":[..]}
+    /// ```
+    /// Location fields are `null` when the diagnostic has no primary span.
+    pub fn to_json(&self, sources: &SourceMap) -> String {
+        let mut json = String::from("{");
+
+        let _ = write!(json, "{}", match self.primary_span() {
+            Some(span) => json_location(sources, span),
+            None => String::from(r#""file":null,"start":null,"end":null,"line":null,"column":null,"end_line":null,"end_column":null"#),
+        });
+
+        let _ = write!(json, r#","severity":{},"code":{},"message":{}"#,
+            json_string(&self.severity.to_string()),
+            self.code.map(json_string).unwrap_or_else(|| String::from("null")),
+            json_string(&self.message));
+
+        let labels = self.labels.iter().map(|label| {
+            format!(r#"{{{},"primary":{},"message":{}}}"#, json_location(sources, label.span), label.is_primary, json_string(&label.message))
+        }).collect::<Vec<String>>();
+        let _ = write!(json, r#","labels":[{}]"#, labels.join(","));
+
+        let notes = self.origin_notes(sources).iter()
+            .chain(self.notes.iter())
+            .map(|note| json_string(note))
+            .collect::<Vec<String>>();
+        let _ = write!(json, r#","notes":[{}]"#, notes.join(","));
+
+        let suggestions = self.suggestions.iter().map(|suggestion| {
+            format!(r#"{{{},"replacement":{},"message":{}}}"#,
+                json_location(sources, suggestion.span), json_string(&suggestion.replacement), json_string(&suggestion.message))
+        }).collect::<Vec<String>>();
+        let _ = write!(json, r#","suggestions":[{}]}}"#, suggestions.join(","));
+
+        json
+    }
+
+    /// Describes the expansions and `#include`s which produced the primary span
+    fn origin_notes(&self, sources: &SourceMap) -> Vec<String> {
+        let span = match self.primary_span() {
+            Some(span) => span,
+            None => return Vec::new(),
+        };
+
+        let location = |span: Span| format!("{}:{}:{}", sources.path(span.file), span.start_line, span.start_column);
+
+        let mut notes = Vec::new();
+
+        for expansion in sources.expansion_chain(span) {
+            if let ExpansionKind::Define { definition, .. } = &expansion.kind {
+                notes.push(format!("{} (defined at {})", expansion.describe(), location(*definition)));
+            } else {
+                notes.push(expansion.describe());
+            }
+        }
+
+        let mut file = span.file;
+        while let Some(include_site) = sources.file(file).and_then(|source| source.included_from) {
+            notes.push(format!("`{}` is included from {}", sources.path(file), location(include_site)));
+            file = include_site.file;
+        }
+
+        notes
+    }
+}
+
+/// Header-only form (no source context)
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(span) = self.primary_span() {
+            write!(f, " ({}:{})", span.start_line, span.start_column)?;
+        }
+        Ok(())
+    }
+}
+
+/// `"file":..,"start":..,"end":..,"line":..,"column":..,"end_line":..,"end_column":..`
+fn json_location(sources: &SourceMap, span: Span) -> String {
+    format!(r#""file":{},"start":{},"end":{},"line":{},"column":{},"end_line":{},"end_column":{}"#,
+        json_string(sources.path(span.file)), span.start, span.end,
+        span.start_line, span.start_column, span.end_line, span.end_column)
+}
+
+/// Quotes and escapes a string for JSON
+fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Stable diagnostic codes.  
+/// Codes are never reused. Retired codes stay listed here (commented out).
+pub mod codes {
+    /// A type name does not refer to any known type
+    pub const UNKNOWN_TYPE: &str = "E0001";
+    /// Two types (structs, etc.) share a name
+    pub const DUPLICATE_TYPE: &str = "E0002";
+    /// Two functions share a name
+    pub const DUPLICATE_FUNCTION: &str = "E0003";
+    /// A called function does not exist
+    pub const UNKNOWN_FUNCTION: &str = "E0004";
+    /// Two variables share a name in the same scope
+    pub const DUPLICATE_VARIABLE: &str = "E0005";
+    /// A variable is used but not in scope
+    pub const UNKNOWN_VARIABLE: &str = "E0006";
+    /// A type does not have the accessed field
+    pub const UNKNOWN_FIELD: &str = "E0007";
+    /// A private field is accessed
+    pub const PRIVATE_FIELD: &str = "E0008";
+    /// A field constructor does not assign every field
+    pub const MISSING_FIELDS: &str = "E0009";
+    /// An expression's type does not match the expected type
+    pub const MISMATCHED_TYPES: &str = "E0010";
+    /// A function is called with the wrong number of arguments
+    pub const WRONG_ARGUMENT_COUNT: &str = "E0011";
+    /// A function returns a type other than its declared type
+    pub const MISMATCHED_RETURN_TYPE: &str = "E0012";
+    /// An immutable variable (or data behind a `&` reference) is assigned to or mutably borrowed
+    pub const ASSIGN_TO_IMMUTABLE: &str = "E0013";
+    /// A custom operator is used but never defined
+    pub const UNKNOWN_OPERATOR: &str = "E0014";
+    /// A field is accessed on a type which has no fields
+    pub const NO_FIELDS: &str = "E0015";
+    /// A literal does not fit in its (possibly inferred) type
+    pub const LITERAL_OUT_OF_RANGE: &str = "E0016";
+    /// A type could not be inferred
+    pub const TYPE_ANNOTATIONS_NEEDED: &str = "E0017";
+    /// A function with a return type can reach the end of its body without returning
+    pub const MISSING_RETURN: &str = "E0018";
+    /// A variable is read before it is given a value
+    pub const UNINITIALIZED_VARIABLE: &str = "E0019";
+    /// The left-hand side of an assignment is not a variable, field, or dereference
+    pub const INVALID_ASSIGNMENT: &str = "E0020";
+    /// A function returns a reference to one of its own locals (which no longer exist once it returns)
+    pub const RETURN_LOCAL_REFERENCE: &str = "E0021";
+    /// Structs contain one another by value, so they would be infinitely sized
+    pub const RECURSIVE_TYPE: &str = "E0022";
+    /// A builtin attribute (`@packed`, `@align`, ..) is used incorrectly
+    pub const INVALID_ATTRIBUTE: &str = "E0023";
+    /// A struct's layout does not match its `@assert_layout`
+    pub const LAYOUT_MISMATCH: &str = "E0024";
+
+    /// Code which can never run (warning)
+    pub const UNREACHABLE_CODE: &str = "W0001";
+
+    /// Syntax error found while parsing
+    pub const SYNTAX_ERROR: &str = "E0100";
+}
@@ -67,8 +67,91 @@ pub trait Nodify<T> : Sized {
 
 impl<T> Nodify<T> for T {
     fn nodify(self) -> Node<Self> {
-        // FIXME: Create a way to specify spans as being meta-created?
-        Node::new(self, crate::Span::new(0, 0, 0, 0))
+        // Located once the parser receives the node (see `locate_generated_nodes`)
+        Node::new(self, crate::Span::generated())
+    }
+}
+
+/// Replaces the spans of extension-created nodes with `call_site`.
+/// Nodes which the extension kept from the original item keep their spans.
+pub(crate) fn locate_generated_nodes(item: &mut TopLevel, call_site: crate::Span) {
+    fn locate<T>(node: &mut Node<T>, call_site: crate::Span) {
+        if node.span.is_generated() {
+            node.span = call_site;
+        }
+    }
+
+    fn locate_block(block: &mut BlockExpression, call_site: crate::Span) {
+        locate(&mut block.block, call_site);
+
+        for statement in &mut block.block.item {
+            locate(statement, call_site);
+
+            match &mut statement.item {
+                Statement::Let { value, .. } => {
+                    if let Some(value) = value {
+                        locate_expression(value, call_site);
+                    }
+                }
+                Statement::Assign { lhs, operator, expression } => {
+                    locate_expression(lhs, call_site);
+                    locate(operator, call_site);
+                    locate_expression(expression, call_site);
+                }
+                Statement::ImplicitReturn { expression, .. }
+                | Statement::Return { expression }
+                | Statement::Expression(expression) => locate_expression(expression, call_site),
+            }
+        }
+    }
+
+    fn locate_expression(expression: &mut Node<Expression>, call_site: crate::Span) {
+        locate(expression, call_site);
+
+        match &mut expression.item {
+            Expression::BinaryExpression { lhs, op, rhs, .. } => {
+                locate_expression(lhs, call_site);
+                locate(op, call_site);
+                locate_expression(rhs, call_site);
+            }
+            Expression::UnaryExpression { op, expr, .. } => {
+                locate(op, call_site);
+                locate_expression(expr, call_site);
+            }
+            Expression::FieldConstructor { fields, .. } => {
+                fields.values_mut().for_each(|field| locate_expression(field, call_site));
+            }
+            Expression::FieldAccess { base_expr, .. } => locate_expression(base_expr, call_site),
+            Expression::FunctionCall { inputs, .. } => {
+                inputs.iter_mut().for_each(|input| locate_expression(input, call_site));
+            }
+            Expression::Block(block) => locate_block(block, call_site),
+            Expression::Literal { .. } | Expression::Ident { .. } => {}
+        }
+    }
+
+    match item {
+        TopLevel::Function(function) => {
+            locate(function, call_site);
+            locate(&mut function.prototype, call_site);
+            locate(&mut function.prototype.parameters, call_site);
+            for parameter in &mut function.prototype.parameters.item {
+                locate(parameter, call_site);
+            }
+            locate(&mut function.body, call_site);
+            locate_block(&mut function.body, call_site);
+        }
+
+        TopLevel::Struct(struct_) => {
+            locate(struct_, call_site);
+            locate(&mut struct_.fields, call_site);
+            for field in &mut struct_.fields.item {
+                locate(field, call_site);
+            }
+        }
+
+        // Extensions can only produce the above
+        _ => {}
     }
 }
 
@@ -3,6 +3,8 @@
 
 use std::collections::HashMap;
 
+use crate::source_map::{SourceMap, FileId, Expansion, ExpansionKind};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Keyword {
     Binary,
@@ -69,6 +71,8 @@ pub enum Token {
 
     OpenSquareBracket,  // '['
     CloseSquareBracket, // ']'
+
+    EndOfFile,          // Appended by the parser (special case)
 }
 
 impl std::fmt::Display for Token {
@@ -127,6 +131,7 @@ impl std::fmt::Display for Token {
             Token::CloseCurlyBrace => "}".to_owned(),
             Token::OpenSquareBracket => "[".to_owned(),
             Token::CloseSquareBracket => "]".to_owned(),
+            Token::EndOfFile => "end of file".to_owned(),
         };
 
         write!(f, "{}", string)
@@ -149,12 +154,7 @@ impl Token {
     pub fn spanned(self, start_line: usize, start_column: usize, end_line: usize, end_column: usize) -> SpannedToken {
         SpannedToken {
             token: self,
-            span: crate::Span {
-                start_line,
-                start_column,
-                end_line,
-                end_column,
-            },
+            span: crate::Span::new(start_line, start_column, end_line, end_column),
         }
     }
 }
@@ -178,6 +178,8 @@ pub struct Preprocessor {
     state: PreprocessorState,
 
     define_from: Token,
+    /// Location of `from` (for reporting expansions)
+    define_span: crate::Span,
     define_to: Vec<Token>,
 
     include_path: String,
@@ -191,6 +193,7 @@ impl Preprocessor {
             // NOTE: This is just a default. Will be replaced before use
             //       Pound cannot appear in this position, so this safe anyway.
             define_from: Token::Pound,
+            define_span: crate::Span::generated(),
             
             define_to: Vec::new(),
 
@@ -203,10 +206,23 @@ impl Preprocessor {
     }
 }
 
+/// Tokens which replace a single token, along with where the rule came from
+struct Replacement {
+    tokens: Vec<Token>,
+    kind: ExpansionKind,
+}
+
 pub struct Lexer {
     file_path: String,
     input: String,
     position: usize,
+    /// Byte offset of `position`
+    byte_position: usize,
+    last_byte_position: usize,
+
+    /// Files lexed so far (this one, and anything it includes)
+    source_map: SourceMap,
+    file_id: FileId,
 
     last_line: usize,
     last_column: usize,
@@ -218,19 +234,31 @@ pub struct Lexer {
     preprocessor: Preprocessor,
 
     // Token replacements (seen_token -> becomes)
-    custom_replacements: HashMap<Token, Vec<Token>>,
+    custom_replacements: HashMap<Token, Replacement>,
 }
 
 impl Lexer {
     pub fn new(file_path: String, input: String, strip_whitespace: bool) -> Self {
+        Self::with_source_map(SourceMap::new(), None, file_path, input, strip_whitespace)
+    }
+
+    /// Creates a lexer whose file is added to an existing `SourceMap`
+    fn with_source_map(mut source_map: SourceMap, included_from: Option<crate::Span>, file_path: String, input: String, strip_whitespace: bool) -> Self {
+        let file_id = source_map.add_file(file_path.clone(), input.clone(), included_from);
+
         Self {
             file_path,
             input,
             position: 0,
+            byte_position: 0,
+            last_byte_position: 0,
+            source_map,
+            file_id,
             last_line: 0,
             last_column: 0,
             current_line: 1,
-            current_column: 0,
+            // Columns are 1-based (see the new line case in `lex_next_token`)
+            current_column: 1,
             strip_whitespace,
 
             preprocessor: Preprocessor::new(),
@@ -255,17 +283,38 @@ impl Lexer {
                 panic!("Only single-token `string`s are currently supported. Found {:?}", input);
             }
 
-            self.custom_replacements.insert(input[0].token.clone(), output);
+            self.custom_replacements.insert(input[0].token.clone(), Replacement {
+                tokens: output,
+                kind: ExpansionKind::Callback {
+                    pattern: cb.string.to_owned(),
+                    replacement: cb.replacement.to_owned(),
+                },
+            });
         }
     }
 
     fn make_spanned(&self, token: Token) -> SpannedToken {
-        token.spanned(self.last_line, self.last_column, self.current_line, self.current_column)
+        let mut spanned = token.spanned(self.last_line, self.last_column, self.current_line, self.current_column);
+        spanned.span.file = self.file_id;
+        spanned.span.start = self.last_byte_position;
+        spanned.span.end = self.byte_position;
+        spanned
     }
 
     pub fn reset_last_position(&mut self) {
         self.last_line = self.current_line;
         self.last_column = self.current_column;
+        self.last_byte_position = self.byte_position;
+    }
+
+    /// Returns every file lexed so far along with any expansions
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// Moves the `SourceMap` out of the lexer (for use after lexing)
+    pub fn take_source_map(&mut self) -> SourceMap {
+        std::mem::take(&mut self.source_map)
     }
 
     /// Converts the given input to tokens. `file_path` is used only for printing errors.
@@ -276,6 +325,7 @@ impl Lexer {
     // TODO: Bounds check?
     /// Advances the lexer forward one character
     fn advance(&mut self) {
+        self.byte_position += self.input[self.byte_position..].chars().next().map_or(1, char::len_utf8);
         self.position += 1;
         self.current_column += 1;
     }
@@ -366,6 +416,7 @@ impl Lexer {
                             //        changed to something more clear.
                             if self.preprocessor.define_from == Token::Pound {
                                 self.preprocessor.define_from = token.token;
+                                self.preprocessor.define_span = token.span;
                                 self.preprocessor.state = PreprocessorState::AwaitingNewLine;
                             } 
                             continue;
@@ -384,7 +435,7 @@ impl Lexer {
                                     
                                     // 1. Read the file to string
                                     // println!("Inserting file: {:?}", target_path);
-                                    let target_source = std::fs::read_to_string(target_path);
+                                    let target_source = std::fs::read_to_string(&target_path);
                                     if target_source.is_err() {
                                         errors.push(format!("Failed to read file: `./{}`", &string));
                                         continue;
@@ -392,7 +443,17 @@ impl Lexer {
                                     let target_source = target_source.unwrap();
 
                                     // 2. Lex the file, obtaining spanned tokens
-                                    let target_tokens = Lexer::lex_str(string, target_source, true);
+                                    //    The included file shares this lexer's `SourceMap` so its spans stay traceable
+                                    let mut include_lexer = Lexer::with_source_map(
+                                        self.take_source_map(), 
+                                        Some(token.span), 
+                                        target_path.to_string_lossy().into_owned(), 
+                                        target_source, 
+                                        true
+                                    );
+                                    let target_tokens = include_lexer.lex();
+                                    self.source_map = include_lexer.take_source_map();
+
                                     // 3. Insert the tokens into this lexer (via `tokens.push()`)
                                     target_tokens.into_iter().for_each(|t| tokens.push(t));
         
@@ -409,7 +470,16 @@ impl Lexer {
                                 // Finalize a `#define`
                                 if self.preprocessor.define_from != Token::Pound {
                                     // println!("Registering definition: ({}) -> {:?}", self.preprocessor.define_from, self.preprocessor.define_to);
-                                    self.custom_replacements.insert(self.preprocessor.define_from.clone(), self.preprocessor.define_to.clone());
+                                    self.custom_replacements.insert(self.preprocessor.define_from.clone(), Replacement {
+                                        tokens: self.preprocessor.define_to.clone(),
+                                        kind: ExpansionKind::Define {
+                                            name: match &self.preprocessor.define_from {
+                                                Token::Ident(name) => name.clone(),
+                                                other => other.to_string(),
+                                            },
+                                            definition: self.preprocessor.define_span,
+                                        },
+                                    });
                                 }
 
                                 self.preprocessor.reset();
@@ -437,8 +507,14 @@ impl Lexer {
                     }
 
                     if let Some(rule) = self.custom_replacements.get(&token.token) {
-                        let span = token.span;
-                        for t in rule {
+                        // Replaced tokens are located at the replaced token, but remember why they exist
+                        let expansion = self.source_map.add_expansion(Expansion {
+                            kind: rule.kind.clone(),
+                            call_site: token.span,
+                        });
+                        let span = token.span.expanded(expansion);
+
+                        for t in &rule.tokens {
                             tokens.push(SpannedToken {
                                 token: t.clone(),
                                 span: span.clone(),
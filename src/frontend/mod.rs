@@ -3,7 +3,67 @@ pub mod parse;
 pub mod validate;
 pub mod modules;
 
+use validate::context::Context as ValidationContext;
+use validate::target::TargetInfo;
+
 pub struct LexerCallback<'a> {
     pub string: &'a str,
     pub replacement: &'a str,
-}
\ No newline at end of file
+}
+
+/// Lexes, parses, and validates a source file for the given target.  
+/// Returns the validated code along with any (rendered) warnings and the source files involved, or every error found.
+pub(crate) fn validate_source(
+    source_path: &str,
+    extension_path: &str,
+    lexer_callbacks: Vec<LexerCallback>,
+    target: TargetInfo,
+    colored_errors: bool,
+    json_errors: bool,
+) -> Result<(ValidationContext, Vec<String>, crate::source_map::SourceMap), String> {
+    // Lex
+    let input = &std::fs::read_to_string(source_path).expect("Read input");
+    let mut lexer = lex::Lexer::new(source_path.to_owned(), input.to_owned(), true);
+    lexer.parse_callbacks(lexer_callbacks);
+
+    let tokens = lexer.lex();
+    // Parse
+    let mut parser = parse::Parser::new(source_path, tokens);
+    parser.set_extension_path(extension_path.to_string());
+    parser.set_source_map(lexer.take_source_map());
+    let ast = parser.parse_ast(String::from(""));
+    // println!("AST: {:#?}", ast);
+
+    // Analyze
+    let mut validation_context = ValidationContext::with_target(target);
+    let validation_result = validation_context.validate(ast);
+
+    // Report every syntax error (plus anything validation found outside of them)
+    let mut errors = parser.diagnostics();
+    if let Err(e) = validation_result {
+        errors.push(e);
+    }
+
+    let source_map = parser.take_source_map();
+    let render = |diagnostic: &crate::diagnostic::Diagnostic| if json_errors {
+        diagnostic.to_json(&source_map)
+    } else {
+        diagnostic.render(&source_map, colored_errors)
+    };
+
+    // Warnings are reported alongside errors, or kept for the caller otherwise
+    let warnings = validation_context.warnings.iter()
+        .map(render)
+        .collect::<Vec<String>>();
+
+    if !errors.is_empty() {
+        let errors = errors.iter()
+            .map(render)
+            .chain(warnings)
+            .collect::<Vec<String>>();
+
+        return Err(errors.join("\n"));
+    }
+
+    Ok((validation_context, warnings, source_map))
+}
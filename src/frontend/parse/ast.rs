@@ -6,8 +6,8 @@ use crate::frontend::validate::types::Type;
 pub struct Node<NodeType> {
     pub item: NodeType,
     pub span: crate::Span,
-    // TODO: This flag might not be needed (just knowing at least one error exists is enough)
-    // pub is_error_recovery_node: bool,
+    /// Set when the node stands in for (or contains) source that failed to parse
+    pub is_error_recovery_node: bool,
 }
 
 // Removes the need to add `.item` everywhere
@@ -29,14 +29,19 @@ impl<T> Node<T> {
         Self {
             item,
             span,
-            // is_error_recovery_node: false,
+            is_error_recovery_node: false,
         }
     }
 
-    // pub fn poison(mut self) -> Self {
-    //     self.is_error_recovery_node = true;
-    //     self
-    // }
+    /// Marks the node as the product of error recovery
+    pub fn poison(mut self) -> Self {
+        self.is_error_recovery_node = true;
+        self
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.is_error_recovery_node
+    }
 }
 
 ///////////////// AST VARIANTS /////////////////
@@ -176,6 +181,7 @@ pub struct Struct {
     pub name: String,
     pub fields: Node<StructFieldList>,
     pub is_public: bool,
+    pub attributes: Vec<Node<LayoutAttribute>>,
 }
 
 pub type StructFieldList = Vec<Node<StructField>>;
@@ -185,6 +191,23 @@ pub struct StructField {
     pub name: String,
     pub ty: Type,
     pub is_public: bool,
+    pub attributes: Vec<Node<LayoutAttribute>>,
+}
+
+/// Builtin attributes which change (or check) how a struct is laid out in memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutAttribute {
+    /// `@packed` -> fields are placed without any padding (structs only)
+    Packed,
+    /// `@align(N)` -> raises the alignment to at least `N` bytes
+    Align(usize),
+    /// `@assert_layout(size = N, align = N, offset = N)` -> fails to compile unless each given value matches.  
+    /// `offset` is only valid on fields.
+    AssertLayout {
+        size: Option<usize>,
+        align: Option<usize>,
+        offset: Option<usize>,
+    },
 }
 
 pub type FunctionParameterList = Vec<Node<FunctionParameter>>;
@@ -194,8 +217,15 @@ pub struct FunctionParameter {
     pub mutable: bool,
     pub name: String,
     pub ty: Type,
+    /// Assigned during validation
+    pub binding: Option<BindingId>,
 }
 
+/// Identifies a single variable binding (`let` or parameter) within a function.  
+/// Shadowed variables share a name, but never a `BindingId`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BindingId(pub u32);
+
 #[derive(Debug, Clone)]
 pub enum Statement {
     Let {
@@ -203,6 +233,8 @@ pub enum Statement {
         mutable: bool,
         ty: Type,
         value: Option<Node<Expression>>,
+        /// Assigned during validation
+        binding: Option<BindingId>,
     },
 
     Assign {
@@ -279,6 +311,8 @@ pub enum Expression {
     Ident {
         name: String,
         ty: Type,
+        /// The binding `name` refers to (assigned during validation)
+        binding: Option<BindingId>,
     },
 }
 
@@ -307,8 +341,8 @@ pub struct BlockExpression {
 
 #[derive(Debug, Clone)]
 pub enum Literal {
-    /// Integer of any type
-    Integer(isize),
+    /// Integer of any type (wide enough to hold every `u64` and `i128` literal)
+    Integer(i128),
     /// Floating point number of any type
     Float(f64),
     /// `()` type
@@ -319,6 +353,10 @@ pub enum Literal {
 pub enum UnaryOp {
     Negate,
     Not,
+    /// `&expr` or `&mut expr`
+    Reference { mutable: bool },
+    /// `*expr`
+    Dereference,
     Custom(Vec<Token>),
 }
 
@@ -2,40 +2,35 @@ pub mod ast;
 
 
 use crate::Span;
+use crate::diagnostic::{Diagnostic, Suggestion, codes};
+use crate::source_map::{SourceMap, Expansion, ExpansionKind};
 use ast::{Literal, Node};
 use super::lex::{self, Token, SpannedToken, Keyword};
 use crate::frontend::validate::types::Type;
 
 use std::collections::HashMap;
 
-// TODO: Return Results from everything.
-// TODO: Handle errors by simply return the expected node, but poisoned.
-//       Then, print all errors before exiting.
-
-// TODO: Replace this macro with a printed error message, then return the expected
-// token as a poisoned Node to allow the parser to continue.
-
-// TODO: Print the context of the error with the span underlined (like rustc does)
-
-/// Print error and its location, then exit without panic
+/// Record an error and its location, then bail out of the current parsing rule.
+/// The parser recovers at the nearest synchronization point (statement or top-level item).
 macro_rules! parser_error {
-    ( $path:expr, $span:expr, $($item:expr),+ ) => {
-        eprintln!(
-            "Parsing Error at {}:{}:{}:\n\n{}\n",
-            $path, $span.start_line, $span.start_column,
-            format!(  $($item,)+  )
-        ); 
-        
-        std::process::exit(1);
+    ( $parser:expr, $span:expr, $($item:expr),+ ) => {
+        return Err($parser.error($span, format!(  $($item,)+  )))
     };
 }
 
+/// Signals that a parsing rule failed. The error itself is stored by the `Parser`.
+#[derive(Debug)]
+pub struct ParseError;
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
 /// Represents an `@directive(inputs, ..)`
 // NOTE: This is not used by the AST. Rather, this is used to generate AST nodes.
 #[derive(Debug)]
 pub struct MetaTag<'a> {
     directive: &'a str,
     inputs: Vec<&'a str>,
+    span: Span,
 }
 
 pub struct Parser<'a> {
@@ -45,6 +40,10 @@ pub struct Parser<'a> {
     position: std::cell::RefCell<usize>,
     extension_path: String,
     extensions: std::cell::RefCell<HashMap<String, super::super::extension::Extension>>,
+    /// Every error encountered so far
+    errors: std::cell::RefCell<Vec<Diagnostic>>,
+    /// Files the tokens came from. Extension expansions are recorded here.
+    source_map: std::cell::RefCell<SourceMap>,
 }
 
 // NOTE: Some functions expect to parse only the desired token.
@@ -60,13 +59,22 @@ pub struct Parser<'a> {
 //       refers to the current token needing evaluation (rather than the most recently
 //       parsed token)
 impl<'a> Parser<'a> {
-    pub fn new(file_path: &'a str, tokens: Vec<SpannedToken>) -> Self {
+    pub fn new(file_path: &'a str, mut tokens: Vec<SpannedToken>) -> Self {
+        // Terminate the stream so that errors at the end of a file can be reported
+        let end_span = tokens.last().map(|t| t.span).unwrap_or_else(|| Span::new(1, 0, 1, 0));
+        tokens.push(SpannedToken {
+            token: Token::EndOfFile,
+            span: end_span,
+        });
+
         Self {
             file_path,
             tokens,
             position: std::cell::RefCell::new(0),
             extension_path: String::from("."),
             extensions: std::cell::RefCell::new(HashMap::new()),
+            errors: std::cell::RefCell::new(Vec::new()),
+            source_map: std::cell::RefCell::new(SourceMap::new()),
         }
     }
 
@@ -74,6 +82,115 @@ impl<'a> Parser<'a> {
         self.extension_path = path;
     }
 
+    /// Provides the `SourceMap` produced by the lexer
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = std::cell::RefCell::new(source_map);
+    }
+
+    /// Moves the `SourceMap` out of the parser (for use after parsing)
+    pub fn take_source_map(&self) -> SourceMap {
+        self.source_map.take()
+    }
+
+    /// Returns true if any syntax errors were found
+    pub fn has_errors(&self) -> bool {
+        !self.errors.borrow().is_empty()
+    }
+
+    /// Returns every syntax error found, in order of appearance
+    pub fn errors(&self) -> Vec<String> {
+        let source_map = self.source_map.borrow();
+
+        self.errors.borrow().iter().map(|error| {
+            let span = error.primary_span().expect("Parser errors are spanned");
+            // Errors may be located within included files
+            let path = source_map.file(span.file).map(|file| file.path.as_str()).unwrap_or(self.file_path);
+            format!("Parsing Error at {}:{}:{}:\n\n{}\n", path, span.start_line, span.start_column, error.message)
+        }).collect()
+    }
+
+    /// Returns every syntax error found as diagnostics (for rendering with the source)
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors.borrow().clone()
+    }
+
+    /// Stores an error to be reported once parsing completes
+    fn error(&self, span: &Span, message: String) -> ParseError {
+        self.errors.borrow_mut().push(Diagnostic::error(message).with_label(*span, String::new()).with_code(codes::SYNTAX_ERROR));
+        ParseError
+    }
+
+    /// Returns true if the token can begin a top-level item
+    fn is_top_level_start(token: &Token) -> bool {
+        matches!(token,
+            Token::At
+            | Token::Keyword(Keyword::Pub)
+            | Token::Keyword(Keyword::Extern)
+            | Token::Keyword(Keyword::Use)
+            | Token::Keyword(Keyword::Impl)
+            | Token::Keyword(Keyword::Fn)
+            | Token::Keyword(Keyword::Binary)
+            | Token::Keyword(Keyword::Unary)
+            | Token::Keyword(Keyword::Trait)
+            | Token::Keyword(Keyword::Struct)
+        )
+    }
+
+    /// Skips the remainder of a malformed statement.
+    /// Stops after a `;`, or before the `}` closing the current block or the next top-level item.
+    fn synchronize_statement(&self) {
+        // Nested blocks are skipped entirely
+        let mut depth = 0_usize;
+
+        while self.is_anything_unparsed() {
+            match self.current_token() {
+                Token::OpenCurlyBrace => depth += 1,
+
+                Token::CloseCurlyBrace => {
+                    // Belongs to the enclosing block
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                }
+
+                Token::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+
+                token if depth == 0 && Self::is_top_level_start(token) => return,
+
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    /// Skips the remainder of a malformed top-level item
+    fn synchronize_top_level(&self, error_position: usize) {
+        // Ensure progress when the item's first token caused the error
+        if *self.position.borrow() == error_position && self.is_anything_unparsed() {
+            self.advance();
+        }
+
+        while self.is_anything_unparsed() && !Self::is_top_level_start(self.current_token()) {
+            self.advance();
+        }
+    }
+
+    /// Creates a placeholder for a statement which could not be parsed
+    fn poisoned_statement(&self, span: Span) -> Node<ast::Statement> {
+        let placeholder = ast::Expression::Literal {
+            value: Literal::UnitType,
+            ty: Type::Unit,
+        };
+
+        let statement = ast::Statement::Expression(Node::new(placeholder, span).poison());
+        Node::new(statement, span).poison()
+    }
+
     // NOTE: This is used to determine certain item spans *after* parsing,
     //       hence the looking back. This removes the need to save temp spans without
     //       knowing whether or not they will be needed
@@ -97,22 +214,42 @@ impl<'a> Parser<'a> {
         &self.tokens[*self.position.borrow() + n].token
     }
 
+    /// Passes over the expected token (or errors if it is something else)
+    fn expect_token(&self, expected: Token) -> ParseResult<()> {
+        if &expected == self.current_token() {
+            self.advance();
+            Ok(())
+        } else {
+            parser_error!(self, self.current_span(), "Expected `{}`. Found `{}`", expected, self.current_token());
+        }
+    }
+
     fn advance(&self) {
         *self.position.borrow_mut() += 1;
     }
 
     // Returns true if there are any unparsed tokens
     fn is_anything_unparsed(&self) -> bool {
-        *self.position.borrow() < self.tokens.len()
+        *self.current_token() != Token::EndOfFile
     }
 
     ///////////// Parse Functions /////////////
 
+    /// Parses every top-level item. Malformed items are skipped, and their errors
+    /// are available through `Parser::errors`.
     pub fn parse_ast(&self, module: String) -> ast::AST {
         let mut ast = ast::AST::new(module);
 
         while self.is_anything_unparsed() {
-            let (item, meta) = self.parse_top_level();
+            let item_position = *self.position.borrow();
+
+            let (item, meta) = match self.parse_top_level() {
+                Ok(parsed) => parsed,
+                Err(ParseError) => {
+                    self.synchronize_top_level(item_position);
+                    continue;
+                }
+            };
 
             if let Some(meta_usage) = meta {
                 // println!("Meta: {:?}\non: {:?}", meta_usage, item);
@@ -139,9 +276,20 @@ impl<'a> Parser<'a> {
                 
                 let transformed = extension.transform_top_level(item, meta_usage.inputs);
 
+                // Anything the extension created is located at the meta tag
+                let expansion = self.source_map.borrow_mut().add_expansion(Expansion {
+                    kind: ExpansionKind::Extension {
+                        directive: meta_usage.directive.to_owned(),
+                    },
+                    call_site: meta_usage.span,
+                });
+                let call_site = meta_usage.span.expanded(expansion);
+
                 // TODO: Insert results into AST
                 for item in transformed.unwrap() {
-                    ast.insert_top_level(item.into());
+                    let mut item = item.into();
+                    crate::extension::locate_generated_nodes(&mut item, call_site);
+                    ast.insert_top_level(item);
                 }
             } else {
                 ast.insert_top_level(item);
@@ -152,48 +300,63 @@ impl<'a> Parser<'a> {
     }
 
     // TopLevel items are all nodes by themselves
-    pub fn parse_top_level(&self) -> (ast::TopLevel, Option<MetaTag>) {
-        let fail_if_public = |public: bool| {
-            if public {parser_error!(self.file_path, self.current_span(), "Unexpected `pub` keyword");}
+    pub fn parse_top_level(&self) -> ParseResult<(ast::TopLevel, Option<MetaTag<'_>>)> {
+        let fail_if_public = |public: bool| -> ParseResult<()> {
+            if public {parser_error!(self, self.current_span(), "Unexpected `pub` keyword");}
+            Ok(())
         };
 
+        // `@` prefixes are either builtin layout attributes (handled by the compiler),
+        // or a meta tag naming the extension which transforms the item. They can come in any order.
+        let mut attributes = Vec::new();
+        let mut meta: Option<MetaTag<'_>> = None;
+        while let Token::At = self.current_token() {
+            if self.is_layout_attribute() {
+                attributes.push(self.parse_layout_attribute()?);
+            } else {
+                let tag = self.parse_meta_tag()?;
+                if let Some(previous) = &meta {
+                    parser_error!(self, &tag.span, "Only one extension can be applied to an item. Found `@{}` after `@{}`", tag.directive, previous.directive);
+                }
+                meta = Some(tag);
+            }
+        }
+
+        if let (Some(attribute), false) = (attributes.first(), &Token::Keyword(Keyword::Struct) == self.current_token()) {
+            parser_error!(self, &attribute.span, "Layout attributes can only be applied to structs. Found `{}`", self.current_token());
+        }
+
         let is_public = if let Token::Keyword(Keyword::Pub) = self.current_token() {
             true    
         } else {false};
 
-        let meta = if let Token::At = self.current_token() {
-            Some(self.parse_meta_tag())
-        } else {
-            None
-        };
-
         let item = match self.current_token() {
             Token::Keyword(keyword) => {
                 match keyword {
                     Keyword::Extern => {
-                        fail_if_public(is_public);
+                        fail_if_public(is_public)?;
                         self.advance();
                         
                         ast::TopLevel::ExternBlock(
-                            self.parse_extern_block()
+                            self.parse_extern_block()?
                         )
                     }
                     
                     Keyword::Use => {
-                        fail_if_public(is_public);
+                        fail_if_public(is_public)?;
                         self.advance();
                         
                         ast::TopLevel::Use(
-                            self.parse_use()
+                            self.parse_use()?
                         )
                     }
                     
                     Keyword::Impl => {
-                        fail_if_public(is_public);
+                        fail_if_public(is_public)?;
                         self.advance();
 
                         ast::TopLevel::Impl(
-                            self.parse_impl()
+                            self.parse_impl()?
                         )
                     }
                     
@@ -201,19 +364,19 @@ impl<'a> Parser<'a> {
                         self.advance();
 
                         ast::TopLevel::Function(
-                            self.parse_function_definition(is_public)
+                            self.parse_function_definition(is_public)?
                         )
                     }
 
                     Keyword::Binary | Keyword::Unary => {
-                        self.parse_operator_definition(is_public)
+                        self.parse_operator_definition(is_public)?
                     }
 
                     Keyword::Trait => {
                         self.advance();
 
                         ast::TopLevel::Trait(
-                            self.parse_trait_definition(is_public)
+                            self.parse_trait_definition(is_public)?
                         )
                     }
 
@@ -221,27 +384,27 @@ impl<'a> Parser<'a> {
                         self.advance();
 
                         ast::TopLevel::Struct(
-                            self.parse_struct_definition(is_public)
+                            self.parse_struct_definition(is_public, attributes)?
                         )
                     }
 
                     _ => {
-                        parser_error!(self.file_path, self.current_span(), "Expected one of TODO:. Found unexpected keyword `{}`", self.current_token());
+                        parser_error!(self, self.current_span(), "Expected one of TODO:. Found unexpected keyword `{}`", self.current_token());
                     }
                 }
             }
 
             // Not a valid TopLevel item
             _ => {
-                parser_error!(self.file_path, self.current_span(), "Expected a TODO:. Found unexpected token `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected a TODO:. Found unexpected token `{}`", self.current_token());
             }
         };
 
-        (item, meta)
+        Ok((item, meta))
     }
 
     // `@directive(inputs, ..)`
-    pub fn parse_meta_tag(&self) -> MetaTag {
+    pub fn parse_meta_tag(&self) -> ParseResult<MetaTag<'_>> {
         let start = self.current_span();
         self.advance(); // pass the `@`
 
@@ -266,25 +429,26 @@ impl<'a> Parser<'a> {
                         self.advance();
                         break;
                     } else {
-                        parser_error!(self.file_path, self.current_span(), "Expected `)`. Found `{}`", self.current_token());
+                        parser_error!(self, self.current_span(), "Expected `)`. Found `{}`", self.current_token());
                     }
                 }
 
-                MetaTag {
+                Ok(MetaTag {
                     directive,
                     inputs,
-                }
+                    span: start.extend(*self.previous_span()),
+                })
             } else {
-                parser_error!(self.file_path, self.current_span(), "Expected `(`. Found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected `(`. Found `{}`", self.current_token());
             }
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected identifier to create meta tag. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected identifier to create meta tag. Found `{}`", self.current_token());
         }
     }
 
     // TODO: Handle multiple imports: `a::b::{b1, b2, b3};`
     //       and everything imports:  `a::b::*;`
-    pub fn parse_use(&self) -> Node<ast::Use> {
+    pub fn parse_use(&self) -> ParseResult<Node<ast::Use>> {
         let start = self.previous_span();
         let mut path = Vec::new();
 
@@ -302,14 +466,14 @@ impl<'a> Parser<'a> {
                     self.advance();
                     continue;
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected `::` in module path. Found `:{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected `::` in module path. Found `:{}`", self.current_token());
                 }
             // ..a;
             } else if let Token::Semicolon = self.current_token() {
                 self.advance();
                 break;
             } else {
-                parser_error!(self.file_path, self.current_span(), "Expected `:` or `;` in module path. Found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected `:` or `;` in module path. Found `{}`", self.current_token());
             }
         }
 
@@ -317,10 +481,10 @@ impl<'a> Parser<'a> {
             path,
         };
 
-        Node::new(use_, start.extend(*self.previous_span()))
+        Ok(Node::new(use_, start.extend(*self.previous_span())))
     }
 
-    pub fn parse_extern_block(&self) -> Node<ast::ExternBlock> {
+    pub fn parse_extern_block(&self) -> ParseResult<Node<ast::ExternBlock>> {
         // span of `extern` keyword
         let start = self.previous_span();
         let mut externs = Vec::new();
@@ -331,33 +495,35 @@ impl<'a> Parser<'a> {
                 if let Token::Keyword(Keyword::Fn) = self.current_token() { 
                     self.advance();
 
-                    externs.push(self.parse_function_prototype());
+                    externs.push(self.parse_function_prototype()?);
 
                     if let Token::Semicolon = self.current_token() {
                         self.advance();
                     } else {
-                        parser_error!(self.file_path, self.current_span(), "Expected `;` following extern function prototype. Found `{}`", self.current_token());
+                        parser_error!(self, self.current_span(), "Expected `;` following extern function prototype. Found `{}`", self.current_token());
                     }
                 }
                 
                 if let Token::CloseCurlyBrace = self.current_token() {
                     self.advance();
                     break;
+                } else if Token::Keyword(Keyword::Fn) != *self.current_token() {
+                    parser_error!(self, self.current_span(), "Expected `fn` or `}}` in extern block. Found `{}`", self.current_token());
                 }
             }
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected `{{` to begin extern block. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected `{{` to begin extern block. Found `{}`", self.current_token());
         }
         
-        Node::new(externs, start.extend(*self.previous_span()))
+        Ok(Node::new(externs, start.extend(*self.previous_span())))
     }
 
     // TODO: Use this whenever possible for parsing types
     //       Need to look through to see where applicable
     /// Recursively evaluates types
-    pub fn parse_type(&self) -> Type { //Result<Type, String> {
+    pub fn parse_type(&self) -> ParseResult<Type> {
         // TODO: `dyn`, `impl`, etc.
-        match self.current_token() {
+        let ty = match self.current_token() {
             // `T`
             Token::Ident(ident) => {
                 self.advance();
@@ -368,9 +534,12 @@ impl<'a> Parser<'a> {
             Token::And => {
                 self.advance();
                 let mutable = &Token::Keyword(Keyword::Mut) == self.current_token();
+                if mutable {
+                    self.advance();
+                }
 
                 Type::Reference {
-                    ty: Box::new(self.parse_type()),
+                    ty: Box::new(self.parse_type()?),
                     mutable,
                 }
             }
@@ -391,10 +560,10 @@ impl<'a> Parser<'a> {
                     loop {
                         // Allows one comma after the final field
                         if let Token::Comma = self.current_token() {
-                            parser_error!(self.file_path, self.current_span(), "Only one trailing comma is allowed in tuples following the final parameter");
+                            parser_error!(self, self.current_span(), "Only one trailing comma is allowed in tuples following the final parameter");
                         }
 
-                        tuple_types.push(self.parse_type());
+                        tuple_types.push(self.parse_type()?);
 
                         if let Token::Comma = self.current_token() {
                             self.advance();
@@ -417,12 +586,14 @@ impl<'a> Parser<'a> {
 
             // Invalid type component
             x => {
-                parser_error!(self.file_path, self.current_span(), "Expected a type component. Found `{}`", x);
+                parser_error!(self, self.current_span(), "Expected a type component. Found `{}`", x);
             }
-        }
+        };
+
+        Ok(ty)
     }
 
-    pub fn parse_trait_definition(&self, is_public: bool) -> Node<ast::Trait> {
+    pub fn parse_trait_definition(&self, is_public: bool) -> ParseResult<Node<ast::Trait>> {
         // span of `trait` keyword
         let start = self.previous_span();
 
@@ -441,13 +612,13 @@ impl<'a> Parser<'a> {
                             let fn_start = self.current_span();
                             self.advance();
 
-                            let prototype = self.parse_function_prototype();
+                            let prototype = self.parse_function_prototype()?;
                             // No default implementation
                             if let Token::Semicolon = self.current_token() {
                                 self.advance();
                                 required_functions.push(prototype);
                             } else {
-                                let body = self.parse_expression_block();
+                                let body = self.parse_expression_block()?;
                                 let function = ast::Function {
                                     prototype,
                                     body,
@@ -463,7 +634,7 @@ impl<'a> Parser<'a> {
                         // Token::Keyword(Keyword::Type) => {}
 
                         _ => {
-                            parser_error!(self.file_path, self.current_span(), "Expected one of `fn`, `const`, `type`. Found `{}`", self.current_token());
+                            parser_error!(self, self.current_span(), "Expected one of `fn`, `const`, `type`. Found `{}`", self.current_token());
                         }
                     }
                 }
@@ -477,24 +648,24 @@ impl<'a> Parser<'a> {
                     is_public,
                 };
 
-                Node::new(trait_, start.extend(*self.previous_span()))
+                Ok(Node::new(trait_, start.extend(*self.previous_span())))
             } else {
-                parser_error!(self.file_path, self.current_span(), "Expected `{{`, found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected `{{`, found `{}`", self.current_token());
             }
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected trait identifier. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected trait identifier. Found `{}`", self.current_token());
         }
     }
 
     // impl Trait for Type {..}
     // or
     // impl Type {..}
-    pub fn parse_impl(&self) -> Node<ast::Impl> {
+    pub fn parse_impl(&self) -> ParseResult<Node<ast::Impl>> {
         // span of `impl` keyword
         let start = self.previous_span();
 
         // TODO: Constants, etc.
-        let parse_impl_items = || {
+        let parse_impl_items = || -> ParseResult<Vec<Node<ast::Function>>> {
             let mut functions = Vec::new();
             loop {
                 let is_public = if let Token::Keyword(Keyword::Pub) = self.current_token() {
@@ -504,12 +675,12 @@ impl<'a> Parser<'a> {
 
                 if let Token::Keyword(Keyword::Fn) = self.current_token() {
                     self.advance();
-                    functions.push(self.parse_function_definition(is_public));
+                    functions.push(self.parse_function_definition(is_public)?);
                 } else {
                     break;
                 }
             }
-            functions
+            Ok(functions)
         };
 
         // TODO: Create a `parse_impl_body` to simplify this
@@ -529,7 +700,7 @@ impl<'a> Parser<'a> {
                         if let Token::OpenCurlyBrace = self.current_token() {
                             self.advance();
 
-                            let functions = parse_impl_items();
+                            let functions = parse_impl_items()?;
 
                             if let Token::CloseCurlyBrace = self.current_token() {
                                 self.advance();
@@ -541,15 +712,15 @@ impl<'a> Parser<'a> {
                                     functions,
                                 };
 
-                                Node::new(impl_, start.extend(*self.previous_span()))
+                                Ok(Node::new(impl_, start.extend(*self.previous_span())))
                             } else {
-                                parser_error!(self.file_path, self.current_span(), "Expected `}}`. Found `{}`", self.current_token());
+                                parser_error!(self, self.current_span(), "Expected `}}`. Found `{}`", self.current_token());
                             }
                         } else {
-                            parser_error!(self.file_path, self.current_span(), "Expected `{{`. Found `{}`", self.current_token());
+                            parser_error!(self, self.current_span(), "Expected `{{`. Found `{}`", self.current_token());
                         }
                     } else {
-                        parser_error!(self.file_path, self.current_span(), "Expected identifier. Found `{}`", self.current_token());
+                        parser_error!(self, self.current_span(), "Expected identifier. Found `{}`", self.current_token());
                     }
                 }
 
@@ -558,7 +729,7 @@ impl<'a> Parser<'a> {
                 Token::OpenCurlyBrace => {
                     self.advance();
                    
-                    let functions = parse_impl_items();
+                    let functions = parse_impl_items()?;
 
                     if let Token::CloseCurlyBrace = self.current_token() {
                         self.advance();
@@ -570,24 +741,24 @@ impl<'a> Parser<'a> {
                             functions,
                         };
 
-                        Node::new(impl_, start.extend(*self.previous_span()))
+                        Ok(Node::new(impl_, start.extend(*self.previous_span())))
                     } else {
-                        parser_error!(self.file_path, self.current_span(), "Expected `}}`. Found `{}`", self.current_token());
+                        parser_error!(self, self.current_span(), "Expected `}}`. Found `{}`", self.current_token());
                     }
                 }
 
                 x => {
-                    parser_error!(self.file_path, self.current_span(), "Expected `for` or `{{`. Found `{}`", x);
+                    parser_error!(self, self.current_span(), "Expected `for` or `{{`. Found `{}`", x);
                 }
             }
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected identifier. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected identifier. Found `{}`", self.current_token());
         }
     }
 
     // TODO: Do I want tuple structs and/or unit structs?
     // struct ident {field1: type1, ..}
-    pub fn parse_struct_definition(&self, is_public: bool) -> Node<ast::Struct> {
+    pub fn parse_struct_definition(&self, is_public: bool, attributes: Vec<Node<ast::LayoutAttribute>>) -> ParseResult<Node<ast::Struct>> {
         // span of `struct` keyword
         let start = self.previous_span();
 
@@ -595,23 +766,24 @@ impl<'a> Parser<'a> {
             self.advance();
             if let Token::OpenCurlyBrace = self.current_token() {
                 self.advance();
-                let fields = self.parse_struct_fields();
+                let fields = self.parse_struct_fields()?;
                 let item = ast::Struct {
                     name: name.clone(),
                     fields,
                     is_public,
+                    attributes,
                 };
 
-                Node::new(item, start.extend(*self.previous_span()))
+                Ok(Node::new(item, start.extend(*self.previous_span())))
             } else {
-                parser_error!(self.file_path, self.current_span(), "Expected `{{` after struct name. Found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected `{{` after struct name. Found `{}`", self.current_token());
             }
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected identifier after keyword `struct`. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected identifier after keyword `struct`. Found `{}`", self.current_token());
         }
     }
 
-    pub fn parse_struct_fields(&self) -> Node<ast::StructFieldList> {
+    pub fn parse_struct_fields(&self) -> ParseResult<Node<ast::StructFieldList>> {
         let mut fields = Vec::new();
         // span of `{` token
         let start = self.previous_span();
@@ -621,9 +793,11 @@ impl<'a> Parser<'a> {
 
             // Allows one comma after the final field
             if let Token::Comma = self.current_token() {
-                parser_error!(self.file_path, self.current_span(), "Only one trailing comma is allowed after struct fields");
+                parser_error!(self, self.current_span(), "Only one trailing comma is allowed after struct fields");
             }
 
+            let attributes = self.parse_layout_attributes()?;
+
             let is_public = if let Token::Keyword(Keyword::Pub) = self.current_token() {
                 self.advance();
                 true
@@ -636,17 +810,18 @@ impl<'a> Parser<'a> {
 
                     let field = ast::StructField {
                         name: name.clone(),
-                        ty: self.parse_type(),
+                        ty: self.parse_type()?,
                         is_public,
+                        attributes,
                     };
 
                     fields.push(
                         Node::new(field, span.extend(*self.previous_span()))
                     );
 
-                    // parser_error!(self.file_path, self.current_span(), "Expected type parameter type after `:`. Found `{}", self.current_token());
+                    // parser_error!(self, self.current_span(), "Expected type parameter type after `:`. Found `{}", self.current_token());
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected `:` after struct field name. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected `:` after struct field name. Found `{}`", self.current_token());
                 }
             }
 
@@ -661,19 +836,112 @@ impl<'a> Parser<'a> {
         if let Token::CloseCurlyBrace = self.current_token() {
             self.advance();
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected `}}` to end struct declaration. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected `}}` to end struct declaration. Found `{}`", self.current_token());
+        }
+
+        Ok(Node::new(fields, start.extend(*self.previous_span())))
+    }
+
+    // `@packed`, `@align(N)`, `@assert_layout(key = N, ..)`
+    // Any other `@directive` is left for the extension it names
+    fn parse_layout_attributes(&self) -> ParseResult<Vec<Node<ast::LayoutAttribute>>> {
+        let mut attributes = Vec::new();
+
+        while self.is_layout_attribute() {
+            attributes.push(self.parse_layout_attribute()?);
         }
 
-        Node::new(fields, start.extend(*self.previous_span()))
+        Ok(attributes)
+    }
+
+    /// Whether the next tokens are `@packed`, `@align`, or `@assert_layout` (rather than a meta tag)
+    fn is_layout_attribute(&self) -> bool {
+        matches!(
+            (self.current_token(), self.look_ahead(1)),
+            (Token::At, Token::Ident(name)) if matches!(name.as_str(), "packed" | "align" | "assert_layout")
+        )
+    }
+
+    fn parse_layout_attribute(&self) -> ParseResult<Node<ast::LayoutAttribute>> {
+        let name = match self.look_ahead(1) {
+            Token::Ident(name) => name,
+            _ => unreachable!("checked by `is_layout_attribute`"),
+        };
+
+        let start = self.current_span();
+        self.advance(); // pass the `@`
+        self.advance(); // pass the name
+
+        let attribute = match name.as_str() {
+            "packed" => ast::LayoutAttribute::Packed,
+
+            "align" => {
+                self.expect_token(Token::OpenParen)?;
+                let alignment = self.parse_attribute_number()?;
+                self.expect_token(Token::CloseParen)?;
+
+                ast::LayoutAttribute::Align(alignment)
+            }
+
+            _ => {
+                let (mut size, mut align, mut offset) = (None, None, None);
+
+                self.expect_token(Token::OpenParen)?;
+                loop {
+                    let key = if let Token::Ident(key) = self.current_token() {
+                        key
+                    } else {
+                        parser_error!(self, self.current_span(), "Expected one of `size`, `align`, or `offset`. Found `{}`", self.current_token());
+                    };
+                    let key_span = *self.current_span();
+                    self.advance();
+
+                    self.expect_token(Token::Equals)?;
+                    let value = Some(self.parse_attribute_number()?);
+
+                    match key.as_str() {
+                        "size" => size = value,
+                        "align" => align = value,
+                        "offset" => offset = value,
+                        _ => parser_error!(self, &key_span, "Unknown layout property `{}`. Expected one of `size`, `align`, or `offset`", key),
+                    }
+
+                    // Allow one trailing comma
+                    if let Token::Comma = self.current_token() {
+                        self.advance();
+                    }
+                    if let Token::CloseParen = self.current_token() {
+                        self.advance();
+                        break;
+                    }
+                }
+
+                ast::LayoutAttribute::AssertLayout { size, align, offset }
+            }
+        };
+
+        Ok(Node::new(attribute, start.extend(*self.previous_span())))
+    }
+
+    fn parse_attribute_number(&self) -> ParseResult<usize> {
+        if let Token::Number(number) = self.current_token() {
+            self.advance();
+            Ok(*number)
+        } else {
+            parser_error!(self, self.current_span(), "Expected a number. Found `{}`", self.current_token());
+        }
     }
 
     // fn ident(param: type, ..) -> return_type { statements.. }
-    pub fn parse_function_definition(&self, is_public: bool) -> Node<ast::Function> {
+    pub fn parse_function_definition(&self, is_public: bool) -> ParseResult<Node<ast::Function>> {
         // span of `fn` keyword
         let start = self.previous_span();
 
-        let prototype = self.parse_function_prototype();
-        let body = self.parse_expression_block();
+        let prototype = self.parse_function_prototype()?;
+        let body = self.parse_expression_block()?;
+
+        // Errors within the body taint the whole function
+        let is_poisoned = body.is_poisoned();
 
         let function = ast::Function {
             prototype,
@@ -681,11 +949,12 @@ impl<'a> Parser<'a> {
             is_public,
         };
 
-        Node::new(function, start.extend(*self.previous_span()))
+        let node = Node::new(function, start.extend(*self.previous_span()));
+        Ok(if is_poisoned { node.poison() } else { node })
     }
 
     // fn ident(param: type, ..) -> return_type
-    pub fn parse_function_prototype(&self) -> Node<ast::FunctionPrototype> {
+    pub fn parse_function_prototype(&self) -> ParseResult<Node<ast::FunctionPrototype>> {
         // span of `fn` keyword
         let start = self.previous_span();
 
@@ -694,9 +963,9 @@ impl<'a> Parser<'a> {
 
             let parameters = if let Token::OpenParen = self.current_token() {
                 self.advance();
-                self.parse_function_parameters()
+                self.parse_function_parameters()?
             } else {
-                panic!("Expected `(` after function name. Found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected `(` after function name. Found `{}`", self.current_token());
             };
 
             // `()` type is same as Rust's
@@ -706,10 +975,10 @@ impl<'a> Parser<'a> {
                 if let Token::RightAngleBracket = self.current_token() {
                     self.advance();
 
-                    self.parse_type()
-                    // parser_error!(self.file_path, self.current_span(), "Expected a return type after `->`. Founds `{}`", self.current_token());
+                    self.parse_type()?
+                    // parser_error!(self, self.current_span(), "Expected a return type after `->`. Founds `{}`", self.current_token());
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected `->`. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected `->`. Found `{}`", self.current_token());
                 }
             } else {
                 // No return type -> unit type (void)
@@ -722,15 +991,15 @@ impl<'a> Parser<'a> {
                 return_type,
             };
 
-            Node::new(prototype, start.extend(*self.previous_span()))
+            Ok(Node::new(prototype, start.extend(*self.previous_span())))
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected identifier, found `{}` while parsing function definition", self.current_token());
+            parser_error!(self, self.current_span(), "Expected identifier, found `{}` while parsing function definition", self.current_token());
         }
     }
 
     // (ident: type, ident: type, ..)
     // `self` becomes `self: Unknown` which later becomes `self: T` for `T`
-    pub fn parse_function_parameters(&self) -> Node<ast::FunctionParameterList> {
+    pub fn parse_function_parameters(&self) -> ParseResult<Node<ast::FunctionParameterList>> {
         let mut parameters = Vec::new();
         // span of `(` token
         let start = self.previous_span();
@@ -742,7 +1011,7 @@ impl<'a> Parser<'a> {
 
             // Allows one comma after the final field
             if let Token::Comma = self.current_token() {
-                parser_error!(self.file_path, self.current_span(), "Only one trailing comma is allowed in function parameters following the final parameter");
+                parser_error!(self, self.current_span(), "Only one trailing comma is allowed in function parameters following the final parameter");
             }
 
             if let Token::Keyword(Keyword::Mut) = self.current_token() {
@@ -759,6 +1028,7 @@ impl<'a> Parser<'a> {
                     // FIXME: This will later be replaced with the proper `User` variant. 
                     //        Is there a better approach?
                     ty: Type::Unknown,
+                    binding: None,
                 };
                 parameters.push(Node::new(param, span.extend(*self.previous_span())));
             }
@@ -770,13 +1040,14 @@ impl<'a> Parser<'a> {
                     let param = ast::FunctionParameter {
                         mutable,
                         name: field_name.to_owned(),
-                        ty: self.parse_type(),
+                        ty: self.parse_type()?,
+                        binding: None,
                     };
 
-                    // parser_error!(self.file_path, self.current_span(), "Expected type parameter type after `:`. Found `{}", self.current_token());
+                    // parser_error!(self, self.current_span(), "Expected type parameter type after `:`. Found `{}", self.current_token());
                     parameters.push(Node::new(param, span.extend(*self.previous_span())));
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected `:` after function parameter. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected `:` after function parameter. Found `{}`", self.current_token());
                 }
             }
 
@@ -791,15 +1062,15 @@ impl<'a> Parser<'a> {
         if let Token::CloseParen = self.current_token() {
             self.advance();
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected `)` to end function parameter list. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected `)` to end function parameter list. Found `{}`", self.current_token());
         }
 
-        Node::new(parameters, start.extend(*self.previous_span()))
+        Ok(Node::new(parameters, start.extend(*self.previous_span())))
     }
 
     // FIXME: Hacky
     // (binary | unary) symbol+ { function }
-    pub fn parse_operator_definition(&self, is_public: bool) -> ast::TopLevel {
+    pub fn parse_operator_definition(&self, is_public: bool) -> ParseResult<ast::TopLevel> {
         let start = self.current_span();
 
         let is_binary = if let Token::Keyword(Keyword::Binary) = self.current_token() {
@@ -814,6 +1085,8 @@ impl<'a> Parser<'a> {
             if let Token::OpenCurlyBrace = self.current_token() {
                 self.advance();
                 break;
+            } else if let Token::EndOfFile = self.current_token() {
+                parser_error!(self, self.current_span(), "Expected `{{` to begin operator definition. Found `{}`", self.current_token());
             } else {
                 pattern.push((*self.current_token()).clone());
                 self.advance();
@@ -823,13 +1096,13 @@ impl<'a> Parser<'a> {
         if let Token::Keyword(Keyword::Fn) = self.current_token() {
             self.advance();
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected function defintion in operator definition. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected function defintion in operator definition. Found `{}`", self.current_token());
         }
-        let function = self.parse_function_definition(true);
+        let function = self.parse_function_definition(true)?;
         
         
         if *self.current_token() != Token::CloseCurlyBrace {
-            parser_error!(self.file_path, self.current_span(), "Expected `{{` to end operator definition. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected `{{` to end operator definition. Found `{}`", self.current_token());
         }
         self.advance();
 
@@ -841,13 +1114,32 @@ impl<'a> Parser<'a> {
         };
 
         let op_node = Node::new(operator, start.extend(*self.previous_span()));
-        ast::TopLevel::Operator(op_node, function)
+        Ok(ast::TopLevel::Operator(op_node, function))
+    }
+
+    /// Looks past a field access (`a.b.c`) to see whether an assignment follows  
+    /// Does not consume any tokens
+    fn is_assignment_statement(&self) -> ParseResult<bool> {
+        let start_position = *self.position.borrow();
+        let lhs = self.parse_expression_unary();
+
+        let is_assignment = match self.current_token() {
+            Token::Equals => true,
+            Token::Plus | Token::Minus | Token::Asterisk | Token::Slash => {
+                self.look_ahead(1) == &Token::Equals
+            }
+
+            _ => false,
+        };
+
+        *self.position.borrow_mut() = start_position;
+        lhs.map(|_| is_assignment)
     }
 
     // TODO: This function needs a major refactor
     /// Parses a statement terminated by ';'  
     /// Assumes implicit return for non-terminated expressions
-    pub fn parse_statement(&self) -> Node<ast::Statement> {
+    pub fn parse_statement(&self) -> ParseResult<Node<ast::Statement>> {
         let statement;
         // span of first statement element (`let` keyword, expression, etc.)
         let start = self.current_span();
@@ -877,8 +1169,8 @@ impl<'a> Parser<'a> {
                     // Has `:` -> Type must be specified
                     ty = if let Token::Colon = self.current_token() {
                         self.advance();
-                        self.parse_type()
-                        // parser_error!(self.file_path, self.current_span(), "Expected type after `:`. Found `{}`", self.current_token());
+                        self.parse_type()?
+                        // parser_error!(self, self.current_span(), "Expected type after `:`. Found `{}`", self.current_token());
                     } else {
                         Type::Unknown
                     };
@@ -886,12 +1178,12 @@ impl<'a> Parser<'a> {
                     // Has `=` -> Must have assignment expression
                     expression = if let Token::Equals = self.current_token() {
                         self.advance();
-                        Some(self.parse_expression())
+                        Some(self.parse_expression()?)
                     } else {
                         None
                     };
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected identifier after `let`. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected identifier after `let`. Found `{}`", self.current_token());
                 }
                 
                 statement = ast::Statement::Let {
@@ -899,6 +1191,7 @@ impl<'a> Parser<'a> {
                     mutable,
                     ty,
                     value: expression,
+                    binding: None,
                 };
             }
 
@@ -913,7 +1206,7 @@ impl<'a> Parser<'a> {
                     };
                     Node::new(unit_return, *self.previous_span())
                 } else {
-                    self.parse_expression()
+                    self.parse_expression()?
                 };
 
                 statement = ast::Statement::Return {
@@ -921,74 +1214,30 @@ impl<'a> Parser<'a> {
                 };
             }
 
-            // TODO: Support derefs here too (*a.b.c)
-            // FIXME: This needs to be refactored
-            Token::Ident(_) => {
-                // FIXME: This doesn't allow `x + 1` because `x` will be used alone here
-                let base = self.parse_expression_field_access();
-
-                // Check whether this is an assignment statement
-                let is_assignment = match self.current_token() {
-                    Token::Equals => true,
-                    Token::Plus | Token::Minus | Token::Asterisk | Token::Slash => {
-                        self.look_ahead(1) == &Token::Equals
-                    }
-
-                    _ => false,
-                };
-
-                match self.current_token() {
-                    // x [+=, -=, *=, /=] expression
-                    _ if is_assignment => {
-                        let op_token = self.current();
-
-                        // Special case (advance past the op in an op-assign)
-                        if !(Token::Equals == *self.current_token()) {
-                            self.advance();
-                        }
-                        if let Token::Equals = *self.current_token() {
-                            self.advance();
-                            let op = ast::AssignmentOp::from_token(op_token);
-
-                            statement = ast::Statement::Assign {
-                                // TODO: Replace `variable` with `expression`
-                                lhs: base,
-                                operator: Node::new(op, op_token.span.extend(*self.previous_span())),
-                                expression: self.parse_expression(),
-                            };
-                        } else {
-                            unreachable!() // confirmed this above for `is_assignment`
-                            // parser_error!(self.file_path, op_token.span, "Expected `{}=` to create an op-assign statement. Found `{}`", op_token.token, op_token.token);
-                        }
-                    }
-
-                    // Found: `expression;`
-                    Token::Semicolon => {
-                        statement = ast::Statement::Expression(base);
-                    }
-
-                    // NOTE: Current token is not a semicolon
-                    // Return the base expression
-                    _ => {
-                        statement = if let Token::Semicolon = self.current_token() {
-                            ast::Statement::Expression(base)
-                        } else {
-                            needs_semicolon = false;
-
-                            // TODO: Ensure this is correct
-                            ast::Statement::ImplicitReturn {
-                                expression: base, //Node::new(ast::Expression::Ident(ident), start.extend(*self.previous_span())),
-                                is_function_return: false,
-                            }
-                        };
+            // x.y [+=, -=, *=, /=] expression
+            // *x.y [+=, -=, *=, /=] expression
+            Token::Ident(_) | Token::Asterisk if self.is_assignment_statement()? => {
+                let lhs = self.parse_expression_unary()?;
 
-                    }
+                let op_token = self.current();
+                // Special case (advance past the op in an op-assign)
+                if Token::Equals != *self.current_token() {
+                    self.advance();
                 }
+                // Confirmed to be `=` by `is_assignment_statement`
+                self.advance();
+                let op = ast::AssignmentOp::from_token(op_token);
+
+                statement = ast::Statement::Assign {
+                    lhs,
+                    operator: Node::new(op, op_token.span.extend(*self.previous_span())),
+                    expression: self.parse_expression()?,
+                };
             }
 
             // Must be an expression
             _ => {
-                let expression = self.parse_expression();
+                let expression = self.parse_expression()?;
                 statement = if let Token::Semicolon = self.current_token() {   
                     // Terminated by semicolon
                     ast::Statement::Expression(expression)
@@ -1007,16 +1256,29 @@ impl<'a> Parser<'a> {
         if let Token::Semicolon = self.current_token() {
             self.advance();
         } else if needs_semicolon {
-            parser_error!(self.file_path, self.current_span(), "Expected `;` to terminate a statement. Found `{}`", self.current_token());
+            let error = self.error(self.current_span(), format!("Expected `;` to terminate a statement. Found `{}`", self.current_token()));
+
+            // Offer to insert the `;` directly after the statement
+            if let Some(last) = self.errors.borrow_mut().last_mut() {
+                last.suggestions.push(Suggestion {
+                    span: self.previous_span().end_point(),
+                    replacement: ";".to_owned(),
+                    message: "add `;` here".to_owned(),
+                });
+            }
+
+            return Err(error);
         }
 
-        Node::new(statement, start.extend(*self.previous_span()))
+        Ok(Node::new(statement, start.extend(*self.previous_span())))
     }
 
     // NOTE: Special case (not technically an expression)
-    fn parse_expression_block(&self) -> Node<ast::BlockExpression> {
+    fn parse_expression_block(&self) -> ParseResult<Node<ast::BlockExpression>> {
         // Starting `{`
         let start = self.current_span();
+        // Set if any statement fails to parse
+        let mut is_poisoned = false;
 
         let body = if let Token::OpenCurlyBrace = self.current_token() {
             self.advance();
@@ -1024,18 +1286,37 @@ impl<'a> Parser<'a> {
             let mut body = Vec::new();
     
             loop {
-                if let Token::CloseCurlyBrace = self.current_token() {
-                    self.advance();
-                    break;
+                match self.current_token() {
+                    Token::CloseCurlyBrace => {
+                        self.advance();
+                        break;
+                    }
+
+                    // The block was never closed. End it here so the next item can be parsed.
+                    token if *token == Token::EndOfFile || Self::is_top_level_start(token) => {
+                        self.error(self.current_span(), format!("Expected `}}` to end block. Found `{}`", token));
+                        is_poisoned = true;
+                        break;
+                    }
+
+                    _ => {}
                 }
     
-                // This will not allow an infinite loop
-                body.push(self.parse_statement());
+                // Synchronizing always moves past the failed statement, so this will not loop infinitely
+                let statement_start = *self.current_span();
+                match self.parse_statement() {
+                    Ok(statement) => body.push(statement),
+                    Err(ParseError) => {
+                        self.synchronize_statement();
+                        body.push(self.poisoned_statement(statement_start.extend(*self.previous_span())));
+                        is_poisoned = true;
+                    }
+                }
             }
 
             Node::new(body, start.extend(*self.previous_span()))
         } else {
-            parser_error!(self.file_path, self.current_span(), "Expected `{{` to form a statement block implementing a function. Found `{}`", self.current_token());
+            parser_error!(self, self.current_span(), "Expected `{{` to form a statement block implementing a function. Found `{}`", self.current_token());
         };
 
         let block_expression = ast::BlockExpression {
@@ -1043,7 +1324,8 @@ impl<'a> Parser<'a> {
             ty: Type::Unknown,
         };
 
-        Node::new(block_expression, start.extend(*self.previous_span()))
+        let node = Node::new(block_expression, start.extend(*self.previous_span()));
+        Ok(if is_poisoned { node.poison() } else { node })
     }
 
     ////////////////// ONLY EXPRESSIONS BELOW HERE ///////////////////
@@ -1051,12 +1333,12 @@ impl<'a> Parser<'a> {
     ////////// Helper functions go after base_expression    //////////
 
     // Employs recursive descent
-    fn parse_expression(&self) -> Node<ast::Expression> {
+    fn parse_expression(&self) -> ParseResult<Node<ast::Expression>> {
         self.parse_expression_custom()
     }
 
     // Precedence for custom operators
-    fn parse_expression_custom(&self) -> Node<ast::Expression> {
+    fn parse_expression_custom(&self) -> ParseResult<Node<ast::Expression>> {
         let start = self.current_span();
 
         //// Unary ////
@@ -1069,17 +1351,17 @@ impl<'a> Parser<'a> {
                 
                 let expr = ast::Expression::UnaryExpression {
                     op,
-                    expr: Box::new(self.parse_expression_additive()),
+                    expr: Box::new(self.parse_expression_additive()?),
                     ty: Type::Unknown,
                 };
-                return Node::new(expr, start.extend(*self.previous_span()));
+                return Ok(Node::new(expr, start.extend(*self.previous_span())));
             }
 
             _ => {}
         }
 
         //// Binary ////
-        let mut expression = self.parse_expression_additive();
+        let mut expression = self.parse_expression_additive()?;
 
         loop {
             let mut pattern = Vec::new();
@@ -1097,7 +1379,7 @@ impl<'a> Parser<'a> {
                 },
             }
 
-            let rhs = self.parse_expression_additive();
+            let rhs = self.parse_expression_additive()?;
             let expr = ast::Expression::BinaryExpression {
                 lhs: Box::new(expression),
                 op: Node::new(ast::BinaryOp::Custom(pattern), *op_span),
@@ -1107,13 +1389,13 @@ impl<'a> Parser<'a> {
             expression = Node::new(expr, start.extend(*self.previous_span()));
         }
 
-        expression
+        Ok(expression)
     }
 
     // Precedence for [+, -]
-    fn parse_expression_additive(&self) -> Node<ast::Expression> {
+    fn parse_expression_additive(&self) -> ParseResult<Node<ast::Expression>> {
         let start = self.current_span();
-        let mut expression = self.parse_expression_multiplicative();
+        let mut expression = self.parse_expression_multiplicative()?;
 
         // loop => associative
         // Note that the expression is built up with each iteration
@@ -1125,7 +1407,7 @@ impl<'a> Parser<'a> {
                     self.advance();
 
 
-                    let rhs = self.parse_expression_multiplicative();
+                    let rhs = self.parse_expression_multiplicative()?;
                     let expr = ast::Expression::BinaryExpression {
                         lhs: Box::new(expression),
                         op: Node::new(op, op_token.span),
@@ -1139,13 +1421,13 @@ impl<'a> Parser<'a> {
             }
         }
 
-        expression
+        Ok(expression)
     }
 
     // Precedence for [*, /]
-    fn parse_expression_multiplicative(&self) -> Node<ast::Expression> {
+    fn parse_expression_multiplicative(&self) -> ParseResult<Node<ast::Expression>> {
         let start = self.current_span();
-        let mut expression = self.parse_expression_unary();
+        let mut expression = self.parse_expression_unary()?;
 
         loop {
             match self.current_token() {
@@ -1154,7 +1436,7 @@ impl<'a> Parser<'a> {
                     let op = ast::BinaryOp::from_token(op_token);
                     self.advance();
 
-                    let rhs = self.parse_expression_unary();
+                    let rhs = self.parse_expression_unary()?;
                     let expr = ast::Expression::BinaryExpression {
                         lhs: Box::new(expression),
                         op: Node::new(op, op_token.span),
@@ -1168,11 +1450,11 @@ impl<'a> Parser<'a> {
             }
         }
 
-        expression
+        Ok(expression)
     }
 
-    // Precedence for [negation, not]
-    fn parse_expression_unary(&self) -> Node<ast::Expression> {
+    // Precedence for [negation, not, reference, dereference]
+    fn parse_expression_unary(&self) -> ParseResult<Node<ast::Expression>> {
         let start = self.current_span();
         let expression;
 
@@ -1182,11 +1464,11 @@ impl<'a> Parser<'a> {
                 
                 // TODO: If next token is number, return negative literal
                 if let Token::Number(number) = self.current_token() {
-                    expression = self.parse_numeric_literal(number, true);
+                    expression = self.parse_numeric_literal(number, true)?;
                 } else {   
                     expression = ast::Expression::UnaryExpression {
                         op: Node::new(ast::UnaryOp::Negate, *self.previous_span()),
-                        expr: Box::new(self.parse_expression()),
+                        expr: Box::new(self.parse_expression()?),
                         ty: Type::Unknown,
                     };
                 }
@@ -1196,7 +1478,32 @@ impl<'a> Parser<'a> {
                 self.advance();
                 expression = ast::Expression::UnaryExpression {
                     op: Node::new(ast::UnaryOp::Not, *self.previous_span()),
-                    expr: Box::new(self.parse_expression()),
+                    expr: Box::new(self.parse_expression()?),
+                    ty: Type::Unknown,
+                };
+            }
+
+            // `&x` or `&mut x`
+            Token::And => {
+                self.advance();
+                let mutable = &Token::Keyword(Keyword::Mut) == self.current_token();
+                if mutable {
+                    self.advance();
+                }
+
+                expression = ast::Expression::UnaryExpression {
+                    op: Node::new(ast::UnaryOp::Reference { mutable }, start.extend(*self.previous_span())),
+                    expr: Box::new(self.parse_expression_unary()?),
+                    ty: Type::Unknown,
+                };
+            }
+
+            // `*x`
+            Token::Asterisk => {
+                self.advance();
+                expression = ast::Expression::UnaryExpression {
+                    op: Node::new(ast::UnaryOp::Dereference, *self.previous_span()),
+                    expr: Box::new(self.parse_expression_unary()?),
                     ty: Type::Unknown,
                 };
             }
@@ -1206,15 +1513,15 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Node::new(expression, start.extend(*self.previous_span()))
+        Ok(Node::new(expression, start.extend(*self.previous_span())))
     }
 
     // `a.b.c` ..etc.
     // Should always have 3rd highest precedence (below method call and base expressions)
-    fn parse_expression_field_access(&self) -> Node<ast::Expression> {
+    fn parse_expression_field_access(&self) -> ParseResult<Node<ast::Expression>> {
         let start = self.current_span();
-        // let base = self.parse_expression_method_call();
-        let mut base = self.parse_expression_base();
+        // let base = self.parse_expression_method_call()?;
+        let mut base = self.parse_expression_base()?;
         
         // FIXME: There is a nice recursive solution here that I can't figure out
         //        (although nothing is wrong with this implementation)
@@ -1234,24 +1541,24 @@ impl<'a> Parser<'a> {
                     };
                     base = Node::new(access, start.extend(*self.current_span()));
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected ident to create field access. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected ident to create field access. Found `{}`", self.current_token());
                 }
             } else {
                 break;
             }
         }
 
-        base
+        Ok(base)
     }
 
     // `a.b()`
     // Should always have 2nd highest precedence (below base expression)
-    fn parse_expression_method_call(&self) -> Node<ast::Expression> {
+    fn parse_expression_method_call(&self) -> ParseResult<Node<ast::Expression>> {
         todo!()
     }
 
     // Precedence for [parentheticals, literals, identifiers]
-    fn parse_expression_base(&self) -> Node<ast::Expression> {
+    fn parse_expression_base(&self) -> ParseResult<Node<ast::Expression>> {
         let expression;
         // This is a terminal item, so span contains the element about to be parsed
         let start = self.current_span();
@@ -1270,22 +1577,33 @@ impl<'a> Parser<'a> {
                         ty: Type::Unit,
                     };
 
-                    return Node::new(expr, start.extend(*self.previous_span()));
+                    return Ok(Node::new(expr, start.extend(*self.previous_span())));
                 }
 
 
-                let inner = self.parse_expression();
+                let inner = self.parse_expression()?;
                 if let Token::CloseParen = self.current_token() {
                     self.advance();
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected ')' to end parenthesized expression. Found `{}`", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected ')' to end parenthesized expression. Found `{}`", self.current_token());
                 }
-                return inner;
+                return Ok(inner);
             }
 
             // Numeric literal
             Token::Number(number) => {
-                expression = self.parse_numeric_literal(number, false);
+                expression = self.parse_numeric_literal(number, false)?;
+            }
+
+            // Block expression -> `{ statements.. }`
+            Token::OpenCurlyBrace => {
+                let block = self.parse_expression_block()?;
+                // Its statements were already reported
+                if block.is_poisoned() {
+                    return Err(ParseError);
+                }
+
+                expression = ast::Expression::Block(block.item);
             }
 
             // Identifier, Constructor, or FunctionCall
@@ -1295,12 +1613,12 @@ impl<'a> Parser<'a> {
                 match self.current_token() {
                     // FieldConstructor
                     Token::OpenCurlyBrace => {
-                        expression = self.parse_field_constructor(ident);
+                        expression = self.parse_field_constructor(ident)?;
                     }
 
                     // FunctionCall
                     Token::OpenParen => {
-                        let inputs = self.parse_function_call_inputs();
+                        let inputs = self.parse_function_call_inputs()?;
                         expression = ast::Expression::FunctionCall {
                             name: ident.to_string(),
                             inputs,
@@ -1313,22 +1631,23 @@ impl<'a> Parser<'a> {
                         expression = ast::Expression::Ident {
                             name: ident.clone(),
                             ty: Type::Unknown,
+                            binding: None,
                         };
                     }
                 }
             }
 
             _ => {
-                parser_error!(self.file_path, self.current_span(), "Expected a base expression. Found `{}`", self.current_token());
+                parser_error!(self, self.current_span(), "Expected a base expression. Found `{}`", self.current_token());
             }
         }
 
-        Node::new(expression, start.extend(*self.previous_span()))
+        Ok(Node::new(expression, start.extend(*self.previous_span())))
     }
 
     //////////////////// EXPRESSION HELPER FUNCTIONS ////////////////////
 
-    fn parse_function_call_inputs(&self) -> Vec<Node<ast::Expression>> {
+    fn parse_function_call_inputs(&self) -> ParseResult<Vec<Node<ast::Expression>>> {
         // Eat opening `(`
         self.advance();
 
@@ -1337,7 +1656,7 @@ impl<'a> Parser<'a> {
         loop {
             // Allow trailing comma
             if let Token::Comma = self.current_token() {
-                parser_error!(self.file_path, self.current_span(), "Only one trailing comma is allowed in function call inputs following the final parameter");
+                parser_error!(self, self.current_span(), "Only one trailing comma is allowed in function call inputs following the final parameter");
             }
 
             if let Token::CloseParen = self.current_token() {
@@ -1345,17 +1664,17 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            inputs.push(self.parse_expression());
+            inputs.push(self.parse_expression()?);
 
             if let Token::Comma = self.current_token() {
                 self.advance();
             }
         }
 
-        inputs
+        Ok(inputs)
     }
 
-    fn parse_numeric_literal(&self, number: &usize, negative: bool) -> ast::Expression {
+    fn parse_numeric_literal(&self, number: &usize, negative: bool) -> ParseResult<ast::Expression> {
         self.advance();
 
         // `#.` -> must be floating point number
@@ -1368,7 +1687,7 @@ impl<'a> Parser<'a> {
             } else {
                 // Don't allow `#.type` to avoid struct-field confusion
                 if let Token::Ident(_) = self.current_token() {
-                    parser_error!(self.file_path, self.current_span(), "Floating point numbers with trailing decimal points cannot have type specifiers (use `1.0f32` or `1f32` instead of `1.f32`)");
+                    parser_error!(self, self.current_span(), "Floating point numbers with trailing decimal points cannot have type specifiers (use `1.0f32` or `1f32` instead of `1.f32`)");
                 }
 
                 // `#.` -> `#.0`
@@ -1384,18 +1703,19 @@ impl<'a> Parser<'a> {
                 self.advance();
                 let float_type = Type::resolve_builtin(type_specifier.clone());
                 if !float_type.is_float() {
-                    parser_error!(self.file_path, self.previous_span(), "`{}` is not a valid floating-point type specifier", type_specifier);
+                    parser_error!(self, self.previous_span(), "`{}` is not a valid floating-point type specifier", type_specifier);
                 }
 
                 float_type
             } else {
-                Type::Unknown
+                // Inferred during validation
+                Type::FloatVariable
             };
 
-            ast::Expression::Literal {
+            Ok(ast::Expression::Literal {
                 value: Literal::Float(float),
                 ty,
-            }
+            })
         }
         // No decimal -> could be any builtin
         else {
@@ -1403,21 +1723,22 @@ impl<'a> Parser<'a> {
                 self.advance();
                 let specified = Type::resolve_builtin(type_specifier.clone());
                 if !specified.is_numeric() {
-                    parser_error!(self.file_path, self.previous_span(), "`{}` is not a valid type specifier", type_specifier);
+                    parser_error!(self, self.previous_span(), "`{}` is not a valid type specifier", type_specifier);
                 }
                 
                 // Only signed integers and floats can be negative
                 if negative && !(specified.is_signed_integer() || specified.is_float()) {
-                    parser_error!(self.file_path, self.previous_span(), "Only signed types can be negative (got unsigned type, `{}`)", type_specifier);
+                    parser_error!(self, self.previous_span(), "Only signed types can be negative (got unsigned type, `{}`)", type_specifier);
                 }
 
                 specified
             } else {
-                Type::Unknown
+                // Inferred during validation
+                Type::IntegerVariable
             };
 
-            let value = if ty.is_integer() { // parse integer
-                let mut integer: isize = number.to_string().parse().expect("parse integer");
+            let value = if ty.is_integer() || ty == Type::IntegerVariable { // parse integer
+                let mut integer = *number as i128;
 
                 // This is already confirmed valid
                 if negative {
@@ -1430,22 +1751,22 @@ impl<'a> Parser<'a> {
                 Literal::Float(float)
             };
 
-            ast::Expression::Literal {
+            Ok(ast::Expression::Literal {
                 value,
                 ty,
-            }
+            })
         }
     }
 
     // Helper function -- separated for readability/complexity
     // Note that FieldConstructor is an expression
-    fn parse_field_constructor(&self, ident: &'a str) -> ast::Expression {
+    fn parse_field_constructor(&self, ident: &'a str) -> ParseResult<ast::Expression> {
         self.advance();
         let mut fields = std::collections::HashMap::new();
 
         loop {
             if let Token::Comma = self.current_token() {
-                parser_error!(self.file_path, self.current_span(), "Only one trailing comma is allowed in field constructors following the final parameter");
+                parser_error!(self, self.current_span(), "Only one trailing comma is allowed in field constructors following the final parameter");
             }
             
             if let Token::Ident(field_name) = self.current_token() {
@@ -1454,7 +1775,7 @@ impl<'a> Parser<'a> {
                 // Get the assignment either by ident shorthand or by `field: value`
                 let field_assignment = if let Token::Colon = self.current_token() {
                     self.advance();
-                    self.parse_expression()
+                    self.parse_expression()?
                 }
                 // Shorthand by identifier
                 else if (self.current_token() == &Token::Comma) || (self.current_token() == &Token::CloseCurlyBrace) {
@@ -1462,20 +1783,20 @@ impl<'a> Parser<'a> {
                         ast::Expression::Ident { 
                             name: field_name.clone(),
                             ty: Type::Unknown,
+                            binding: None,
                         },
                         // Span of the ident token
                         *self.previous_span()
                     )    
                 } else {
-                    parser_error!(self.file_path, self.current_span(), "Expected `:` after field name. Found {}", self.current_token());
+                    parser_error!(self, self.current_span(), "Expected `:` after field name. Found {}", self.current_token());
                 };
 
-                fields.insert(field_name.clone(), field_assignment)
-                    .map(|_existing| {
-                        parser_error!(self.file_path, self.current_span(), "Field `{}` was already defined", self.current_token());
-                    });
+                if fields.insert(field_name.clone(), field_assignment).is_some() {
+                    parser_error!(self, self.previous_span(), "Field `{}` was already defined", field_name);
+                }
             } else {
-                parser_error!(self.file_path, self.current_span(), "Expected field name identifier. Found {}", self.current_token());
+                parser_error!(self, self.current_span(), "Expected field name identifier. Found {}", self.current_token());
             }
 
             if let Token::Comma = self.current_token() {
@@ -1487,9 +1808,9 @@ impl<'a> Parser<'a> {
             }
         }
 
-        ast::Expression::FieldConstructor {
+        Ok(ast::Expression::FieldConstructor {
             ty: Type::User(ident.to_owned()),
             fields,
-        }
+        })
     }
 }
\ No newline at end of file
@@ -2,7 +2,10 @@ use std::collections::{HashMap, HashSet};
 
 use crate::frontend::parse::ast;
 
-use super::types::Type;
+use super::flow::FlowAnalysis;
+use super::infer::InferenceTable;
+use super::target::TargetInfo;
+use super::types::{Type, TypeVariableKind};
 use super::*;
 
 ///////////////////// Main Validation Functionality /////////////////////
@@ -19,51 +22,62 @@ pub struct Context {
     pub types: TypeTable,
     /// Scoped variable information
     scopes: Scopes,
+    /// Type variables of the function being validated
+    inference: InferenceTable,
 
     /// The validated AST
     pub ast: ast::AST,
+    /// Non-fatal findings (such as unreachable code)
+    pub warnings: Vec<crate::diagnostic::Diagnostic>,
 
     /// Used to validate function bodies using `Statement::Return`s
     last_return_type: Type,
     /// Used to simplify table lookups
     current_function_name: String,
+    /// Declared return type of the current function (used to infer literals)
+    current_return_type: Type,
 }
 
 impl Context {
-    /// Creates an empty validation context
+    /// Creates an empty validation context for the host machine
     pub fn new() -> Self {
+        Self::with_target(TargetInfo::host())
+    }
+
+    /// Creates an empty validation context which lays types out for `target`
+    pub fn with_target(target: TargetInfo) -> Self {
         Self {
             allocations: AllocationTable::new(),
             operators: Vec::new(),
             functions: FunctionTable::new(),
             structs: HashMap::new(),
-            types: TypeTable::new(),
+            types: TypeTable::new(target),
             scopes: Scopes::new(),
+            inference: InferenceTable::new(),
             // Does not allocate any heap memory
             ast: ast::AST::placeholder(),
+            warnings: Vec::new(),
 
             last_return_type: Type::Unknown,
             current_function_name: String::new(),
+            current_return_type: Type::Unknown,
         }
     }
 
     /// Validates and takes ownership of an AST
-    pub fn validate(&mut self, mut ast: ast::AST) -> Result<(), String> {
+    pub fn validate(&mut self, mut ast: ast::AST) -> ValidationResult<()> {
         // Registration pass (gathers contextual information)    
         // NOTE: Order matters here
         // for use_ in &ast.uses {
             // TODO: Build symbol/alias table
             //       Must be done first (to avoid collisions and to reference used items)
         // }
-        for struct_ in &ast.structs {
-            // TODO: Want to declare types, then validate them.
-            //       That way, structs can reference other types declared later on
-            self.register_struct(&struct_)?;
-        }
+        // Structs can refer to one another regardless of declaration order
+        self.register_structs(&ast.structs)?;
         for extern_block in &ast.externs {
             for prototype in &extern_block.item {
                 self.validate_function_prototype(&prototype)?;
-                self.functions.forward_declare_function(&prototype, true)?;
+                self.functions.forward_declare_function(&prototype, true).at(prototype.span)?;
             }
         }
         for operator in &ast.operators {
@@ -71,7 +85,7 @@ impl Context {
         }
         for function in &ast.functions {
             self.validate_function_prototype(&function.prototype)?;
-            self.functions.forward_declare_function(&function.prototype, false)?;
+            self.functions.forward_declare_function(&function.prototype, false).at(function.prototype.span)?;
         }
         // for constant in &ast.constants {
             // TODO: Declare their typed idents in global scope
@@ -85,6 +99,7 @@ impl Context {
         
         for function in &mut ast.functions {
             self.current_function_name = function.prototype.name.clone();
+            self.current_return_type = function.prototype.return_type.clone();
             self.validate_function_body(function)?;
         }
 
@@ -93,8 +108,136 @@ impl Context {
         Ok(())
     }
 
-    /// Registers and lays out a "repr(C)" struct
-    pub fn register_struct(&mut self, struct_: &ast::Struct) -> Result<(), String> {
+    /// Declares every struct, then lays each one out after the structs it contains
+    fn register_structs(&mut self, structs: &[ast::Node<ast::Struct>]) -> ValidationResult<()> {
+        // 1. Declare every name, so that fields can use types declared later on
+        let mut declared = HashMap::new();
+        for struct_ in structs {
+            if declared.insert(struct_.name.as_str(), struct_).is_some() {
+                return Err(validation_error!(DUPLICATE_TYPE, "Struct `{}` is already defined", struct_.name).at(struct_.span));
+            }
+        }
+
+        // 2. Every field's type must exist
+        for struct_ in structs {
+            for field in &struct_.fields.item {
+                self.assert_declared(&field.ty, &declared).at(field.span)?;
+            }
+        }
+
+        // 3. Structs are laid out after everything they contain (by value)
+        for struct_ in Self::layout_order(structs, &declared)? {
+            self.register_struct(struct_)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensures a type is either already known or one of the structs being declared
+    fn assert_declared(&self, ty: &Type, declared: &HashMap<&str, &ast::Node<ast::Struct>>) -> ValidationResult<()> {
+        match ty {
+            Type::User(name) if declared.contains_key(name.as_str()) => Ok(()),
+            Type::Reference { ty, .. } => self.assert_declared(ty, declared),
+            Type::Tuple(types) => types.iter().try_for_each(|ty| self.assert_declared(ty, declared)),
+            _ => self.types.assert_valid(ty),
+        }
+    }
+
+    /// Orders structs so that each one comes after every struct it contains.  
+    /// References have a fixed size, so structs may refer to one another (or themselves) through them.
+    /// Structs which contain themselves by value would be infinitely large, and are rejected.
+    fn layout_order<'a>(structs: &'a [ast::Node<ast::Struct>], declared: &HashMap<&'a str, &'a ast::Node<ast::Struct>>) -> ValidationResult<Vec<&'a ast::Node<ast::Struct>>> {
+        /// Names of the structs which a type contains by value
+        fn contained_structs<'t>(ty: &'t Type, contained: &mut Vec<&'t str>) {
+            match ty {
+                Type::User(name) => contained.push(name),
+                Type::Tuple(types) => types.iter().for_each(|ty| contained_structs(ty, contained)),
+                _ => {}
+            }
+        }
+
+        struct Sorter<'a, 'd> {
+            declared: &'d HashMap<&'a str, &'a ast::Node<ast::Struct>>,
+            /// `false` while a struct's fields are being visited, `true` once it is ordered
+            visited: HashMap<&'a str, bool>,
+            /// Fields leading to the struct being visited
+            path: Vec<(&'a str, &'a ast::Node<ast::StructField>)>,
+            order: Vec<&'a ast::Node<ast::Struct>>,
+        }
+
+        impl<'a, 'd> Sorter<'a, 'd> {
+            fn visit(&mut self, struct_: &'a ast::Node<ast::Struct>) -> ValidationResult<()> {
+                match self.visited.get(struct_.name.as_str()) {
+                    Some(true) => return Ok(()),
+                    Some(false) => return Err(self.cycle_error(&struct_.name)),
+                    None => {}
+                }
+
+                self.visited.insert(&struct_.name, false);
+
+                for field in &struct_.fields.item {
+                    let mut contained = Vec::new();
+                    contained_structs(&field.ty, &mut contained);
+
+                    for name in contained {
+                        let declared = self.declared;
+                        if let Some(dependency) = declared.get(name) {
+                            self.path.push((&struct_.name, field));
+                            self.visit(dependency)?;
+                            self.path.pop();
+                        }
+                    }
+                }
+
+                self.visited.insert(&struct_.name, true);
+                self.order.push(struct_);
+
+                Ok(())
+            }
+
+            /// `name` was reached while its own fields were being visited
+            fn cycle_error(&self, name: &str) -> crate::diagnostic::Diagnostic {
+                let start = self.path.iter().position(|(owner, _)| *owner == name).unwrap();
+                let cycle = &self.path[start..];
+
+                let path = cycle.iter()
+                    .map(|(owner, _)| format!("`{}`", owner))
+                    .chain(std::iter::once(format!("`{}`", name)))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                let mut error = validation_error!(RECURSIVE_TYPE, "Struct `{}` contains itself and would have infinite size ({})", name, path)
+                    .with_label(cycle[0].1.span, format!("`{}` contains `{}` here", cycle[0].0, cycle[0].1.ty));
+
+                for (owner, field) in &cycle[1..] {
+                    error = error.with_secondary_label(field.span, format!("`{}` contains `{}` here", owner, field.ty));
+                }
+
+                error.with_note("store a reference (`&`) somewhere in the cycle to give it a finite size".to_owned())
+            }
+        }
+
+        let mut sorter = Sorter {
+            declared,
+            visited: HashMap::new(),
+            path: Vec::new(),
+            order: Vec::new(),
+        };
+
+        for struct_ in structs {
+            sorter.visit(struct_)?;
+        }
+
+        Ok(sorter.order)
+    }
+
+    /// Registers and lays out a "repr(C)" struct.  
+    /// Every type it contains must already be laid out.
+    pub fn register_struct(&mut self, struct_: &ast::Node<ast::Struct>) -> ValidationResult<()> {
+        if self.structs.contains_key(&struct_.name) {
+            return Err(validation_error!(DUPLICATE_TYPE, "Struct `{}` is already defined", struct_.name).at(struct_.span));
+        }
+
         let needed_padding = |offset: i32, alignment: i32| {
             let misalignment = offset % alignment;
             if misalignment > 0 {
@@ -103,21 +246,33 @@ impl Context {
                 0
             }
         };
+
+        let (packed, struct_alignment) = Self::layout_attributes(&struct_.attributes, false)?;
         
-        // Determine the struct's overall alignment
-        let alignment = struct_.fields.iter().fold(0, |alignment, x| {
-            std::cmp::max(alignment, self.types.alignment_of(&x.ty))
-        });
+        // The struct aligns to its most aligned field (or more, if requested)
+        let mut alignment = struct_alignment;
 
         let mut fields = HashMap::new();
         
         let mut offset = 0_i32;
         // Determine each field's aligned offset
         for field in &struct_.fields.item {
+            let (_, field_alignment) = Self::layout_attributes(&field.attributes, true)?;
+
+            // Packed fields are only aligned when explicitly requested
+            let field_alignment = if packed {
+                field_alignment
+            } else {
+                std::cmp::max(field_alignment, self.types.alignment_of(&field.ty))
+            };
+            alignment = std::cmp::max(alignment, field_alignment);
+
             // Account for any needed padding
-            let field_alignment = self.types.alignment_of(&field.ty);
             // FIXME: Narrowing cast
             offset += needed_padding(offset, field_alignment as i32);
+
+            let field_size = self.types.size_of(&field.ty);
+            Self::check_layout_assertions(&field.attributes, &format!("{}.{}", struct_.name, field.name), field_size, field_alignment, Some(offset as usize))?;
             
             // Place field at current offset
             fields.insert(field.name.clone(), StructField {
@@ -128,29 +283,126 @@ impl Context {
             
             // Account for the size of the field
             // FIXME: Narrowing cast
-            offset += self.types.size_of(&field.ty) as i32;
+            offset += field_size as i32;
         }
         
         self.structs.insert(
             struct_.name.clone(),
             StructDefinition {
                 fields,
+                field_order: struct_.fields.iter().map(|field| field.name.clone()).collect(),
             }
-        ).map(|_already_existing| {
-            return Err::<(), String>(format!("Struct `{}` is already defined", struct_.name));
-        });
+        );
 
         // Add final padding for the struct's alignment
         // FIXME: Narrowing cast
         let size = offset + needed_padding(offset, alignment as i32);
-        self.types.insert(&Type::User(struct_.name.to_owned()), TypeTableEntry::new(size as usize, alignment))?;
+        Self::check_layout_assertions(&struct_.attributes, &struct_.name, size as usize, alignment, None)?;
+        self.types.insert(&Type::User(struct_.name.to_owned()), TypeTableEntry::new(size as usize, alignment)).at(struct_.span)?;
+
+        Ok(())
+    }
+
+    /// Checks the layout attributes of a struct (or one of its fields).  
+    /// Returns whether it is packed, along with its minimum alignment.
+    fn layout_attributes(attributes: &[ast::Node<ast::LayoutAttribute>], is_field: bool) -> ValidationResult<(bool, usize)> {
+        let mut packed = None;
+        let mut alignment = None;
+
+        for attribute in attributes {
+            match &attribute.item {
+                ast::LayoutAttribute::Packed if is_field => {
+                    return Err(validation_error!(INVALID_ATTRIBUTE, "`@packed` can only be applied to structs (use `@align(1)` on the field instead)").at(attribute.span));
+                }
+                ast::LayoutAttribute::Packed => packed = Some(attribute.span),
+
+                ast::LayoutAttribute::Align(align) => {
+                    if !align.is_power_of_two() {
+                        return Err(validation_error!(INVALID_ATTRIBUTE, "Alignment must be a power of two (found `{}`)", align).at(attribute.span));
+                    }
+                    alignment = Some((*align, attribute.span));
+                }
+
+                ast::LayoutAttribute::AssertLayout { offset: Some(_), .. } if !is_field => {
+                    return Err(validation_error!(INVALID_ATTRIBUTE, "Only fields have offsets (`offset` cannot be asserted for structs)").at(attribute.span));
+                }
+                ast::LayoutAttribute::AssertLayout { .. } => {}
+            }
+        }
+
+        // Same as `#[repr(packed, align(N))]` in Rust
+        if let (Some(packed), Some((_, align))) = (packed, alignment) {
+            return Err(validation_error!(INVALID_ATTRIBUTE, "Structs cannot be both packed and aligned")
+                .with_label(packed, "packed here".to_owned())
+                .with_secondary_label(align, "aligned here".to_owned()));
+        }
+
+        Ok((packed.is_some(), alignment.map_or(1, |(align, _)| align)))
+    }
+
+    /// Ensures a struct or field's layout matches each `@assert_layout`
+    fn check_layout_assertions(attributes: &[ast::Node<ast::LayoutAttribute>], name: &str, size: usize, alignment: usize, offset: Option<usize>) -> ValidationResult<()> {
+        for attribute in attributes {
+            if let ast::LayoutAttribute::AssertLayout { size: expected_size, align: expected_align, offset: expected_offset } = &attribute.item {
+                let checks = [
+                    ("size", *expected_size, Some(size)),
+                    ("alignment", *expected_align, Some(alignment)),
+                    ("offset", *expected_offset, offset),
+                ];
+
+                for (property, expected, actual) in checks.iter() {
+                    if let (Some(expected), Some(actual)) = (expected, actual) {
+                        if expected != actual {
+                            return Err(validation_error!(LAYOUT_MISMATCH, "Layout of `{}` does not match its assertion: {} is {} bytes, but {} was expected", name, property, actual, expected)
+                                .with_label(attribute.span, format!("expected {} of {} bytes", property, expected)));
+                        }
+                    }
+                }
+            }
+        }
 
         Ok(())
     }
 
+    /// Describes how a struct is laid out in memory (for comparison with Rust types)
+    pub fn struct_layout(&self, name: &str) -> Option<crate::layout::StructLayout> {
+        let definition = self.structs.get(name)?;
+        let ty = Type::User(name.to_owned());
+
+        let fields = definition.field_order.iter()
+            .map(|field_name| {
+                let field = &definition.fields[field_name];
+                crate::layout::FieldLayout {
+                    name: field_name.clone(),
+                    offset: field.offset as usize,
+                    ty: field.ty.to_string(),
+                }
+            })
+            .collect();
+
+        Some(crate::layout::StructLayout {
+            name: name.to_owned(),
+            size: self.types.size_of(&ty),
+            alignment: self.types.alignment_of(&ty),
+            fields,
+        })
+    }
+
+    /// Returns the (byte offset, type) of each of a struct's fields in declaration order
+    pub fn struct_fields(&self, name: &str) -> Option<Vec<(usize, &Type)>> {
+        let definition = self.structs.get(name)?;
+
+        Some(definition.field_order.iter()
+            .map(|field_name| {
+                let field = &definition.fields[field_name];
+                (field.offset as usize, &field.ty)
+            })
+            .collect())
+    }
+
     /// Returns the type of a field from a struct, enum, or tuple.  
     /// For referenced types, the underlying type will be used.
-    pub fn get_field_type(&self, ty: &Type, field: &str) -> Result<Type, String> {
+    pub fn get_field_type(&self, ty: &Type, field: &str) -> ValidationResult<Type> {
         match ty {
             // Peel away the references
             Type::Reference { ty: underlying_type, .. } => {
@@ -164,24 +416,24 @@ impl Context {
             // If it does, return that field's type
             Type::User(ident) => {
                 self.structs.get(ident)
-                    .ok_or(format!("Type `{}` does not exist", ty))
+                    .ok_or_else(|| validation_error!(UNKNOWN_TYPE, "Type `{}` does not exist", ty))
                     .map(|struct_def| {
                         struct_def.fields.get(field)
                             .map(|field| field.ty.clone())
                     })
                     .transpose()
-                    .unwrap_or(Err(format!("Type `{}` has no field `{}`", ty, field)))
+                    .unwrap_or_else(|| Err(validation_error!(UNKNOWN_FIELD, "Type `{}` has no field `{}`", ty, field)))
             }
 
-            _ => Err(format!("Type `{}` cannot have any fields (tried accessing field `{}`)", ty, field)),
+            _ => Err(validation_error!(NO_FIELDS, "Type `{}` cannot have any fields (tried accessing field `{}`)", ty, field)),
         }
     }
 
     /// Returns the byte offset of a field for the given type.  
     /// Note that the type **must be the base type**. References return errors.
-    pub fn get_field_offset(&self, ty: &Type, field: &str) -> Result<i32, String> {
+    pub fn get_field_offset(&self, ty: &Type, field: &str) -> ValidationResult<i32> {
         match ty {
-            Type::Reference { .. } => Err(format!("Field offsets cannot be obtained from references")),
+            Type::Reference { .. } => Err(validation_error!(NO_FIELDS, "Field offsets cannot be obtained from references")),
 
             Type::Tuple(types) => todo!(),
 
@@ -190,11 +442,11 @@ impl Context {
                 Ok(self.structs.get(ident.as_str()).unwrap().fields.get(field).unwrap().offset)
             }
 
-            _ => Err(format!("Tried getting field offset of incompatible type `{}`", ty)),
+            _ => Err(validation_error!(NO_FIELDS, "Tried getting field offset of incompatible type `{}`", ty)),
         }
     }
 
-    pub fn is_field_public(&self, ty: &Type, field: &str) -> Result<bool, String> {        
+    pub fn is_field_public(&self, ty: &Type, field: &str) -> ValidationResult<bool> {        
         match ty {
             // Recursively strip away type wrappers
             Type::Reference { ty: underlying, .. } => {
@@ -204,26 +456,25 @@ impl Context {
             Type::Tuple(types) => todo!(),
             
             Type::User(name) => {
-                let struct_ = self.structs.get(name.as_str()).ok_or(
-                    format!("No such type: `{}`", name)
+                let struct_ = self.structs.get(name.as_str()).ok_or_else(||
+                    validation_error!(UNKNOWN_TYPE, "No such type: `{}`", name)
                 )?;
 
                 let field = struct_.fields.get(field)
-                    .ok_or(format!("Type `{}` has no field `{}`", ty, field))?;
+                    .ok_or_else(|| validation_error!(UNKNOWN_FIELD, "Type `{}` has no field `{}`", ty, field))?;
 
                 Ok(field.is_public)
             }
 
-            // TODO: Is this correct?
-            _ => unreachable!(), //Err(format!("Type `{}` has no field `{}`", actual_type, field))
+            _ => Err(validation_error!(NO_FIELDS, "Type `{}` cannot have any fields (tried accessing field `{}`)", ty, field)),
         }
     }
 
-    pub fn validate_function_prototype(&self, prototype: &ast::FunctionPrototype) -> Result<(), String> {
-        self.types.assert_valid(&prototype.return_type)?;
+    pub fn validate_function_prototype(&self, prototype: &ast::Node<ast::FunctionPrototype>) -> ValidationResult<()> {
+        self.types.assert_valid(&prototype.return_type).at(prototype.span)?;
 
         for param in &prototype.parameters.item {
-            self.types.assert_valid(&param.ty)?;
+            self.types.assert_valid(&param.ty).at(param.span)?;
         }
 
         Ok(())
@@ -232,57 +483,93 @@ impl Context {
     // TODO: Handle `self` parameter -- needs context of `impl`
     //       `Self` type must be handled similarly
     // NOTE: The function's parameters are valid at this point
-    pub fn validate_function_body(&mut self, function: &mut ast::Function) -> Result<(), String> {        
+    pub fn validate_function_body(&mut self, function: &mut ast::Function) -> ValidationResult<()> {        
         // Create a new scope containing the function's parameters
         self.scopes.push_scope();
-        for param in &function.prototype.parameters.item {
+        let mut parameter_names = HashSet::new();
+        for param in &mut function.prototype.parameters.item {
+            // Parameters can't shadow one another
+            if !parameter_names.insert(param.name.clone()) {
+                return Err(validation_error!(DUPLICATE_VARIABLE, "Parameter `{}` is declared more than once", param.name).at(param.span));
+            }
+
             // NOTE: Function parameters are passed in -> no allocation information needed
-            self.scopes.add_var_to_scope(param.name.clone(), param.mutable, param.ty.clone(), MemoryUsage::FunctionParam)?;
+            param.binding = Some(self.scopes.add_var_to_scope(param.name.clone(), param.mutable, true, param.ty.clone(), MemoryUsage::FunctionParam));
         }
         
         // Validate the function body
-        let _implicit_return_type = self.validate_block(&mut function.body, true)?;
-
-        for (name, data) in self.scopes.pop_scope().variables {
-            self.allocations.insert(self.current_function_name.clone(), name, data.memory_usage)?;
+        let body_result = self.validate_block(&mut function.body, true);
+        self.scopes.pop_scope();
+
+        // Every binding is allocated, including shadowed ones and those of nested blocks
+        let bindings = self.scopes.take_bindings();
+        body_result?;
+        for (id, data) in bindings.into_iter().enumerate() {
+            self.allocations.insert(self.current_function_name.clone(), ast::BindingId(id as u32), data.memory_usage).at(function.body.span)?;
         }
 
-        if self.last_return_type == function.prototype.return_type {
-            // Reset for the next function
+        // The body failed to parse -> its return type can't be trusted
+        if function.body.is_poisoned() {
             self.last_return_type = Type::Unknown;
-            
-            // The function is confirmed valid at this point
-            self.functions.mark_function_validated(function.prototype.name.as_str())?;
+            self.inference.clear();
+            return Ok(());
+        }
 
-            Ok(())
-        } else {
-            Err(format!("Expected function `{}` to have return type `{}` but found `{}`", &function.prototype.name, &function.prototype.return_type, &self.last_return_type))
+        // Uninitialized reads, missing returns, unreachable code, dangling references
+        self.warnings.extend(FlowAnalysis::check_function(function, &self.inference)?);
+
+        let last_return_type = std::mem::replace(&mut self.last_return_type, Type::Unknown);
+        if let Err((expected, found)) = self.inference.unify(&function.prototype.return_type, &last_return_type) {
+            return Err(validation_error!(MISMATCHED_RETURN_TYPE, "Expected function `{}` to have return type `{}` but found `{}`", &function.prototype.name, &expected, &found)
+                .with_label(function.prototype.span, format!("expected `{}` because of this", &expected))
+                .with_secondary_label(function.body.span, format!("returns `{}`", &found)));
         }
+
+        // Everything the body constrains is known now
+        self.finalize_block(&mut function.body)?;
+        self.inference.clear();
+
+        // The function is confirmed valid at this point
+        self.functions.mark_function_validated(function.prototype.name.as_str()).at(function.prototype.span)?;
+
+        Ok(())
     }
 
     /// Validates a block expression/function body.  
     /// Returns the block's type.
-    pub fn validate_block(&mut self, block: &mut ast::BlockExpression, is_function_body: bool) -> Result<Type, String> {
+    pub fn validate_block(&mut self, block: &mut ast::BlockExpression, is_function_body: bool) -> ValidationResult<Type> {
         let mut block_type = Type::Unknown;
 
         for statement in &mut block.block.item {
+            // The parser has already reported the error, so skip the statement rather than cascade
+            if statement.is_poisoned() {
+                continue;
+            }
+
             match &mut statement.item {
                 // ImplcitReturn is just a special expression
                 ast::Statement::ImplicitReturn { expression, is_function_return } => {
-                    let expr_type = self.validate_expression(expression)?;
+                    let mut expr_type = self.validate_expression(expression)?;
+
+                    // Mismatches are reported along with the function's signature
+                    if is_function_body {
+                        expr_type = self.inference.unify(&self.current_return_type, &expr_type).unwrap_or(expr_type);
+                    }
 
                     if is_function_body {
                         *is_function_return = true;
 
-                        if let Some(ident) = Self::reduce_expression_to_alias(expression) {
-                            self.scopes.signal_return_variable(ident.to_owned());
+                        if let Some(binding) = Self::reduce_expression_to_alias(expression) {
+                            self.scopes.signal_return_variable(binding);
                         }
                     }
 
                     if block_type.is_unknown() {
                         block_type = expr_type;
-                    } else if block_type != expr_type {
-                        return Err(format!("Differing return types. Expected `{}` but found `{}`", &block_type, &expr_type));
+                    } else {
+                        block_type = self.inference.unify(&block_type, &expr_type).map_err(|(expected, found)|
+                            validation_error!(MISMATCHED_TYPES, "Differing return types. Expected `{}` but found `{}`", expected, found).at(expression.span)
+                        )?;
                     }
                 }
 
@@ -308,12 +595,17 @@ impl Context {
     }
 
     /// Validates a statement & assigns types
-    pub fn validate_statement(&mut self, statement: &mut ast::Statement) -> Result<(), String> {
-        match statement {
-            // Ensures the variable is not already in scope and has valid types
-            ast::Statement::Let { ident, mutable, ty, value } => {
+    pub fn validate_statement(&mut self, statement: &mut ast::Node<ast::Statement>) -> ValidationResult<()> {
+        match &mut statement.item {
+            // Shadows any existing variable of the same name & ensures valid types
+            ast::Statement::Let { ident, mutable, ty, value, binding } => {
                 // All variables use stack slots by default
                 let mut memory_usage = MemoryUsage::StackSlot;
+
+                // Inferred from the value and/or later uses
+                if ty.is_unknown() {
+                    *ty = self.inference.fresh(TypeVariableKind::General);
+                }
                 
                 // Variable is declared and assigned
                 if let Some(expr) = value {
@@ -322,26 +614,17 @@ impl Context {
                     // If this assignment simply aliases another variable,
                     // signal that no allocations are needed, as this will use that variable's
                     if let Some(alias) = Self::reduce_expression_to_alias(expr) {
-                        memory_usage = MemoryUsage::Alias(alias.to_owned());
+                        memory_usage = MemoryUsage::Alias(alias);
                     }
 
-                    if ty.is_unknown() {
-                        // Immediate type inferrence
-                        *ty = assigned_type;
-                    } else if ty != &assigned_type {
-                        // Eplicit type must be equivalent to the expression's type
-                        return Err(format!("Variable `{}` has type `{}`, but is assigned the type `{}`", ident, ty, assigned_type));
-                    }
-                // Variable is declared, not assigned
-                } else {
-                    // Type is not known and cannot be determined at the moment
-                    if ty.is_unknown() {
-                        // TODO: Account for unknown types
-                        todo!("Mark variable for being inferred later on");
+                    // Explicit type must be equivalent to the expression's type
+                    if let Err((expected, found)) = self.inference.unify(ty, &assigned_type) {
+                        return Err(validation_error!(MISMATCHED_TYPES, "Variable `{}` has type `{}`, but is assigned the type `{}`", ident, expected, found).at(expr.span));
                     }
                 }
 
-                self.scopes.add_var_to_scope(ident.clone(), *mutable, ty.clone(), memory_usage)?;
+                // Declared after its value, so `let x = x + 1;` refers to the previous `x`
+                *binding = Some(self.scopes.add_var_to_scope(ident.clone(), *mutable, value.is_some(), ty.clone(), memory_usage));
             }
 
             // TODO: aliasing/reducing
@@ -371,45 +654,52 @@ impl Context {
                 let assigned_type = self.validate_expression(expression)?;
 
                 match &lhs.item {
-                    ast::Expression::FieldAccess { base_expr, field, ty } => {
-                        // TODO: Assert that the root of base_expr is mutable
-                    }
-
-                    ast::Expression::Ident { name, ty } => {
-                        let var_data = self.scopes.get_variable_mut(name)?;
-                        if !var_data.mutable {
-                            return Err(format!("Cannot assign to immutable variable `{}`", name));
+                    ast::Expression::Ident { name, binding, .. } => {
+                        // NOTE: Set when `lhs` was validated
+                        let var_data = self.scopes.get_variable_mut(binding.expect("validated binding"));
+                        // `let x; x = ..;` gives an immutable variable its value
+                        if !var_data.mutable && var_data.is_initialized {
+                            return Err(validation_error!(ASSIGN_TO_IMMUTABLE, "Cannot assign to immutable variable `{}`", name).at(lhs.span));
                         }
-                        if let Some(ident) = Self::reduce_expression_to_alias(expression) {
-                            var_data.memory_usage = MemoryUsage::Alias(ident.to_owned());
+                        var_data.is_initialized = true;
+                        if let Some(alias) = Self::reduce_expression_to_alias(expression) {
+                            var_data.memory_usage = MemoryUsage::Alias(alias);
                         }
                     }
 
-                    ast::Expression::FunctionCall { name, inputs, ty } => {
-                        todo!("assign to function calls if `&mut` returned?");
+                    ast::Expression::FieldAccess { .. } => self.check_place_mutability(lhs, "assign to")?,
+                    ast::Expression::UnaryExpression { op, .. } if matches!(op.item, ast::UnaryOp::Dereference) => {
+                        self.check_place_mutability(lhs, "assign to")?;
+                    }
+
+                    _ => {
+                        return Err(validation_error!(INVALID_ASSIGNMENT, "Invalid left-hand side of assignment")
+                            .with_label(lhs.span, "cannot assign to this expression".to_owned())
+                            .with_note("only variables, fields, and dereferences (`*x`) can be assigned to".to_owned()));
                     }
-                    _ => unreachable!(),
                 }
                 
-                if destination_type != assigned_type {
-                    return Err(format!("Tried assigning type `{}` to incompatible type `{}`", &destination_type, &assigned_type));
+                if let Err((destination_type, assigned_type)) = self.inference.unify(&destination_type, &assigned_type) {
+                    return Err(validation_error!(MISMATCHED_TYPES, "Tried assigning type `{}` to incompatible type `{}`", &assigned_type, &destination_type).at(expression.span));
                 }
             }
 
             ast::Statement::Return { expression } => {
                 // Note the type
                 let return_type = self.validate_expression(expression)?;
+                // Mismatches are reported along with the function's signature
+                let return_type = self.inference.unify(&self.current_return_type, &return_type).unwrap_or(return_type);
 
                 if self.last_return_type.is_unknown() {
                     self.last_return_type = return_type;
-                } else if self.last_return_type != return_type {
-                    return Err(format!("Found differing return types: `{}` and `{}`", &return_type, &self.last_return_type));
+                } else if let Err((last, found)) = self.inference.unify(&self.last_return_type, &return_type) {
+                    return Err(validation_error!(MISMATCHED_RETURN_TYPE, "Found differing return types: `{}` and `{}`", &found, &last).at(expression.span));
                 }
 
                 // If a stack-allocated variable is being returned,
                 // signal that the variable must use the struct-return slot
-                if let Some(ident) = Self::reduce_expression_to_alias(expression) {
-                    self.scopes.signal_return_variable(ident.to_owned());
+                if let Some(binding) = Self::reduce_expression_to_alias(expression) {
+                    self.scopes.signal_return_variable(binding);
                 }
             }
 
@@ -427,13 +717,15 @@ impl Context {
 
     // TODO: Make sure `ty` is assigned wherever needed
     /// Validates an expression, determining its type. Returns the type of the expression.
-    pub fn validate_expression(&mut self, expression: &mut ast::Expression) -> Result<Type, String> {
-        match expression {
+    pub fn validate_expression(&mut self, expression: &mut ast::Node<ast::Expression>) -> ValidationResult<Type> {
+        let span = expression.span;
+
+        match &mut expression.item {
             ast::Expression::BinaryExpression { lhs, op, rhs, ty } => {
                 let l_type = self.validate_expression(lhs)?;
                 let r_type = self.validate_expression(rhs)?;
-                self.types.assert_valid(&l_type)?;
-                self.types.assert_valid(&r_type)?;
+                self.types.assert_valid(&l_type).at(lhs.span)?;
+                self.types.assert_valid(&r_type).at(rhs.span)?;
 
                 match &op.item {
                     ast::BinaryOp::Custom(custom) => {
@@ -446,54 +738,62 @@ impl Context {
                                         *lhs.clone(),
                                         *rhs.clone(),
                                     ],
-                                    ty: self.functions.get_unchecked_function_definition(&pattern.1)?.return_type.clone(),
+                                    ty: self.functions.get_unchecked_function_definition(&pattern.1).at(op.span)?.return_type.clone(),
                                 })
                             }
                         }
 
                         if let Some(expr) = new_expr {
-                            *expression = expr;
+                            expression.item = expr;
                             self.validate_expression(expression)
                         } else {
-                            Err(format!("Binary operator `{:?}` is not defined", custom))
-                        }
-                    }
-
-                    ast::BinaryOp::Add => {
-                        // Primitive numeric types can be multiplied together
-                        if l_type.is_numeric() && (r_type == l_type) {
-                            // l/r_type is arbitrary here
-                            *ty = r_type;
-                            Ok(l_type)
-                        } else {
-                            todo!("Convert `+` to `std::ops::add(LType, RType)` call")
+                            Err(validation_error!(UNKNOWN_OPERATOR, "Binary operator `{:?}` is not defined", custom).at(op.span))
                         }
                     }
 
-                    ast::BinaryOp::Subtract => {
-                        todo!()
-                    }
-
-                    ast::BinaryOp::Multiply => {
-                        // Primitive numeric types can be multiplied together
-                        if l_type.is_numeric() && (r_type == l_type) {
-                            // l/r_type is arbitrary here
-                            *ty = r_type;
-                            Ok(l_type)
-                        } else {
-                            todo!("Convert `*` to `std::ops::multiply(LType, RType)` call")
+                    arithmetic => {
+                        let symbol = match arithmetic {
+                            ast::BinaryOp::Add => "+",
+                            ast::BinaryOp::Subtract => "-",
+                            ast::BinaryOp::Multiply => "*",
+                            ast::BinaryOp::Divide => "/",
+                            ast::BinaryOp::Custom(_) => unreachable!(),
+                        };
+
+                        // Primitive numeric types support arithmetic
+                        let operand_type = self.unify_operands(&l_type, &r_type, op.span, lhs.span, rhs.span)
+                            .and_then(|operand_type| if operand_type.is_numeric() || operand_type.is_inference_variable() {
+                                Ok(operand_type)
+                            } else {
+                                Err(validation_error!(MISMATCHED_TYPES, "Binary operator `{}` cannot be applied to type `{}`", symbol, operand_type)
+                                    .with_label(op.span, format!("expected numeric operands for `{}`", symbol))
+                                    .with_secondary_label(lhs.span, format!("has type `{}`", operand_type))
+                                    .with_secondary_label(rhs.span, format!("has type `{}`", operand_type)))
+                            });
+
+                        match operand_type {
+                            Ok(operand_type) => {
+                                *ty = operand_type.clone();
+                                Ok(operand_type)
+                            }
+                            Err(error) => {
+                                expression.is_error_recovery_node = true;
+                                Err(error)
+                            }
                         }
                     }
-
-                    ast::BinaryOp::Divide => {
-                        todo!()
-                    }
                 }
             }
 
             ast::Expression::UnaryExpression { op, expr, ty } => {
                 let expr_type = self.validate_expression(expr)?;
-                self.types.assert_valid(&expr_type)?;
+                self.types.assert_valid(&expr_type).at(expr.span)?;
+                // Anything can be borrowed, even if its type is not yet known
+                let expr_type = if let ast::UnaryOp::Reference { .. } = op.item {
+                    expr_type
+                } else {
+                    self.known_type(&expr_type).at(expr.span)?
+                };
                 
                 match &op.item {
                     // TODO: this
@@ -506,34 +806,66 @@ impl Context {
                                     inputs: vec![
                                         *expr.clone(),
                                     ],
-                                    ty: self.functions.get_unchecked_function_definition(&pattern.1)?.return_type.clone(),
+                                    ty: self.functions.get_unchecked_function_definition(&pattern.1).at(op.span)?.return_type.clone(),
                                 })
                             }
                         }
 
                         if let Some(expr) = new_expr {
-                            *expression = expr;
+                            expression.item = expr;
                             self.validate_expression(expression)
                         } else {
-                            Err(format!("Unary operator `{:?}` is not defined", custom))
+                            Err(validation_error!(UNKNOWN_OPERATOR, "Unary operator `{:?}` is not defined", custom).at(op.span))
                         }
                     }
 
                     ast::UnaryOp::Negate => {
-                        if expr_type.is_signed_integer() || expr_type.is_float() {
+                        // Negated literals are checked against their type once inferred
+                        if expr_type.is_signed_integer() || expr_type.is_float() || expr_type.is_inference_variable() {
                             *ty = expr_type.clone();
                             Ok(expr_type)
                         } else {
-                            todo!("Convert `-` to `std::ops::negate(T)` call");
+                            let error = validation_error!(MISMATCHED_TYPES, "Unary operator `-` cannot be applied to type `{}`", expr_type)
+                                .with_label(op.span, "expected a signed integer or float operand".to_owned())
+                                .with_secondary_label(expr.span, format!("has type `{}`", expr_type));
+                            expression.is_error_recovery_node = true;
+                            Err(error)
                         }
                     }
 
                     ast::UnaryOp::Not => {
                         // If not boolean, then must convert to `std::op`
                         if expr_type == Type::bool {
+                            *ty = Type::bool;
                             Ok(expr_type)
                         } else {
-                            todo!("Convert `!` to `std::ops::not(T)` call");
+                            let error = validation_error!(MISMATCHED_TYPES, "Unary operator `!` cannot be applied to type `{}`", expr_type)
+                                .with_label(op.span, "expected a `bool` operand".to_owned())
+                                .with_secondary_label(expr.span, format!("has type `{}`", expr_type));
+                            expression.is_error_recovery_node = true;
+                            Err(error)
+                        }
+                    }
+
+                    ast::UnaryOp::Reference { mutable } => {
+                        if *mutable {
+                            self.check_place_mutability(expr, "mutably borrow")?;
+                        }
+
+                        let reference_type = Type::Reference {
+                            ty: Box::new(expr_type),
+                            mutable: *mutable,
+                        };
+                        *ty = reference_type.clone();
+                        Ok(reference_type)
+                    }
+
+                    ast::UnaryOp::Dereference => {
+                        if let Type::Reference { ty: underlying, .. } = expr_type {
+                            *ty = *underlying.clone();
+                            Ok(*underlying)
+                        } else {
+                            Err(validation_error!(MISMATCHED_TYPES, "Type `{}` cannot be dereferenced", expr_type).at(span))
                         }
                     }
                 }
@@ -541,7 +873,7 @@ impl Context {
 
             // Ensure that all fields are filled and that valid types are used
             ast::Expression::FieldConstructor { ty, fields } => {
-                self.types.assert_valid(ty)?;
+                self.types.assert_valid(ty).at(span)?;
 
                 // FIXME: To maintain correct field ordering during error printing,
                 //        a Vec can be used instead (at the cost of speed)
@@ -550,7 +882,7 @@ impl Context {
                 // FIXME: A few hacks to avoid immutable + mutable borrow
                 {
                     let struct_definition = self.structs.get(&ty.to_string())
-                        .ok_or(format!("No type `{}` compatible with field constructor", ty))?;
+                        .ok_or_else(|| validation_error!(UNKNOWN_TYPE, "No type `{}` compatible with field constructor", ty).at(span))?;
 
                     // Note the required fields
                     for field in struct_definition.fields.keys() {
@@ -562,15 +894,15 @@ impl Context {
                 for (field_name, expr) in fields {
                     // FIXME: Another (not terrible) hack to satisfy borrows
                     let field_type = self.structs.get(&ty.to_string()).unwrap().fields.get(field_name.as_str())
-                        .ok_or(format!("Type `{}` has no field `{}`", ty, field_name))?
+                        .ok_or_else(|| validation_error!(UNKNOWN_FIELD, "Type `{}` has no field `{}`", ty, field_name).at(expr.span))?
                         .ty.clone();
                     
                     // Required field is accounted for
                     required_fields.remove(field_name.as_str());
 
                     let assigned_type = self.validate_expression(expr)?;
-                    if assigned_type != field_type {
-                        return Err(format!("Field `{}.{}` is of type `{}`, but found type `{}`", ty, field_name, field_type, assigned_type));
+                    if let Err((field_type, assigned_type)) = self.inference.unify(&field_type, &assigned_type) {
+                        return Err(validation_error!(MISMATCHED_TYPES, "Field `{}.{}` is of type `{}`, but found type `{}`", ty, field_name, field_type, assigned_type).at(expr.span));
                     }
                 }
 
@@ -584,7 +916,7 @@ impl Context {
                     // Remove trailing ", "
                     error.pop();
                     error.pop();
-                    return Err(error);
+                    return Err(validation_error!(MISSING_FIELDS, "{}", error).at(span));
                 }
 
                 Ok(ty.clone())
@@ -593,12 +925,13 @@ impl Context {
             // TODO: This needs to be modified later to also support enums and tuples
             ast::Expression::FieldAccess { base_expr, field, ty } => {
                 let base_type = self.validate_expression(base_expr)?;
+                let base_type = self.known_type(&base_type).at(base_expr.span)?;
                 
-                if !self.is_field_public(&base_type, field)? {
-                    return Err(format!("Field `{}` of `{}` is private", field, base_type));
+                if !self.is_field_public(&base_type, field).at(span)? {
+                    return Err(validation_error!(PRIVATE_FIELD, "Field `{}` of `{}` is private", field, base_type).at(span));
                 }
                 
-                let field_type = self.get_field_type(&base_type, field)?;
+                let field_type = self.get_field_type(&base_type, field).at(span)?;
                 *ty = field_type.clone();
 
                 Ok(field_type)
@@ -614,16 +947,16 @@ impl Context {
                     );
                 }
 
-                let definition = self.functions.get_unchecked_function_definition(name)?;
+                let definition = self.functions.get_unchecked_function_definition(name).at(span)?;
 
                 if definition.parameters.len() != inputs.len() {
-                    return Err(format!("Function `{}` accepts {} parameters, but {} were passed", name, definition.parameters.len(), inputs.len()));
+                    return Err(validation_error!(WRONG_ARGUMENT_COUNT, "Function `{}` accepts {} parameters, but {} were passed", name, definition.parameters.len(), inputs.len()).at(span));
                 }
 
                 // Note that the evaluation order here is the same as the input order
-                for (i, (given_type, (param_name, param_type, _mutable))) in input_types.iter().zip(definition.parameters.iter()).enumerate() {
-                    if given_type != param_type {
-                        return Err(format!("Parameter #{} (`{}`) of call to `{}` has type `{}`, but found type `{}`", i, param_name, name, param_type, given_type));
+                for (i, (input_expr, (param_name, param_type, _mutable))) in inputs.iter_mut().zip(definition.parameters.iter()).enumerate() {
+                    if let Err((param_type, given_type)) = self.inference.unify(param_type, &input_types[i]) {
+                        return Err(validation_error!(MISMATCHED_TYPES, "Parameter #{} (`{}`) of call to `{}` has type `{}`, but found type `{}`", i, param_name, name, param_type, given_type).at(input_expr.span));
                     }
                 }
 
@@ -633,26 +966,31 @@ impl Context {
             }
 
             ast::Expression::Block(block) => {
+                // Variables declared within the block are only visible inside of it
                 self.scopes.push_scope();
-                let expr_type = self.validate_block(block, false)?;
-                
-                for (name, data) in self.scopes.pop_scope().variables {
-                    self.allocations.insert(self.current_function_name.clone(), name, data.memory_usage)?;
-                }
+                let expr_type = self.validate_block(block, false);
+                self.scopes.pop_scope();
 
-
-                Ok(expr_type)
+                expr_type
             }
 
-            ast::Expression::Literal { value: _, ty } => {
-                // TODO: Is this correct? 
-                //       What about when the type isn't known?
+            ast::Expression::Literal { value, ty } => {
+                // Unsuffixed literals are checked once their type is inferred
+                match ty {
+                    Type::IntegerVariable => *ty = self.inference.fresh(TypeVariableKind::Integer),
+                    Type::FloatVariable => *ty = self.inference.fresh(TypeVariableKind::Float),
+                    Type::Variable(_) => {}
+                    _ => self.check_literal_range(value, ty).at(span)?,
+                }
+
                 Ok(ty.clone())
             }
 
             // Returns the type of the variable
-            ast::Expression::Ident { name, ty } => {
-                let ident_type = self.scopes.get_variable(name).map(|var| var.ty.clone())?;
+            ast::Expression::Ident { name, ty, binding } => {
+                let id = self.scopes.lookup(name).at(span)?;
+                let ident_type = self.scopes.get_variable(id).ty.clone();
+                *binding = Some(id);
                 *ty = ident_type.clone();
 
                 Ok(ident_type)
@@ -660,12 +998,183 @@ impl Context {
         }
     }
 
+    /// Ensures that a place (`x`, `x.y`, `*x`, ..) may be changed, either by assigning to it or by borrowing it as `&mut`.  
+    /// A field is only as mutable as what it belongs to: its root variable, or the reference it is reached through.
+    fn check_place_mutability(&self, place: &ast::Node<ast::Expression>, action: &str) -> ValidationResult<()> {
+        // The reference which the place is reached through
+        let reference = match &place.item {
+            ast::Expression::Ident { name, binding, .. } => {
+                return if self.scopes.get_variable(binding.expect("validated binding")).mutable {
+                    Ok(())
+                } else {
+                    Err(validation_error!(ASSIGN_TO_IMMUTABLE, "Cannot {} immutable variable `{}`", action, name)
+                        .with_label(place.span, format!("`{}` is not declared as `mut`", name)))
+                };
+            }
+
+            ast::Expression::FieldAccess { base_expr, .. } => {
+                if self.inference.resolve(base_expr.get_type()).is_reference() {
+                    base_expr
+                } else {
+                    return self.check_place_mutability(base_expr, action);
+                }
+            }
+
+            ast::Expression::UnaryExpression { op, expr, .. } if matches!(op.item, ast::UnaryOp::Dereference) => expr,
+
+            // Temporaries (`&mut 5`, `&mut f()`, ..) belong to nothing else
+            _ => return Ok(()),
+        };
+
+        if self.inference.resolve(reference.get_type()).is_mutable_reference() {
+            Ok(())
+        } else {
+            Err(validation_error!(ASSIGN_TO_IMMUTABLE, "Cannot {} data behind a `&` reference", action)
+                .with_label(place.span, format!("cannot {} this", action))
+                .with_secondary_label(reference.span, "this is a `&` reference, so the data it refers to cannot be changed".to_owned()))
+        }
+    }
+
+    /// Unifies the operands of a builtin operator
+    fn unify_operands(&mut self, l_type: &Type, r_type: &Type, op_span: crate::Span, l_span: crate::Span, r_span: crate::Span) -> ValidationResult<Type> {
+        let operand_type = self.inference.unify(l_type, r_type).map_err(|(l_type, r_type)|
+            validation_error!(MISMATCHED_TYPES, "Mismatched operand types `{}` and `{}`", l_type, r_type)
+                .with_label(op_span, String::new())
+                .with_secondary_label(l_span, format!("has type `{}`", l_type))
+                .with_secondary_label(r_span, format!("has type `{}`", r_type))
+        )?;
+
+        self.known_type(&operand_type).at(op_span)
+    }
+
+    /// Resolves a type which must be known at this point (for field accesses, operators, etc.).  
+    /// Literals only need to be known to be integers or floats.
+    fn known_type(&self, ty: &Type) -> ValidationResult<Type> {
+        match self.inference.resolve(ty) {
+            Type::Variable(variable) if variable.kind == TypeVariableKind::General => {
+                Err(validation_error!(TYPE_ANNOTATIONS_NEEDED, "Type annotations needed (the type must be known at this point)"))
+            }
+            resolved => Ok(resolved),
+        }
+    }
+
+    /// Replaces every type within a validated function body with its inferred type
+    fn finalize_block(&self, block: &mut ast::BlockExpression) -> ValidationResult<()> {
+        block.ty = self.finalize_type(&block.ty).at(block.block.span)?;
+
+        for statement in &mut block.block.item {
+            let span = statement.span;
+
+            match &mut statement.item {
+                ast::Statement::Let { ident, ty, value, .. } => {
+                    *ty = self.finalize_type(ty).map_err(|_|
+                        validation_error!(TYPE_ANNOTATIONS_NEEDED, "Type annotations needed for `{}`", ident).at(span)
+                    )?;
+
+                    if let Some(expr) = value {
+                        self.finalize_expression(expr)?;
+                    }
+                }
+
+                ast::Statement::Assign { lhs, expression, .. } => {
+                    self.finalize_expression(lhs)?;
+                    self.finalize_expression(expression)?;
+                }
+
+                ast::Statement::Return { expression }
+                | ast::Statement::ImplicitReturn { expression, .. }
+                | ast::Statement::Expression(expression) => {
+                    self.finalize_expression(expression)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finalize_expression(&self, expression: &mut ast::Node<ast::Expression>) -> ValidationResult<()> {
+        let span = expression.span;
+
+        match &mut expression.item {
+            ast::Expression::BinaryExpression { lhs, rhs, ty, .. } => {
+                self.finalize_expression(lhs)?;
+                self.finalize_expression(rhs)?;
+                *ty = self.finalize_type(ty).at(span)?;
+            }
+
+            ast::Expression::UnaryExpression { expr, ty, .. } => {
+                self.finalize_expression(expr)?;
+                *ty = self.finalize_type(ty).at(span)?;
+            }
+
+            ast::Expression::FieldConstructor { fields, .. } => {
+                for expr in fields.values_mut() {
+                    self.finalize_expression(expr)?;
+                }
+            }
+
+            ast::Expression::FieldAccess { base_expr, ty, .. } => {
+                self.finalize_expression(base_expr)?;
+                *ty = self.finalize_type(ty).at(span)?;
+            }
+
+            ast::Expression::FunctionCall { inputs, ty, .. } => {
+                for input in inputs {
+                    self.finalize_expression(input)?;
+                }
+                *ty = self.finalize_type(ty).at(span)?;
+            }
+
+            ast::Expression::Block(block) => self.finalize_block(block)?,
+
+            ast::Expression::Literal { value, ty } => {
+                *ty = self.finalize_type(ty).at(span)?;
+                self.check_literal_range(value, ty).at(span)?;
+            }
+
+            ast::Expression::Ident { ty, .. } => {
+                *ty = self.finalize_type(ty).at(span)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a type, defaulting literals to `i32`/`f64`
+    fn finalize_type(&self, ty: &Type) -> ValidationResult<Type> {
+        self.inference.resolve_with_defaults(ty)
+            .ok_or_else(|| validation_error!(TYPE_ANNOTATIONS_NEEDED, "Type annotations needed (could not infer `{}`)", self.inference.resolve_fully(ty)))
+    }
+
+    /// Ensures that a literal's value can be represented by its type
+    fn check_literal_range(&self, value: &ast::Literal, ty: &Type) -> ValidationResult<()> {
+        match value {
+            ast::Literal::Integer(integer) => {
+                if let Some((min, max)) = ty.integer_range(self.types.target()) {
+                    if *integer < min || *integer > max {
+                        return Err(validation_error!(LITERAL_OUT_OF_RANGE, "Literal `{}` does not fit in type `{}` (range is `{}..={}`)", integer, ty, min, max));
+                    }
+                }
+            }
+
+            ast::Literal::Float(float) => {
+                if ty == &Type::f32 && float.is_finite() && (*float as f32).is_infinite() {
+                    return Err(validation_error!(LITERAL_OUT_OF_RANGE, "Literal `{}` does not fit in type `{}`", float, ty));
+                }
+            }
+
+            ast::Literal::UnitType => {}
+        }
+
+        Ok(())
+    }
+
     /// If an expression reduces to an alias, return the alias.  
     /// Returns `None` if the expression does not alias any variables.
     // TODO: Re-enable this once used
-    fn reduce_expression_to_alias(validated_expression: &ast::Expression) -> Option<&'static str> {
-        // if let ast::Expression::Ident { name, .. } = validated_expression {
-        //     return Some(name);
+    fn reduce_expression_to_alias(validated_expression: &ast::Expression) -> Option<ast::BindingId> {
+        // if let ast::Expression::Ident { binding, .. } = validated_expression {
+        //     return *binding;
         // }
 
         None
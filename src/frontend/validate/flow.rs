@@ -0,0 +1,276 @@
+// Flow-sensitive checks, run on each function body once it has been validated:
+// - Variables must be given a value before they are read
+// - Functions which return a value must do so on every path
+// - Code following a `return` is unreachable (warning)
+// - Returned references must not point into the function's own stack frame
+//
+// Jitter has no lifetimes, so references are followed by tracking which borrows of
+// locals (`&x`, `&x.y`, `&5`, ..) each variable may hold. This is deliberately simple:
+// a function call's result may point into any of its arguments.
+//
+// NOTE: Jitter has no branching yet, so the only path through a body is the straight line
+//       through its statements. `FlowState` is what will need merging once it does.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, codes};
+use crate::frontend::parse::ast::{self, BindingId};
+use crate::Span;
+
+use super::ValidationResult;
+use super::infer::InferenceTable;
+
+/// What is known at a given point of a function body
+#[derive(Clone, Default)]
+struct FlowState {
+    /// Bindings which definitely hold a value
+    initialized: HashSet<BindingId>,
+    /// Set once control can no longer reach this point (holds the `return` responsible)
+    diverged_at: Option<Span>,
+    /// Bindings -> borrows of locals which their values may point into
+    borrows: HashMap<BindingId, Vec<Span>>,
+}
+
+pub struct FlowAnalysis<'a> {
+    /// Types within the body may not be finalized yet
+    inference: &'a InferenceTable,
+    state: FlowState,
+    /// Bindings declared without a value -> their declaration
+    declarations: HashMap<BindingId, Span>,
+    /// Only the first unreachable statement is reported
+    reported_unreachable: bool,
+    /// Non-fatal findings
+    warnings: Vec<Diagnostic>,
+    /// Whether the function returns a reference (which must outlive the function)
+    returns_reference: bool,
+}
+
+impl<'a> FlowAnalysis<'a> {
+    /// Checks a validated function, returning any warnings
+    pub fn check_function(function: &ast::Function, inference: &'a InferenceTable) -> ValidationResult<Vec<Diagnostic>> {
+        let mut analysis = Self {
+            inference,
+            state: FlowState::default(),
+            declarations: HashMap::new(),
+            reported_unreachable: false,
+            warnings: Vec::new(),
+            returns_reference: function.prototype.return_type.is_reference(),
+        };
+
+        // Parameters are always given values by the caller
+        for param in &function.prototype.parameters.item {
+            if let Some(binding) = param.binding {
+                analysis.state.initialized.insert(binding);
+            }
+        }
+
+        analysis.check_block(&function.body)?;
+
+        let has_tail = function.body.block.item.last()
+            .is_some_and(|statement| matches!(statement.item, ast::Statement::ImplicitReturn { .. }));
+
+        if !function.prototype.return_type.is_unit() && !has_tail && analysis.state.diverged_at.is_none() {
+            return Err(validation_error!(MISSING_RETURN, "Function `{}` does not return a value on every path", function.prototype.name)
+                .with_label(function.prototype.span, format!("expected `{}` because of this", function.prototype.return_type))
+                .with_secondary_label(function.body.span, "reaches the end of the body without returning".to_owned()));
+        }
+
+        Ok(analysis.warnings)
+    }
+
+    fn check_block(&mut self, block: &ast::BlockExpression) -> ValidationResult<()> {
+        for statement in &block.block.item {
+            if let Some(return_span) = self.state.diverged_at {
+                if !self.reported_unreachable {
+                    self.reported_unreachable = true;
+                    self.warnings.push(
+                        Diagnostic::warning("Unreachable statement".to_owned())
+                            .with_code(codes::UNREACHABLE_CODE)
+                            .with_label(statement.span, "unreachable statement".to_owned())
+                            .with_secondary_label(return_span, "any code following this `return` is unreachable".to_owned())
+                    );
+                }
+
+                // Nothing here runs, so there is nothing else to check
+                return Ok(());
+            }
+
+            self.check_statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_statement(&mut self, statement: &ast::Node<ast::Statement>) -> ValidationResult<()> {
+        match &statement.item {
+            ast::Statement::Let { value, binding, .. } => {
+                // NOTE: Bindings are always assigned by validation
+                let binding = binding.expect("validated binding");
+
+                if let Some(value) = value {
+                    self.check_expression(value)?;
+                    self.state.initialized.insert(binding);
+                    self.state.borrows.insert(binding, self.local_borrows(value));
+                } else {
+                    self.declarations.insert(binding, statement.span);
+                }
+            }
+
+            ast::Statement::Assign { lhs, expression, .. } => {
+                self.check_expression(expression)?;
+
+                // Assigning to a variable gives it a value. Assigning to a field reads its base.
+                match &lhs.item {
+                    ast::Expression::Ident { binding: Some(binding), .. } => {
+                        self.state.initialized.insert(*binding);
+                        self.state.borrows.insert(*binding, self.local_borrows(expression));
+                    }
+                    _ => self.check_expression(lhs)?,
+                }
+            }
+
+            ast::Statement::Return { expression } => {
+                self.check_expression(expression)?;
+                self.check_returned_reference(expression)?;
+                self.state.diverged_at = Some(statement.span);
+            }
+
+            ast::Statement::ImplicitReturn { expression, is_function_return } => {
+                self.check_expression(expression)?;
+                if *is_function_return {
+                    self.check_returned_reference(expression)?;
+                }
+            }
+
+            ast::Statement::Expression(expression) => self.check_expression(expression)?,
+        }
+
+        Ok(())
+    }
+
+    fn check_expression(&mut self, expression: &ast::Node<ast::Expression>) -> ValidationResult<()> {
+        match &expression.item {
+            ast::Expression::Ident { name, binding, .. } => {
+                let binding = binding.expect("validated binding");
+
+                if !self.state.initialized.contains(&binding) {
+                    let mut error = validation_error!(UNINITIALIZED_VARIABLE, "Variable `{}` is used before it is given a value", name)
+                        .with_label(expression.span, format!("`{}` used here but it is possibly uninitialized", name));
+
+                    if let Some(declaration) = self.declarations.get(&binding) {
+                        error = error.with_secondary_label(*declaration, format!("`{}` declared here without a value", name));
+                    }
+
+                    return Err(error);
+                }
+            }
+
+            ast::Expression::BinaryExpression { lhs, rhs, .. } => {
+                self.check_expression(lhs)?;
+                self.check_expression(rhs)?;
+            }
+
+            ast::Expression::UnaryExpression { expr, .. } => self.check_expression(expr)?,
+
+            ast::Expression::FieldConstructor { fields, .. } => {
+                for field in fields.values() {
+                    self.check_expression(field)?;
+                }
+            }
+
+            ast::Expression::FieldAccess { base_expr, .. } => self.check_expression(base_expr)?,
+
+            ast::Expression::FunctionCall { inputs, .. } => {
+                for input in inputs {
+                    self.check_expression(input)?;
+                }
+            }
+
+            // A `return` within a block also leaves the enclosing function
+            ast::Expression::Block(block) => self.check_block(block)?,
+
+            ast::Expression::Literal { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    fn check_returned_reference(&self, expression: &ast::Node<ast::Expression>) -> ValidationResult<()> {
+        if !self.returns_reference {
+            return Ok(());
+        }
+
+        let borrows = self.local_borrows(expression);
+        if borrows.is_empty() {
+            return Ok(());
+        }
+
+        let mut error = validation_error!(RETURN_LOCAL_REFERENCE, "Cannot return a reference to data owned by the current function")
+            .with_label(expression.span, "returns a reference to data which is freed once the function returns".to_owned());
+
+        for borrow in borrows {
+            error = error.with_secondary_label(borrow, "borrowed here".to_owned());
+        }
+
+        Err(error)
+    }
+
+    /// Borrows of this function's locals (and temporaries) which the value of an expression may point into
+    fn local_borrows(&self, expression: &ast::Node<ast::Expression>) -> Vec<Span> {
+        match &expression.item {
+            ast::Expression::Ident { binding, .. } => binding
+                .and_then(|binding| self.state.borrows.get(&binding))
+                .cloned()
+                .unwrap_or_default(),
+
+            ast::Expression::UnaryExpression { op, expr, .. } => match op.item {
+                ast::UnaryOp::Reference { .. } => self.place_borrows(expr, expression.span),
+                _ => Vec::new(),
+            },
+
+            // A reference stored in a field points wherever the reference it was stored from did
+            ast::Expression::FieldAccess { base_expr, .. } => self.local_borrows(base_expr),
+
+            ast::Expression::FieldConstructor { fields, .. } => fields.values()
+                .flat_map(|field| self.local_borrows(field))
+                .collect(),
+
+            // Without lifetimes, the result may point into any of the arguments
+            ast::Expression::FunctionCall { inputs, .. } => inputs.iter()
+                .flat_map(|input| self.local_borrows(input))
+                .collect(),
+
+            ast::Expression::Block(block) => match block.block.item.last().map(|statement| &statement.item) {
+                Some(ast::Statement::ImplicitReturn { expression, .. }) => self.local_borrows(expression),
+                _ => Vec::new(),
+            },
+
+            ast::Expression::BinaryExpression { .. }
+            | ast::Expression::Literal { .. } => Vec::new(),
+        }
+    }
+
+    /// Borrows of locals made by borrowing a place (`&x`, `&x.y`, `&*x`, ..)
+    fn place_borrows(&self, place: &ast::Node<ast::Expression>, borrow: Span) -> Vec<Span> {
+        match &place.item {
+            // Parameters belong to the function too
+            ast::Expression::Ident { .. } => vec![borrow],
+
+            // Fields reached through a reference live wherever the reference points
+            ast::Expression::FieldAccess { base_expr, .. } => {
+                if self.inference.resolve(base_expr.get_type()).is_reference() {
+                    self.local_borrows(base_expr)
+                } else {
+                    self.place_borrows(base_expr, borrow)
+                }
+            }
+
+            ast::Expression::UnaryExpression { op, expr, .. } if matches!(op.item, ast::UnaryOp::Dereference) => {
+                self.local_borrows(expr)
+            }
+
+            // Temporaries only live until the function returns
+            _ => vec![borrow],
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Type inference within a single function body.
+//
+// Every type which isn't known up front (unannotated `let`s, unsuffixed literals)
+// becomes a `Type::Variable`. Validation unifies these as it walks the body, and once
+// the whole body has been seen, each variable is replaced by whatever it was bound to.
+
+use super::types::{Type, TypeVariable, TypeVariableKind};
+
+/// Stores what each type variable has been unified with
+#[derive(Default)]
+pub struct InferenceTable {
+    /// Kind and binding of each variable (indexed by `TypeVariable::id`)
+    variables: Vec<(TypeVariableKind, Option<Type>)>,
+}
+
+impl InferenceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, unbound type variable
+    pub fn fresh(&mut self, kind: TypeVariableKind) -> Type {
+        self.variables.push((kind, None));
+
+        Type::Variable(TypeVariable {
+            id: self.variables.len() as u32 - 1,
+            kind,
+        })
+    }
+
+    /// Forgets every variable. Functions are inferred independently of one another.
+    pub fn clear(&mut self) {
+        self.variables.clear();
+    }
+
+    /// Follows variable bindings until reaching a non-variable or an unbound variable.
+    /// Types nested within references & tuples are left as they are.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        let mut current = ty.clone();
+
+        while let Type::Variable(variable) = &current {
+            match &self.variables[variable.id as usize].1 {
+                Some(bound) => current = bound.clone(),
+                None => break,
+            }
+        }
+
+        current
+    }
+
+    /// Resolves a type along with every type nested within it.
+    /// Unbound variables are left in place.
+    pub fn resolve_fully(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Reference { ty, mutable } => Type::Reference {
+                ty: Box::new(self.resolve_fully(&ty)),
+                mutable,
+            },
+
+            Type::Tuple(types) => Type::Tuple(
+                types.iter().map(|ty| self.resolve_fully(ty)).collect()
+            ),
+
+            resolved => resolved,
+        }
+    }
+
+    /// Resolves a type, replacing unbound literal variables with their defaults (`i32` & `f64`).
+    /// Returns `None` if anything is left undetermined.
+    pub fn resolve_with_defaults(&self, ty: &Type) -> Option<Type> {
+        match self.resolve_fully(ty) {
+            Type::Variable(TypeVariable { kind: TypeVariableKind::Integer, .. }) => Some(Type::i32),
+            Type::Variable(TypeVariable { kind: TypeVariableKind::Float, .. }) => Some(Type::f64),
+            Type::Variable(TypeVariable { kind: TypeVariableKind::General, .. })
+            | Type::Unknown => None,
+
+            Type::Reference { ty, mutable } => Some(Type::Reference {
+                ty: Box::new(self.resolve_with_defaults(&ty)?),
+                mutable,
+            }),
+
+            Type::Tuple(types) => Some(Type::Tuple(
+                types.iter()
+                    .map(|ty| self.resolve_with_defaults(ty))
+                    .collect::<Option<Vec<Type>>>()?
+            )),
+
+            resolved => Some(resolved),
+        }
+    }
+
+    /// Makes two types equal, binding variables where needed. Returns the unified type.
+    /// On failure, returns the (fully resolved) `expected` and `found` types for error messages.
+    pub fn unify(&mut self, expected: &Type, found: &Type) -> Result<Type, (Type, Type)> {
+        if self.unify_inner(expected, found) {
+            Ok(self.resolve(expected))
+        } else {
+            Err((self.resolve_fully(expected), self.resolve_fully(found)))
+        }
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type) -> bool {
+        let a = self.resolve(a);
+        let b = self.resolve(b);
+
+        if a == b {
+            return true;
+        }
+
+        match (&a, &b) {
+            (Type::Variable(x), Type::Variable(y)) => {
+                // Bind the less specific variable to the more specific one
+                match (x.kind, y.kind) {
+                    (TypeVariableKind::General, _) => self.bind(*x, b.clone()),
+                    (_, TypeVariableKind::General) => self.bind(*y, a.clone()),
+                    (x_kind, y_kind) if x_kind == y_kind => self.bind(*x, b.clone()),
+                    // `{integer}` and `{float}`
+                    _ => false,
+                }
+            }
+
+            (Type::Variable(variable), ty)
+            | (ty, Type::Variable(variable)) => {
+                let allowed = match variable.kind {
+                    TypeVariableKind::General => !self.occurs_in(variable.id, ty),
+                    TypeVariableKind::Integer => ty.is_integer(),
+                    TypeVariableKind::Float => ty.is_float(),
+                };
+
+                allowed && self.bind(*variable, ty.clone())
+            }
+
+            (Type::Reference { ty: a_ty, mutable: a_mutable }, Type::Reference { ty: b_ty, mutable: b_mutable }) => {
+                a_mutable == b_mutable && self.unify_inner(a_ty, b_ty)
+            }
+
+            (Type::Tuple(a_types), Type::Tuple(b_types)) => {
+                a_types.len() == b_types.len()
+                    && a_types.iter().zip(b_types.iter()).all(|(a, b)| self.unify_inner(a, b))
+            }
+
+            _ => false,
+        }
+    }
+
+    fn bind(&mut self, variable: TypeVariable, ty: Type) -> bool {
+        self.variables[variable.id as usize].1 = Some(ty);
+        true
+    }
+
+    /// Whether a variable appears within a type (binding it would make an infinite type)
+    fn occurs_in(&self, id: u32, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Variable(variable) => variable.id == id,
+            Type::Reference { ty, .. } => self.occurs_in(id, &ty),
+            Type::Tuple(types) => types.iter().any(|ty| self.occurs_in(id, ty)),
+            _ => false,
+        }
+    }
+}
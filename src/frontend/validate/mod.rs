@@ -1,11 +1,37 @@
+/// Creates a validation error with a stable code (see `diagnostic::codes`)
+macro_rules! validation_error {
+    ( $code:ident, $($item:expr),+ ) => {
+        crate::diagnostic::Diagnostic::error(format!( $($item,)+ )).with_code(crate::diagnostic::codes::$code)
+    };
+}
+
 pub mod context;
+pub mod flow;
+pub mod infer;
+pub mod target;
 pub mod types;
 
 ///////////////////// Validation Helpers /////////////////////
 
 use std::collections::HashMap;
+use crate::frontend::validate::target::TargetInfo;
 use crate::frontend::validate::types::Type;
-use crate::frontend::parse::ast;
+use crate::frontend::parse::ast::{self, BindingId};
+use crate::diagnostic::Diagnostic;
+use crate::Span;
+
+pub type ValidationResult<T> = Result<T, Diagnostic>;
+
+/// Locates errors from helpers which don't know where they were called from
+trait Locate {
+    fn at(self, span: Span) -> Self;
+}
+
+impl<T> Locate for ValidationResult<T> {
+    fn at(self, span: Span) -> Self {
+        self.map_err(|error| error.at(span))
+    }
+}
 
 ///////////////////// TYPES /////////////////////
 
@@ -15,6 +41,8 @@ use crate::frontend::parse::ast;
 struct StructDefinition {
     /// Map of field_name -> (type, byte offset)
     fields: HashMap<String, StructField>,
+    /// Field names in declaration order
+    field_order: Vec<String>,
 }
 
 pub struct StructField {
@@ -42,48 +70,53 @@ impl TypeTableEntry {
 /// Stores type sizes and alignments
 pub struct TypeTable {
     /// Map of field_name -> (size, alignment) in bytes
-    data: HashMap<Type, TypeTableEntry>
+    data: HashMap<Type, TypeTableEntry>,
+    /// What the sizes and alignments are for
+    target: TargetInfo,
 }
 
 impl TypeTable {
-    // TODO: Accept word size here and adjust table accordingly
-    // TODO: Support `isize` and `usize`
-    fn new() -> Self {
+    pub fn new(target: TargetInfo) -> Self {
         let mut data = HashMap::new();
 
         // FIXME: This could be looked up via `match`, but this is more consistent
-        // FIXME: Only 64-bit architectures are supported by the below values
         
         data.insert(Type::u8,   TypeTableEntry::new(1, 1));
         data.insert(Type::u16,  TypeTableEntry::new(2, 2));
         data.insert(Type::u32,  TypeTableEntry::new(4, 4));
-        data.insert(Type::u64,  TypeTableEntry::new(8, 8));
-        data.insert(Type::u128, TypeTableEntry::new(16, 8));
+        data.insert(Type::u64,  TypeTableEntry::new(8, target.u64_alignment));
+        data.insert(Type::u128, TypeTableEntry::new(16, target.i128_alignment));
+        data.insert(Type::usize, TypeTableEntry::new(target.pointer_size, target.pointer_size));
 
         data.insert(Type::i8,   TypeTableEntry::new(1, 1));
         data.insert(Type::i16,  TypeTableEntry::new(2, 2));
         data.insert(Type::i32,  TypeTableEntry::new(4, 4));
-        data.insert(Type::i64,  TypeTableEntry::new(8, 8));
-        data.insert(Type::i128, TypeTableEntry::new(16, 8));
+        data.insert(Type::i64,  TypeTableEntry::new(8, target.u64_alignment));
+        data.insert(Type::i128, TypeTableEntry::new(16, target.i128_alignment));
+        data.insert(Type::isize, TypeTableEntry::new(target.pointer_size, target.pointer_size));
 
         data.insert(Type::f32,  TypeTableEntry::new(4, 4));
-        data.insert(Type::f64,  TypeTableEntry::new(8, 8));
+        data.insert(Type::f64,  TypeTableEntry::new(8, target.u64_alignment));
 
         data.insert(Type::bool, TypeTableEntry::new(1, 1));
 
         data.insert(Type::Unit, TypeTableEntry::new(0, 1));
 
-        Self { data }
+        Self { data, target }
     }
 
-    fn insert(&mut self, t: &Type, entry: TypeTableEntry) -> Result<(), String> {
+    pub fn target(&self) -> &TargetInfo {
+        &self.target
+    }
+
+    fn insert(&mut self, t: &Type, entry: TypeTableEntry) -> ValidationResult<()> {
         match self.data.insert(t.clone(), entry) {
-            Some(_) => Err(format!("Type {} already exists", t.clone())),
+            Some(_) => Err(validation_error!(DUPLICATE_TYPE, "Type `{}` already exists", t)),
             None => Ok(()),
         }
     }
 
-    fn assert_valid(&self, t: &Type) -> Result<(), String> {
+    fn assert_valid(&self, t: &Type) -> ValidationResult<()> {
         match t {
             // Strip away references to check the underlying type
             Type::Reference { ty, .. } => Ok(self.assert_valid(ty)?),
@@ -101,22 +134,26 @@ impl TypeTable {
                 Ok(())
             }
 
+            // Checked once inferred
+            Type::IntegerVariable
+            | Type::FloatVariable
+            | Type::Variable(_) => Ok(()),
+
             // Base types
             _ => {
                 if self.data.contains_key(t) {
                     Ok(())
                 } else {
-                    Err(format!("Type `{}` is not valid", t))
+                    Err(validation_error!(UNKNOWN_TYPE, "Type `{}` is not valid", t))
                 }
             }
         }
     }
 
     /// Returns alignment of the type in bytes
-    fn alignment_of(&self, t: &Type) -> usize {
+    pub fn alignment_of(&self, t: &Type) -> usize {
         match t {
-            // TODO: Alignment should be same as pointer type
-            Type::Reference { ty, .. } => todo!("need pointer type stuff"),
+            Type::Reference { .. } => self.target.pointer_size,
             
             // TODO: Tuples should align same as structs
             Type::Tuple(types) => todo!("tuple alignment"),
@@ -127,7 +164,11 @@ impl TypeTable {
 
     /// Returns the size of the type in bytes
     pub fn size_of(&self, t: &Type) -> usize {
-        self.data.get(t).unwrap().size
+        match t {
+            Type::Reference { .. } => self.target.pointer_size,
+
+            _ => self.data.get(t).unwrap().size,
+        }
     }
 }
 
@@ -146,7 +187,7 @@ pub enum MemoryUsage {
 
     /// Aliases an existing variable -> use its allocation  
     /// e.g.: `let x: u32 = y;`
-    Alias(String),
+    Alias(BindingId),
 
     /// The variable is allocated elsewhere before being passed as a param  
     /// e.g.: `function(12, x);`
@@ -162,8 +203,8 @@ pub enum MemoryUsage {
 }
 
 pub struct AllocationTable {
-    // Map of ((function_name, variable name) -> variable's usage)
-    pub allocations: HashMap<(String, String), MemoryUsage>,
+    // Map of ((function_name, variable binding) -> variable's usage)
+    pub allocations: HashMap<(String, BindingId), MemoryUsage>,
 }
 
 impl AllocationTable {
@@ -173,17 +214,17 @@ impl AllocationTable {
         }
     }
 
-    pub fn insert(&mut self, function: String, variable: String, usage: MemoryUsage) -> Result<(), String> {
-        if let Some(_existing) = self.allocations.insert((function.clone(), variable.clone()), usage) {
-            return Err(format!("Variable {} is already defined in function {}", variable, function));
+    pub fn insert(&mut self, function: String, binding: BindingId, usage: MemoryUsage) -> ValidationResult<()> {
+        if let Some(_existing) = self.allocations.insert((function.clone(), binding), usage) {
+            return Err(validation_error!(DUPLICATE_VARIABLE, "Variable #{} is already allocated in function `{}`", binding.0, function));
         }
 
         Ok(())
     }
 
-    pub fn get_usage(&mut self, function: &str, variable: &str) -> &MemoryUsage {
+    pub fn get_usage(&mut self, function: &str, binding: BindingId) -> &MemoryUsage {
         // NOTE: This should always be valid
-        self.allocations.get(&(function.to_owned(), variable.to_owned())).expect("get_usage")
+        self.allocations.get(&(function.to_owned(), binding)).expect("get_usage")
     }
 }
 
@@ -194,17 +235,20 @@ struct VariableData {
     pub memory_usage: MemoryUsage,
     /// Is the variable mutable
     pub mutable: bool,
+    /// Has the variable been given a value (`let x;` is assigned later)
+    pub is_initialized: bool,
 }
 
 impl VariableData {
-    fn new(ty: Type, memory_usage: MemoryUsage, mutable: bool) -> Self {
-        Self { ty, memory_usage, mutable }
+    fn new(ty: Type, memory_usage: MemoryUsage, mutable: bool, is_initialized: bool) -> Self {
+        Self { ty, memory_usage, mutable, is_initialized }
     }
 }
 
 struct Scope {
-    /// **This scope's** map of (variable name -> data)
-    variables: HashMap<String, VariableData>,
+    /// **This scope's** map of (variable name -> binding).  
+    /// Shadowing within the scope replaces the name's binding.
+    variables: HashMap<String, BindingId>,
 }
 
 impl Scope {
@@ -213,116 +257,81 @@ impl Scope {
             variables: HashMap::new(),
         }
     }
-
-    fn get_var_data(&self, var: &str) -> &VariableData {
-        // NOTE: This operation should always succeed
-        self.variables.get(var).expect("get_var_data")
-    }
-
-    fn get_var_data_mut(&mut self, var: &str) -> &mut VariableData {
-        // NOTE: This operation should always succeed
-        self.variables.get_mut(var).expect("get_var_data_mut")
-    }
-
-    fn insert_var_data(&mut self, name: String, var: VariableData) {
-        // NOTE: This operation should never overwrite existing
-        self.variables.insert(name, var);
-    }
 }
 
+/// Resolves variable names to bindings using lexical scoping.  
 /// Uses alias analysis to determine stack slot allocations and struct return slot usage
 struct Scopes {
     /// Each element represents a subsequently nested scope
     scopes: Vec<Scope>,
-    /// Map of (variable name -> its scope)
-    all_variables: HashMap<String, usize>,
-    num_scopes: usize,
+    /// Every binding in the current function (indexed by `BindingId`)
+    bindings: Vec<VariableData>,
 }
 
 impl Scopes {
     fn new() -> Self {
         Self {
             scopes: Vec::new(),
-            all_variables: HashMap::new(),
-            num_scopes: 0,
+            bindings: Vec::new(),
         }
     }
 
     fn push_scope(&mut self) {
         self.scopes.push(Scope::new());
-        self.num_scopes += 1;
     }
 
-    fn pop_scope(&mut self) -> Scope {
-        // NOTE: These operations should always succeed
-        let removed_scope = self.scopes.pop().expect("pop_scope");
-        for key in removed_scope.variables.keys() {
-            self.all_variables.remove(key);
-        }
-
-        self.num_scopes -= 1;
-
-        removed_scope
+    fn pop_scope(&mut self) {
+        // NOTE: This operation should always succeed
+        self.scopes.pop().expect("pop_scope");
     }
 
-    fn current_index(&self) -> usize {
-        self.num_scopes - 1
+    fn current_scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("current_scope")
     }
 
-    fn current_scope(&mut self) -> &mut Scope {
-        let i = self.current_index();
-        &mut self.scopes[i]
+    /// Removes every binding of the function (once it has been validated), in `BindingId` order
+    fn take_bindings(&mut self) -> Vec<VariableData> {
+        std::mem::take(&mut self.bindings)
     }
 
     // TODO: Field aliasing
-    // TODO: Handle shadowing
-    fn add_var_to_scope(&mut self, name: String, mutable: bool, ty: Type, memory_usage: MemoryUsage) -> Result<(), String> {
-        // if name exists already
-        if let Some(scope_index) = self.all_variables.insert(name.clone(), self.current_index()) {
-            // Name exists in the current scope
-            if scope_index == self.current_index() {
-                return Err(format!("Variable `{}` is already defined in this scope", name));
-            } else {
-                // TODO: This
-                todo!("Nested scope shadowing")
-            }
-        }
-        
-        self.current_scope().insert_var_data(name, VariableData::new(ty, memory_usage, mutable));
+    /// Creates a new binding, shadowing any existing variable of the same name
+    fn add_var_to_scope(&mut self, name: String, mutable: bool, is_initialized: bool, ty: Type, memory_usage: MemoryUsage) -> BindingId {
+        let binding = BindingId(self.bindings.len() as u32);
+        self.bindings.push(VariableData::new(ty, memory_usage, mutable, is_initialized));
+        self.current_scope().variables.insert(name, binding);
 
-        Ok(())
+        binding
     }
 
-    // TODO: Handle shadowing
-    fn get_variable(&self, name: &str) -> Result<&VariableData, String> {
-        if let Some(&index) = self.all_variables.get(name) {
-            return Ok(self.scopes[index].get_var_data(name));
-        }
-
-        Err(format!("No variable `{}` in scope", name))
+    /// Finds the binding a name refers to, searching from the innermost scope outwards
+    fn lookup(&self, name: &str) -> ValidationResult<BindingId> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.variables.get(name).copied())
+            .ok_or_else(|| validation_error!(UNKNOWN_VARIABLE, "No variable `{}` in scope", name))
     }
 
-    fn get_variable_mut(&mut self, name: &str) -> Result<&mut VariableData, String> {
-        if let Some(&index) = self.all_variables.get(name) {
-            return Ok(self.scopes[index].get_var_data_mut(name));
-        }
+    fn get_variable(&self, binding: BindingId) -> &VariableData {
+        // NOTE: Bindings always come from `lookup` or `add_var_to_scope`
+        &self.bindings[binding.0 as usize]
+    }
 
-        Err(format!("No variable `{}` in scope", name))
+    fn get_variable_mut(&mut self, binding: BindingId) -> &mut VariableData {
+        &mut self.bindings[binding.0 as usize]
     }
 
     // NOTE: Program is valid at this point. No safety checks needed
     /// Uses aliases to convert the return variable's generic allocation to struct-return allocation
-    /// Target variable is always in the current scope.
-    fn signal_return_variable(&mut self, mut target: String) {
+    fn signal_return_variable(&mut self, mut target: BindingId) {
         let mut current;
 
         // Traverse the alias graph to find the true variable being returned.
         loop {
-            current = self.current_scope().get_var_data_mut(&target);
+            current = self.get_variable_mut(target);
             
             match &current.memory_usage {
                 // keep looking for root
-                MemoryUsage::Alias(next) => target = next.clone(),
+                MemoryUsage::Alias(next) => target = *next,
 
                 // TODO: I don't know if this is correct
                 // returning what was input -> use it instead of an allocation
@@ -367,9 +376,9 @@ impl FunctionTable {
     }
 
     // FIXME: A few copies and clones, but nothing bad
-    fn forward_declare_function(&mut self, validated_prototype: &ast::FunctionPrototype, is_extern: bool) -> Result<(), String> {
+    fn forward_declare_function(&mut self, validated_prototype: &ast::FunctionPrototype, is_extern: bool) -> ValidationResult<()> {
         if self.functions.contains_key(&validated_prototype.name) {
-            return Err(format!("Function `{}` already exists", validated_prototype.name));
+            return Err(validation_error!(DUPLICATE_FUNCTION, "Function `{}` already exists", validated_prototype.name));
         }
 
         let parameters = validated_prototype.parameters.iter().map(|param| {
@@ -388,19 +397,19 @@ impl FunctionTable {
         Ok(())
     }
 
-    fn __get_mut(&mut self, name: &str) -> Result<&mut FunctionDefinition, String> {
+    fn __get_mut(&mut self, name: &str) -> ValidationResult<&mut FunctionDefinition> {
         self.functions.get_mut(name)
-            .ok_or(format!("Could not find function `{}`", name))
+            .ok_or_else(|| validation_error!(UNKNOWN_FUNCTION, "Could not find function `{}`", name))
     }
 
-    fn __get(&self, name: &str) -> Result<&FunctionDefinition, String> {
+    fn __get(&self, name: &str) -> ValidationResult<&FunctionDefinition> {
         self.functions.get(name)
-            .ok_or(format!("Could not find function `{}`", name))
+            .ok_or_else(|| validation_error!(UNKNOWN_FUNCTION, "Could not find function `{}`", name))
     }
 
     // TODO: This and `get_validated_function_definition` may not ever be used
     //       (this functionality exists in finalized JIT product)
-    fn mark_function_validated(&mut self, name: &str) -> Result<(), String> {
+    fn mark_function_validated(&mut self, name: &str) -> ValidationResult<()> {
         self.__get_mut(name)?
             .is_validated = true;
         Ok(())
@@ -420,7 +429,7 @@ impl FunctionTable {
 
     /// Returns a `FunctionDefinition` that is not guarenteed to have been
     /// successfully validated
-    fn get_unchecked_function_definition(&mut self, name: &str) -> Result<&FunctionDefinition, String> {
+    fn get_unchecked_function_definition(&mut self, name: &str) -> ValidationResult<&FunctionDefinition> {
         self.__get(name)
     }
 }
\ No newline at end of file
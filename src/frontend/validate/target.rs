@@ -0,0 +1,58 @@
+// Layout-relevant properties of the target being compiled for.
+//
+// Sizes and alignments match what Rust uses on the same target, so that structs
+// can be shared with Rust code (as `#[repr(C)]` structs) on 32-bit targets too.
+
+use target_lexicon::{Architecture, OperatingSystem, Triple};
+
+/// Describes how the target lays out the types whose size depends on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetInfo {
+    /// Size & alignment of pointers (references, `usize`, and `isize`) in bytes
+    pub pointer_size: usize,
+    /// Alignment of `u64`, `i64`, and `f64` in bytes
+    pub u64_alignment: usize,
+    /// Alignment of `u128` and `i128` in bytes
+    pub i128_alignment: usize,
+}
+
+impl TargetInfo {
+    /// The machine which Jitter is running on
+    pub fn host() -> Self {
+        Self::from_triple(&Triple::host())
+    }
+
+    pub fn from_triple(triple: &Triple) -> Self {
+        // FIXME: Unknown architectures are assumed to be 64-bit
+        let pointer_size = triple.pointer_width()
+            .map_or(8, |width| width.bytes() as usize);
+
+        // The System V i386 ABI only aligns 64-bit scalars to 4 bytes
+        let u64_alignment = match triple.architecture {
+            Architecture::X86_32(_) if triple.operating_system != OperatingSystem::Windows => 4,
+            _ => 8,
+        };
+
+        // 128-bit integers are 16-byte aligned wherever LLVM (and therefore Rust) does so.
+        // Everywhere else they align the same as 64-bit integers.
+        let i128_alignment = match triple.architecture {
+            Architecture::X86_64
+            | Architecture::X86_32(_)
+            | Architecture::Aarch64(_)
+            | Architecture::Riscv32(_)
+            | Architecture::Riscv64(_)
+            | Architecture::Powerpc64
+            | Architecture::Powerpc64le
+            | Architecture::Wasm32
+            | Architecture::Wasm64 => 16,
+
+            _ => u64_alignment,
+        };
+
+        Self {
+            pointer_size,
+            u64_alignment,
+            i128_alignment,
+        }
+    }
+}
@@ -2,6 +2,8 @@
 
 use cranelift::codegen::ir::types as cranelift_types;
 
+use super::target::TargetInfo;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[allow(non_camel_case_types)]
 pub enum Type {
@@ -74,10 +76,38 @@ pub enum Type {
     /// Name of a struct, enum, alias, etc.
     User(String),
 
+    /// Type of an unsuffixed integer literal, as parsed.  
+    /// Validation replaces this with an integer `Variable` (which defaults to `i32`)
+    IntegerVariable,
+    /// Type of an unsuffixed floating-point literal, as parsed.  
+    /// Validation replaces this with a float `Variable` (which defaults to `f64`)
+    FloatVariable,
+
+    /// Type being inferred (see `validate::infer`)
+    Variable(TypeVariable),
+
     /// Unspecified and uninferred type
     Unknown,
 }
 
+/// Placeholder for a type which is not known yet
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TypeVariable {
+    pub id: u32,
+    pub kind: TypeVariableKind,
+}
+
+/// Restricts what a `TypeVariable` may be unified with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TypeVariableKind {
+    /// Any type
+    General,
+    /// Integer types only (unsuffixed integer literals)
+    Integer,
+    /// Floating-point types only (unsuffixed float literals)
+    Float,
+}
+
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let string = match self {
@@ -118,6 +148,13 @@ impl std::fmt::Display for Type {
                 string
             },
             Type::User(t) => t.clone(),
+            Type::IntegerVariable => "{integer}".to_owned(),
+            Type::FloatVariable => "{float}".to_owned(),
+            Type::Variable(variable) => match variable.kind {
+                TypeVariableKind::General => "_".to_owned(),
+                TypeVariableKind::Integer => "{integer}".to_owned(),
+                TypeVariableKind::Float => "{float}".to_owned(),
+            },
             Type::Unknown => "!Unknown!".to_owned(),
         };
 
@@ -185,6 +222,11 @@ impl Type {
             Type::Tuple(_) => cranelift_types::INVALID,
             // Type::User(_) => cranelift_types::INVALID,
 
+            // Always resolved before codegen
+            Type::IntegerVariable
+            | Type::FloatVariable
+            | Type::Variable(_) => cranelift_types::INVALID,
+
             Type::Unknown => cranelift_types::INVALID,
         }
     }
@@ -193,6 +235,36 @@ impl Type {
         self == &Type::Unknown
     }
 
+    /// Whether this type is still being inferred
+    pub fn is_inference_variable(&self) -> bool {
+        matches!(self, Type::IntegerVariable | Type::FloatVariable | Type::Variable(_))
+    }
+
+    /// Inclusive range of values an integer type can hold
+    pub fn integer_range(&self, target: &TargetInfo) -> Option<(i128, i128)> {
+        let pointer_bits = target.pointer_size as u32 * 8;
+
+        let range = match self {
+            Type::u8 => (0, u8::MAX as i128),
+            Type::u16 => (0, u16::MAX as i128),
+            Type::u32 => (0, u32::MAX as i128),
+            Type::u64 => (0, u64::MAX as i128),
+            // Literals are never larger than `i128::MAX`
+            Type::u128 => (0, i128::MAX),
+            Type::i8 => (i8::MIN as i128, i8::MAX as i128),
+            Type::i16 => (i16::MIN as i128, i16::MAX as i128),
+            Type::i32 => (i32::MIN as i128, i32::MAX as i128),
+            Type::i64 => (i64::MIN as i128, i64::MAX as i128),
+            Type::i128 => (i128::MIN, i128::MAX),
+            Type::usize => (0, (1 << pointer_bits) - 1),
+            Type::isize => (-(1 << (pointer_bits - 1)), (1 << (pointer_bits - 1)) - 1),
+
+            _ => return None,
+        };
+
+        Some(range)
+    }
+
     pub fn is_unit(&self) -> bool {
         self == &Type::Unit
     }
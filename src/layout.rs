@@ -0,0 +1,77 @@
+// Struct layouts shared between Rust and Jitter.
+//
+// Jitter structs are passed to and from Rust by pointer, so both sides must agree on
+// where every field lives. Rather than relying on that being true, a Rust struct
+// declared with `JitterLayout!` can describe its layout, which is then compared with
+// the layout Jitter computed for the struct of the same name.
+
+/// Memory layout of a struct (from either Rust or Jitter)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub name: String,
+    /// Size in bytes (including trailing padding)
+    pub size: usize,
+    /// Alignment in bytes
+    pub alignment: usize,
+    /// Fields in declaration order
+    pub fields: Vec<FieldLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: String,
+    /// Byte offset from the start of the struct
+    pub offset: usize,
+    /// Type as written in source (`u32`, `&Point`, ..)
+    pub ty: String,
+}
+
+impl StructLayout {
+    /// Compares a Jitter layout (`self`) with a Rust one, describing each difference
+    pub fn compare(&self, rust: &StructLayout) -> Result<(), String> {
+        let mut differences = Vec::new();
+
+        if self.size != rust.size {
+            differences.push(format!("size is {} bytes in Jitter but {} bytes in Rust", self.size, rust.size));
+        }
+        if self.alignment != rust.alignment {
+            differences.push(format!("alignment is {} bytes in Jitter but {} bytes in Rust", self.alignment, rust.alignment));
+        }
+
+        for index in 0..std::cmp::max(self.fields.len(), rust.fields.len()) {
+            match (self.fields.get(index), rust.fields.get(index)) {
+                (Some(jitter), Some(rust)) => {
+                    if jitter.name != rust.name {
+                        differences.push(format!("field #{} is `{}` in Jitter but `{}` in Rust", index, jitter.name, rust.name));
+                    } else if jitter.offset != rust.offset {
+                        differences.push(format!("field `{}` is at offset {} in Jitter but {} in Rust", jitter.name, jitter.offset, rust.offset));
+                    }
+
+                    if !same_type(&jitter.ty, &rust.ty) {
+                        differences.push(format!("field `{}` has type `{}` in Jitter but `{}` in Rust", jitter.name, jitter.ty, rust.ty));
+                    }
+                }
+
+                (Some(jitter), None) => differences.push(format!("field `{}` only exists in Jitter", jitter.name)),
+                (None, Some(rust)) => differences.push(format!("field `{}` only exists in Rust", rust.name)),
+                (None, None) => unreachable!(),
+            }
+        }
+
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Layout of struct `{}` differs between Jitter and Rust:\n  - {}", self.name, differences.join("\n  - ")))
+        }
+    }
+}
+
+/// Types are compared as written, ignoring whitespace (`& mut T` == `&mut T`)
+fn same_type(a: &str, b: &str) -> bool {
+    a.split_whitespace().collect::<String>() == b.split_whitespace().collect::<String>()
+}
+
+/// Rust types which can describe their layout for comparison with Jitter (see `JitterLayout!`)
+pub trait JitterLayout {
+    fn layout() -> StructLayout;
+}
@@ -2,46 +2,49 @@ pub mod frontend;
 pub mod backend;
 pub mod macros;
 pub mod extension;
+pub mod diagnostic;
+pub mod layout;
+pub mod source_map;
 
 /// Includes essential types and convenience macros
 pub mod prelude {
-    pub use crate::{Return, Jitter, GetFunction, GetFunctions};
+    pub use crate::{Jitter, GetFunction, GetFunctions, JitterLayout};
+    pub use crate::layout::JitterLayout;
+    pub use crate::backend::function::{JitterFn, JitterType};
     pub use crate::frontend::LexerCallback;
     pub use crate::backend::jit::{JitterContextBuilder, JitterContext};
+    pub use crate::backend::object::JitterObjectBuilder;
+    pub use crate::backend::options::{CompileOptions, OptLevel};
+    pub use crate::backend::trap::{JitterTrap, TrapKind};
 }
 
-/// Wrapper around a stack-allocated Jitter-returned type.
-///
-/// Note that the underlying type **must** be `#[repr(C)]` for non-primitive types.  
-/// Similarly, the Rust and Jitter layouts must be the same (i.e.: fields in same order).
-#[repr(transparent)]
-pub struct Return<T> {
-    value: *mut T,
-}
-
-impl<T> Return<T> {
-    /// Converts Jitter return value to underlying type
-    pub fn into(self) -> T {
-        // NOTE: Dereferencing a raw pointer drops it immediately. This is the only way
-        //       I have been able to get the proper data (zeroed out otherwise)
-        unsafe { std::ptr::read(self.value) }
-    }
-}
-
-
 // TODO: make `pub(crate)`
 /// Token/AST spans
 #[derive(Copy, Clone)]
 pub struct Span {
+    /// File containing the span (see `SourceMap`)
+    pub file: source_map::FileId,
     pub start_line: usize,
     pub start_column: usize,
     pub end_line: usize,
     pub end_column: usize,
+    /// Byte offsets into the file (`start..end`)
+    pub start: usize,
+    pub end: usize,
+    /// Set when the spanned code was produced by a `#define`, lexer callback, or extension.
+    /// The line/column information then refers to the call site.
+    pub expansion: Option<source_map::ExpansionId>,
 }
 
+// `#file_id:line:column` (the file's path is only known to the `SourceMap`)
 impl std::fmt::Debug for Span {
-    // FIXME: Make printing spans optional
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}:{}:{}", self.file.0, self.start_line, self.start_column)?;
+
+        if self.expansion.is_some() {
+            write!(f, " (expanded)")?;
+        }
+
         Ok(())
     }
 }
@@ -49,17 +52,46 @@ impl std::fmt::Debug for Span {
 impl Span {
     pub fn new(start_line: usize, start_column: usize, end_line: usize, end_column: usize) -> Self {
         Self {
+            file: source_map::FileId::default(),
             start_line, 
             start_column,
             end_line,
             end_column,
+            start: 0,
+            end: 0,
+            expansion: None,
         }
     }
 
+    /// Span for code which has no source location (such as nodes created by extensions)
+    pub fn generated() -> Self {
+        Self::new(0, 0, 0, 0)
+    }
+
+    /// Lines are 1-based, so line 0 only exists for generated code
+    pub fn is_generated(&self) -> bool {
+        self.start_line == 0
+    }
+
     /// Extends the span to start at `self` and end at `other`
     pub fn extend(mut self, other: Span) -> Span {
         self.end_line = other.end_line;
         self.end_column = other.end_column;
+        self.end = other.end;
+        self
+    }
+
+    /// Empty span located at the end of `self` (for insertions)
+    pub fn end_point(mut self) -> Span {
+        self.start_line = self.end_line;
+        self.start_column = self.end_column;
+        self.start = self.end;
+        self
+    }
+
+    /// Marks the span as produced by an expansion
+    pub fn expanded(mut self, expansion: source_map::ExpansionId) -> Span {
+        self.expansion = Some(expansion);
         self
     }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@
 ///
 /// If no functions need to be linked, simply omit the `<- [...]` section.
 /// If no lexer replacements are needed, omit the `where [...]` section.
+/// A `layouts <- [...]` section checks Rust structs declared with `JitterLayout!` against Jitter's.
 ///
 /// Usage:
 /// ```
@@ -37,6 +38,10 @@ macro_rules! Jitter {
         $(
             extensions <- [$extension_path:expr]
         )?
+        // Optional Rust structs to check against Jitter's (see `JitterLayout!`)
+        $(
+            layouts <- [ $($layout:ty),+    $(,)? ]
+        )?
         // Optional lexer callbacks
         $(
             where [ $($input:expr => $output:expr),+    $(,)? ]
@@ -59,6 +64,12 @@ macro_rules! Jitter {
         $(
             .with_extension_path($extension_path)
         )?
+        // Layout group
+        $(
+            $(
+                .with_layout::<$layout>()
+            )+
+        )?
         // Lexer group
         $(
             $(
@@ -74,47 +85,33 @@ macro_rules! Jitter {
     };
 }
 
-/// Get a function pointer from a Jitter context without worrying about FFI details.
+/// Get a function from a Jitter context without worrying about FFI details.
+/// Panics if the function's signature differs from the one given.
 /// 
 /// Usage:
-/// ```
+/// ```ignore
 /// let jitter: JitterContext = ...;
 ///
 /// let jitter_fn = GetFunction! {
 ///     jitter::function as fn(param, types) -> return_type
 /// };
+///
+/// let result: return_type = jitter_fn.call((param, types));
 /// ```  
 /// The macro will expand to the following code:
-/// ```
-/// let jitter_fn: fn(&param, &types) -> Return<return_type> = unsafe {
-///     std::mem::transmute(jitter.get_fn("function"))
-/// };
+/// ```ignore
+/// let jitter_fn: JitterFn<(param, types), return_type> = jitter
+///     .get_typed_fn("function")
+///     .expect("function signature");
 /// ```
 #[macro_export]
 macro_rules! GetFunction {
     // context::function as fn(ty1, ty2, ..) -> type
     ($context:ident :: $function:ident as fn($($param:ty),*) $(-> $ret:ty)?) => {
-        unsafe {
-            std::mem::transmute::<
-                _,
-                fn(
-                    $(
-                        &$param,
-                    )*
-                ) $(
-                    -> Return<$ret>
-                )?
-            >
-            ($context.get_fn(stringify!($function)))
-        }
-
-        // TODO: Could wrap the above in a closure like so:
-        //       Requires naming the closure inputs.
-        //       This would need a proc macro, but eliminates FFI types.
-        
-        // |$(p1: $param,)*| $(-> $ret)? {
-        //     func(&p1, ...).into()
-        // }
+        // NOTE: `($ret)` is just `$ret`, while a missing return type gives `()`
+        $context
+            .get_typed_fn::<($($param,)*), ($($ret)?)>(stringify!($function))
+            .unwrap_or_else(|error| panic!("{}", error))
     };
 }
 
@@ -147,6 +144,76 @@ macro_rules! GetFunctions {
 }
 
 
+/// Declares a `#[repr(C)]` Rust struct which can be checked against the Jitter struct of the same name.
+/// The struct can also be used in the signatures given to `JitterContext::get_typed_fn`.
+///
+/// Usage:
+/// ```ignore
+/// JitterLayout! {
+///     #[repr(C)]
+///     #[derive(Debug)]
+///     pub struct Point {
+///         pub x: f32,
+///         pub y: f32,
+///     }
+/// }
+///
+/// let jitter = JitterContextBuilder::new()
+///     .with_source_path("./path/file.jitter")
+///     .with_layout::<Point>()
+///     .build()?; // Errors if `Point` is laid out differently in Jitter
+/// ```
+#[macro_export]
+macro_rules! JitterLayout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::layout::JitterLayout for $name {
+            fn layout() -> $crate::layout::StructLayout {
+                $crate::layout::StructLayout {
+                    name: stringify!($name).to_owned(),
+                    size: std::mem::size_of::<$name>(),
+                    alignment: std::mem::align_of::<$name>(),
+                    fields: vec![
+                        $(
+                            $crate::layout::FieldLayout {
+                                name: stringify!($field).to_owned(),
+                                offset: std::mem::offset_of!($name, $field),
+                                ty: stringify!($ty).to_owned(),
+                            },
+                        )*
+                    ],
+                }
+            }
+        }
+
+        impl $crate::backend::function::JitterType for $name {
+            fn jitter_type() -> $crate::frontend::validate::types::Type {
+                $crate::frontend::validate::types::Type::User(stringify!($name).to_owned())
+            }
+
+            fn layout() -> Option<$crate::layout::StructLayout> {
+                Some(<$name as $crate::layout::JitterLayout>::layout())
+            }
+        }
+    };
+}
+
+
 /// Convenience macro for optional printing
 pub(crate) mod log {
     #[cfg(not(feature = "benchmark"))]
@@ -0,0 +1,121 @@
+use crate::Span;
+
+
+/// Identifies a file within a `SourceMap`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FileId(pub u32);
+
+/// Identifies an expansion within a `SourceMap`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ExpansionId(pub u32);
+
+pub struct SourceFile {
+    pub path: String,
+    pub source: String,
+    /// Location of the `#include` which brought in this file (if any)
+    pub included_from: Option<Span>,
+}
+
+/// Describes what produced an expansion
+#[derive(Debug, Clone)]
+pub enum ExpansionKind {
+    /// `#define name ...` (`definition` is the span of `name` in the directive)
+    Define {
+        name: String,
+        definition: Span,
+    },
+    /// A `LexerCallback` given to the `JitterContextBuilder`
+    Callback {
+        pattern: String,
+        replacement: String,
+    },
+    /// `@directive(..)` on a top-level item
+    Extension {
+        directive: String,
+    },
+}
+
+/// Code which was not written where it appears.
+/// Expanded spans keep the location of their `call_site`, so this is only needed
+/// to explain *why* code exists.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub kind: ExpansionKind,
+    /// The code which was replaced (may itself be expanded)
+    pub call_site: Span,
+}
+
+impl Expansion {
+    /// Short description for diagnostics
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            ExpansionKind::Define { name, .. } => format!("expanded from `#define {}`", name),
+            ExpansionKind::Callback { pattern, replacement } => {
+                format!("expanded from lexer callback `{}` => `{}`", pattern, replacement)
+            }
+            ExpansionKind::Extension { directive } => format!("generated by extension `@{}`", directive),
+        }
+    }
+}
+
+/// Owns every source file involved in a compilation along with the
+/// expansions applied to them. `Span`s index into this.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    expansions: Vec<Expansion>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, path: String, source: String, included_from: Option<Span>) -> FileId {
+        self.files.push(SourceFile {
+            path,
+            source,
+            included_from,
+        });
+
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+
+    /// Path of the file, or `"<unknown>"` for spans not belonging to this map
+    pub fn path(&self, id: FileId) -> &str {
+        self.file(id).map(|file| file.path.as_str()).unwrap_or("<unknown>")
+    }
+
+    /// Returns the text of a 1-based line (without the line ending)
+    pub fn line(&self, id: FileId, line: usize) -> Option<&str> {
+        let file = self.file(id)?;
+        file.source.lines().nth(line.checked_sub(1)?)
+    }
+
+    pub fn add_expansion(&mut self, expansion: Expansion) -> ExpansionId {
+        self.expansions.push(expansion);
+        ExpansionId(self.expansions.len() as u32 - 1)
+    }
+
+    pub fn expansion(&self, id: ExpansionId) -> &Expansion {
+        &self.expansions[id.0 as usize]
+    }
+
+    /// Returns every expansion that led to `span`, innermost first
+    pub fn expansion_chain(&self, span: Span) -> Vec<&Expansion> {
+        let mut chain = Vec::new();
+
+        let mut current = span.expansion;
+        while let Some(id) = current {
+            let expansion = self.expansion(id);
+            chain.push(expansion);
+            current = expansion.call_site.expansion;
+        }
+
+        chain
+    }
+}
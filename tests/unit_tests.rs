@@ -4,9 +4,59 @@ mod tests {
         frontend::lex::*,
         frontend::parse::*,
         frontend::validate::types::Type,
+        diagnostic::{Diagnostic, codes},
+        frontend::validate::context::Context,
+        backend::jit::{JitterContext, JitterContextBuilder},
+        source_map::SourceMap,
+        JitterLayout,
         Span,
     };
 
+    // Shared by the layout and typed function tests
+    JitterLayout! {
+        #[allow(dead_code)]
+        #[repr(C, packed)]
+        struct Packed {
+            a: u8,
+            b: u64,
+            c: u32,
+        }
+    }
+
+    JitterLayout! {
+        #[allow(dead_code)]
+        #[repr(C, align(16))]
+        struct Aligned {
+            a: u8,
+            b: u32,
+        }
+    }
+
+    /// Parses and validates `input` (which must be free of syntax errors) with `context`
+    fn validate_with(mut context: Context, path: &str, input: &str) -> Result<Context, Diagnostic> {
+        let parser = Parser::new(path, Lexer::lex_str(path.to_owned(), input.to_owned(), true));
+        let ast = parser.parse_ast("test".to_owned());
+        assert!(!parser.has_errors(), "{:?}", parser.errors());
+
+        context.validate(ast).map(|_| context)
+    }
+
+    fn validate(path: &str, input: &str) -> Result<Context, Diagnostic> {
+        validate_with(Context::new(), path, input)
+    }
+
+    fn validate_file(path: &str) -> Result<Context, Diagnostic> {
+        validate(path, &std::fs::read_to_string(path).unwrap())
+    }
+
+    /// JIT-compiles a source file with the default options
+    fn build(path: &str) -> JitterContext {
+        JitterContextBuilder::new()
+            .with_source_path(path)
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn nodes() {
         use ast::*;
@@ -36,6 +86,1100 @@ mod tests {
 
         let parser = Parser::new(path, tokens);
         parser.parse_ast("parser_test".to_owned());
+        assert!(!parser.has_errors(), "{}", parser.errors().join("\n"));
+    }
+
+    #[test]
+    fn parser_recovery() {
+        let path = "./tests/recovery_test.jitter";
+        let input = std::fs::read_to_string(path).unwrap();
+
+        let tokens = Lexer::lex_str(path.to_owned(), input, true);
+
+        let parser = Parser::new(path, tokens);
+        let ast = parser.parse_ast("recovery_test".to_owned());
+
+        // Every error is reported, not just the first
+        let errors = parser.errors();
+        assert_eq!(errors.len(), 4, "{}", errors.join("\n"));
+        assert!(errors[0].contains(":11:"));
+        assert!(errors[1].contains(":15:"));
+        assert!(errors[2].contains(":24:"));
+        assert!(errors[3].contains(":31:"));
+
+        // Items surrounding the errors are still parsed
+        let function_names = ast.functions.iter()
+            .map(|f| f.prototype.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(function_names, vec!["missing_semicolon", "bad_let", "still_parsed", "unclosed_block", "after_unclosed"]);
+        assert!(ast.functions[0].is_poisoned());
+        assert!(!ast.functions[2].is_poisoned());
+        assert_eq!(ast.structs.len(), 1);
+
+        // Validation skips the poisoned statement, but still checks those after it
+        let path = "skipped.jitter";
+        let input = "fn test() {\n    let = 3;\n    let y: u8 = 1_u32;\n}\n".to_owned();
+        let parser = Parser::new(path, Lexer::lex_str(path.to_owned(), input, true));
+        let ast = parser.parse_ast("skipped".to_owned());
+        assert_eq!(parser.errors().len(), 1);
+
+        let error = Context::new().validate(ast).unwrap_err();
+        assert_eq!(error.code, Some(codes::MISMATCHED_TYPES));
+        assert_eq!(error.primary_span().map(|span| span.start_line), Some(3));
+    }
+
+    #[test]
+    fn diagnostic_rendering() {
+        let mut sources = SourceMap::new();
+        sources.add_file("test.jitter".to_owned(), "fn test() -> i32 {\n    let x: i32 = 1\n    x\n}\n".to_owned(), None);
+
+        let diagnostic = Diagnostic::error("Expected `;` to terminate a statement. Found `x`".to_owned())
+            .with_label(Span::new(3, 5, 3, 6), "expected `;` before this".to_owned())
+            .with_secondary_label(Span::new(2, 5, 2, 8), "statement starts here".to_owned())
+            .with_note("statements must end with `;`".to_owned());
+
+        let expected = "\
+error: Expected `;` to terminate a statement. Found `x`
+ --> test.jitter:3:5
+  |
+2 |     let x: i32 = 1
+  |     --- statement starts here
+3 |     x
+  |     ^ expected `;` before this
+  |
+  = note: statements must end with `;`
+";
+        assert_eq!(diagnostic.render(&sources, false), expected);
+
+        // Colour only adds escape codes
+        let colored = diagnostic.render(&sources, true);
+        assert!(colored.contains("\x1b["));
+    }
+
+    #[test]
+    fn source_map() {
+        let path = "./tests/source_map_test.jitter";
+        let input = std::fs::read_to_string(path).unwrap();
+
+        let mut lexer = Lexer::new(path.to_owned(), input, true);
+        let tokens = lexer.lex();
+
+        let mut parser = Parser::new(path, tokens);
+        parser.set_source_map(lexer.take_source_map());
+        parser.parse_ast("source_map_test".to_owned());
+
+        let sources = parser.take_source_map();
+        let errors = parser.diagnostics();
+        assert_eq!(errors.len(), 2);
+
+        // Error within the included file
+        let span = errors[0].primary_span().unwrap();
+        let file = sources.file(span.file).unwrap();
+        assert!(file.path.ends_with("source_map_include.jitter"));
+        assert_eq!((span.start_line, span.start_column), (4, 1));
+        assert_eq!(&file.source[span.start..span.end], "}");
+
+        let rendered = errors[0].render(&sources, false);
+        assert!(rendered.contains("source_map_include.jitter:4:1"));
+        assert!(rendered.contains("is included from ./tests/source_map_test.jitter:6:"));
+
+        // Error within a `#define` expansion (located at its use)
+        let span = errors[1].primary_span().unwrap();
+        assert_eq!(sources.path(span.file), path);
+        assert_eq!(span.start_line, 9);
+        assert_eq!(sources.expansion_chain(span).len(), 1);
+        assert!(format!("{:?}", span).starts_with(&format!("#{}:9:", span.file.0)));
+
+        let rendered = errors[1].render(&sources, false);
+        assert!(rendered.contains("expanded from `#define BAD_LET` (defined at ./tests/source_map_test.jitter:4:9)"));
+    }
+
+    #[test]
+    fn json_diagnostics() {
+        let path = "json_test.jitter";
+        let input = "fn test() -> i32 {\n    let x: i32 = 1_u8;\n    x\n}\n".to_owned();
+
+        let mut lexer = Lexer::new(path.to_owned(), input, true);
+        let tokens = lexer.lex();
+
+        let mut parser = Parser::new(path, tokens);
+        parser.set_source_map(lexer.take_source_map());
+        let ast = parser.parse_ast("json_test".to_owned());
+        let sources = parser.take_source_map();
+
+        let error = Context::new().validate(ast).unwrap_err();
+        assert_eq!(error.code, Some(codes::MISMATCHED_TYPES));
+
+        let json = error.to_json(&sources);
+        assert!(json.starts_with(r#"{"file":"json_test.jitter","start":36,"end":40,"line":2,"column":18,"end_line":2,"end_column":22,"severity":"error","code":"E0010","message":"Variable `x` has type `i32`, but is assigned the type `u8`""#), "{}", json);
+        assert!(!json.contains('\n'));
+
+        // Syntax errors suggest fixes
+        let path = "./tests/recovery_test.jitter";
+        let input = std::fs::read_to_string(path).unwrap();
+        let mut lexer = Lexer::new(path.to_owned(), input, true);
+        let mut parser = Parser::new(path, lexer.lex());
+        parser.set_source_map(lexer.take_source_map());
+        parser.parse_ast("recovery_test".to_owned());
+
+        let json = parser.diagnostics()[0].to_json(&parser.take_source_map());
+        assert!(json.contains(r#""code":"E0100""#));
+        assert!(json.contains(r#""suggestions":[{"file":"./tests/recovery_test.jitter","#), "{}", json);
+        assert!(json.contains(r#""line":10,"column":19,"end_line":10,"end_column":19,"replacement":";""#), "{}", json);
+    }
+
+    #[test]
+    fn literal_inference() {
+        let context = validate_file("./tests/literal_inference_test.jitter").unwrap();
+
+        let type_of = |function: usize, statement: usize| {
+            match &context.ast.functions[function].body.block.item[statement].item {
+                ast::Statement::Let { ty, .. } => ty.clone(),
+                ast::Statement::Return { expression }
+                | ast::Statement::ImplicitReturn { expression, .. } => expression.get_type().clone(),
+                _ => unreachable!(),
+            }
+        };
+
+        assert_eq!(type_of(0, 0), Type::u8);
+        assert_eq!(type_of(1, 0), Type::i64);
+        assert_eq!(type_of(2, 0), Type::i32);
+        assert_eq!(type_of(2, 1), Type::f64);
+        assert_eq!(type_of(2, 2), Type::f32);
+        assert_eq!(type_of(2, 3), Type::u8);
+
+        // Literals must fit their inferred type
+        let error = validate("range.jitter", "fn test() -> u8 {\n    255 + 256\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::LITERAL_OUT_OF_RANGE));
+        assert_eq!(error.message, "Literal `256` does not fit in type `u8` (range is `0..=255`)");
+        assert_eq!(error.primary_span().map(|span| span.start_column), Some(11));
+
+        let error = validate("range.jitter", "fn test() {\n    let x: u32 = -1;\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::LITERAL_OUT_OF_RANGE));
+
+        // `{integer}` never becomes a float
+        let error = validate("mismatch.jitter", "fn test() {\n    let x: f32 = 1;\n}\n").err().unwrap();
+        assert_eq!(error.message, "Variable `x` has type `f32`, but is assigned the type `{integer}`");
+    }
+
+    #[test]
+    fn type_inference() {
+        let context = validate_file("./tests/type_inference_test.jitter").unwrap();
+
+        let statements = &context.ast.functions[2].body.block.item;
+        let let_type = |statement: usize| match &statements[statement].item {
+            ast::Statement::Let { ty, .. } => ty.clone(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(let_type(0), Type::u16);
+        assert_eq!(let_type(2), Type::u8);
+        assert_eq!(let_type(4), Type::u64);
+
+        // The block and everything in it are filled in too
+        match &statements[4].item {
+            ast::Statement::Let { value: Some(value), .. } => match &value.item {
+                ast::Expression::Block(block) => {
+                    assert_eq!(block.ty, Type::u64);
+                    match &block.block.item[0].item {
+                        ast::Statement::Let { ty, .. } => assert_eq!(ty, &Type::u64),
+                        _ => unreachable!(),
+                    }
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+
+        // Nothing determines `x`
+        let error = validate("undecided.jitter", "fn test() {\n    let x;\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::TYPE_ANNOTATIONS_NEEDED));
+        assert_eq!(error.message, "Type annotations needed for `x`");
+
+        // Conflicting uses
+        let error = validate("conflict.jitter", "fn takes_u8(x: u8) {}\nfn test() {\n    let x = 1.5;\n    takes_u8(x);\n}\n").err().unwrap();
+        assert_eq!(error.message, "Parameter #0 (`x`) of call to `takes_u8` has type `u8`, but found type `{float}`");
+
+        // Operators point at both operands
+        let operand_lines = |error: Diagnostic| error.labels.iter()
+            .map(|label| (label.span.start_column, label.is_primary))
+            .collect::<Vec<_>>();
+
+        let error = validate("operands.jitter", "fn test(a: u8, b: u16) {\n    a + b;\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::MISMATCHED_TYPES));
+        assert_eq!(operand_lines(error), vec![(7, true), (5, false), (9, false)]);
+
+        let error = validate("operands.jitter", "fn test(a: bool, b: bool) {\n    a * b;\n}\n").err().unwrap();
+        assert_eq!(error.message, "Binary operator `*` cannot be applied to type `bool`");
+        assert_eq!(operand_lines(error), vec![(7, true), (5, false), (9, false)]);
+
+        let error = validate("operands.jitter", "fn test(a: u8) {\n    -a;\n}\n").err().unwrap();
+        assert_eq!(error.message, "Unary operator `-` cannot be applied to type `u8`");
+
+        let error = validate("operands.jitter", "fn test(a: u8) {\n    !a;\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::MISMATCHED_TYPES));
+    }
+
+    #[test]
+    fn shadowing() {
+        let context = validate_file("./tests/shadowing_test.jitter").unwrap();
+
+        let function = &context.ast.functions[0];
+        let parameter = function.prototype.parameters[0].binding;
+        let statements = &function.body.block.item;
+
+        let let_binding = |statements: &[ast::Node<ast::Statement>], i: usize| match &statements[i].item {
+            ast::Statement::Let { binding, .. } => binding.unwrap(),
+            _ => unreachable!(),
+        };
+        let ident_binding = |expression: &ast::Node<ast::Expression>| match &expression.item {
+            ast::Expression::Ident { binding, .. } => binding.unwrap(),
+            _ => unreachable!(),
+        };
+        let let_value = |statement: &ast::Node<ast::Statement>| match &statement.item {
+            ast::Statement::Let { value: Some(value), .. } => value.clone(),
+            _ => unreachable!(),
+        };
+
+        // `let x = x;` reads the parameter
+        assert_eq!(Some(ident_binding(&let_value(&statements[0]))), parameter);
+
+        // The block's `x` is distinct from both outer `x`s
+        let block = match let_value(&statements[2]).item {
+            ast::Expression::Block(block) => block,
+            _ => unreachable!(),
+        };
+        let inner = let_binding(&block.block.item, 0);
+        assert_ne!(inner, let_binding(statements, 1));
+        match &block.block.item[1].item {
+            ast::Statement::ImplicitReturn { expression, .. } => assert_eq!(ident_binding(expression), inner),
+            _ => unreachable!(),
+        }
+
+        // After the block, `x` is the `u16` again
+        assert_eq!(ident_binding(&let_value(&statements[3])), let_binding(statements, 1));
+
+        // Every binding (including shadowed ones) gets its own allocation
+        assert_eq!(context.allocations.allocations.len(), 6);
+
+        // Block-local variables are not visible after the block
+        let error = validate("scope.jitter", "fn test() -> u8 {\n    let y = {\n        let z = 1u8;\n        z\n    };\n    z\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::UNKNOWN_VARIABLE));
+        assert_eq!(error.primary_span().map(|span| span.start_line), Some(6));
+    }
+
+    #[test]
+    fn flow_analysis() {
+        let validate = |input: &str| validate("flow.jitter", input);
+
+        // Read before being given a value
+        let error = validate("fn test() -> i32 {\n    let x: i32;\n    x\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::UNINITIALIZED_VARIABLE));
+        let lines = error.labels.iter().map(|label| (label.span.start_line, label.is_primary)).collect::<Vec<_>>();
+        assert_eq!(lines, vec![(3, true), (2, false)]);
+
+        // Assigned first
+        assert!(validate("fn test() -> i32 {\n    let x: i32;\n    x = 1;\n    x\n}\n").is_ok());
+
+        // Falls off the end
+        let error = validate("fn test() -> i32 {\n    let x = 1;\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::MISSING_RETURN));
+
+        // A `return` within a block leaves the function too, and anything after it is unreachable
+        let context = validate("fn test() -> i32 {\n    {\n        return 1;\n    };\n    let y = 2;\n    let z = 3;\n}\n").unwrap();
+        assert_eq!(context.warnings.len(), 1);
+        assert_eq!(context.warnings[0].code, Some(codes::UNREACHABLE_CODE));
+        assert_eq!(context.warnings[0].primary_span().map(|span| span.start_line), Some(5));
+
+        // Code generation stops at the `return`
+        let context = validate("fn test() -> i32 {\n    return 1;\n    let y = 2;\n}\n").unwrap();
+        assert_eq!(context.warnings.len(), 1);
+        JitterContext::default().translate(context).unwrap();
+    }
+
+    #[test]
+    fn references() {
+        let validate = |body: &str| validate("references.jitter", &format!("struct Point {{\n    pub x: i32,\n    pub y: i32,\n}}\n\n{}", body));
+        let code = |body: &str| validate(body).err().and_then(|error| error.code);
+
+        // Fields are as mutable as their root variable
+        assert_eq!(code("fn test() {\n    let p = Point { x: 1, y: 2 };\n    p.x = 3;\n}\n"), Some(codes::ASSIGN_TO_IMMUTABLE));
+        assert!(validate("fn test() {\n    let mut p = Point { x: 1, y: 2 };\n    p.x = 3;\n}\n").is_ok());
+
+        // ..unless they are reached through a reference, which decides instead
+        assert_eq!(code("fn test(p: &Point) {\n    p.x = 3;\n}\n"), Some(codes::ASSIGN_TO_IMMUTABLE));
+        assert!(validate("fn test(p: &mut Point, x: &mut i32) {\n    p.x = 3;\n    *x += 1;\n}\n").is_ok());
+        assert_eq!(code("fn test() {\n    let x = 1;\n    let r = &mut x;\n}\n"), Some(codes::ASSIGN_TO_IMMUTABLE));
+
+        // References to locals (including parameters and temporaries) can't outlive the function
+        assert_eq!(code("fn test() -> &i32 {\n    let x = 1;\n    &x\n}\n"), Some(codes::RETURN_LOCAL_REFERENCE));
+        assert_eq!(code("fn test(p: Point) -> &i32 {\n    let r = &p;\n    return &r.y;\n}\n"), Some(codes::RETURN_LOCAL_REFERENCE));
+        assert_eq!(code("fn test() -> &i32 {\n    &5\n}\n"), Some(codes::RETURN_LOCAL_REFERENCE));
+        assert!(validate("fn test(p: &Point) -> &i32 {\n    let r = &p.x;\n    r\n}\n").is_ok());
+
+        assert_eq!(code("fn test() {\n    test() = 2;\n}\n"), Some(codes::INVALID_ASSIGNMENT));
+    }
+
+    #[test]
+    fn struct_ordering() {
+        let validate = |input: &str| validate("structs.jitter", input);
+
+        // Used before being declared
+        let context = validate("struct Outer {\n    a: u8,\n    inner: Inner,\n}\n\nstruct Inner {\n    x: u64,\n}\n").unwrap();
+        assert_eq!(context.types.size_of(&Type::User("Outer".to_owned())), 16);
+        assert_eq!(context.get_field_offset(&Type::User("Outer".to_owned()), "inner").unwrap(), 8);
+
+        // Referring to one another is fine
+        let context = validate("struct A {\n    b: &B,\n    x: u8,\n}\n\nstruct B {\n    a: &A,\n    next: &B,\n}\n").unwrap();
+        assert_eq!(context.types.size_of(&Type::User("A".to_owned())), 16);
+        assert_eq!(context.types.size_of(&Type::User("B".to_owned())), 16);
+
+        // Containing one another is not
+        let error = validate("struct A {\n    b: B,\n}\n\nstruct B {\n    c: C,\n}\n\nstruct C {\n    a: A,\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::RECURSIVE_TYPE));
+        assert!(error.message.contains("`A` -> `B` -> `C` -> `A`"), "{}", error.message);
+        assert_eq!(error.labels.len(), 3);
+
+        let error = validate("struct A {\n    x: Missing,\n}\n").err().unwrap();
+        assert_eq!(error.code, Some(codes::UNKNOWN_TYPE));
+    }
+
+    #[test]
+    fn target_layout() {
+        use std::str::FromStr;
+        use jitter::frontend::validate::target::TargetInfo;
+
+        let validate = |triple: &str, input: &str| {
+            let target = TargetInfo::from_triple(&target_lexicon::Triple::from_str(triple).unwrap());
+            validate_with(Context::with_target(target), "layout.jitter", input)
+        };
+
+        // Field offsets, size, and alignment (matching `#[repr(C)]` in Rust)
+        let layout = |triple: &str| {
+            let context = validate(triple, "struct Layout {\n    a: u8,\n    b: u64,\n    c: usize,\n    d: i128,\n    e: &Layout,\n}\n").unwrap();
+            let ty = Type::User("Layout".to_owned());
+            let offsets = ["a", "b", "c", "d", "e"].iter()
+                .map(|field| context.get_field_offset(&ty, field).unwrap())
+                .collect::<Vec<_>>();
+
+            (offsets, context.types.size_of(&ty), context.types.alignment_of(&ty))
+        };
+
+        assert_eq!(layout("x86_64-unknown-linux-gnu"), (vec![0, 8, 16, 32, 48], 64, 16));
+        assert_eq!(layout("aarch64-apple-darwin"), (vec![0, 8, 16, 32, 48], 64, 16));
+        assert_eq!(layout("i686-unknown-linux-gnu"), (vec![0, 4, 12, 16, 32], 48, 16));
+        assert_eq!(layout("i686-pc-windows-msvc"), (vec![0, 8, 16, 32, 48], 64, 16));
+        assert_eq!(layout("armv7-unknown-linux-gnueabihf"), (vec![0, 8, 16, 24, 40], 48, 8));
+        assert_eq!(layout("wasm32-unknown-unknown"), (vec![0, 8, 16, 32, 48], 64, 16));
+
+        // `usize` literals are checked against the target's pointer width
+        let input = "fn test() -> usize {\n    4294967296\n}\n";
+        assert!(validate("x86_64-unknown-linux-gnu", input).is_ok());
+        assert_eq!(validate("i686-unknown-linux-gnu", input).err().unwrap().code, Some(codes::LITERAL_OUT_OF_RANGE));
+    }
+
+    #[test]
+    fn layout_attributes() {
+        let context = validate_file("./tests/layout_test.jitter").unwrap();
+        let layout = |name: &str| {
+            let ty = Type::User(name.to_owned());
+            (context.types.size_of(&ty), context.types.alignment_of(&ty))
+        };
+
+        assert_eq!(layout("Packed"), (std::mem::size_of::<Packed>(), std::mem::align_of::<Packed>()));
+        assert_eq!(context.get_field_offset(&Type::User("Packed".to_owned()), "c").unwrap() as usize, std::mem::offset_of!(Packed, c));
+        assert_eq!(layout("Aligned"), (std::mem::size_of::<Aligned>(), std::mem::align_of::<Aligned>()));
+        assert_eq!(layout("FieldAligned"), (16, 8));
+        assert_eq!(layout("OverAligned"), (32, 32));
+
+        // Over-aligned allocations
+        JitterContext::default().translate(context).unwrap();
+
+        let code = |input: &str| validate("layout.jitter", input).err().and_then(|error| error.code);
+        assert_eq!(code("@assert_layout(size = 8)\nstruct A {\n    a: u32,\n}\n"), Some(codes::LAYOUT_MISMATCH));
+        assert_eq!(code("@align(3)\nstruct A {\n    a: u32,\n}\n"), Some(codes::INVALID_ATTRIBUTE));
+        assert_eq!(code("@packed\n@align(4)\nstruct A {\n    a: u32,\n}\n"), Some(codes::INVALID_ATTRIBUTE));
+        assert_eq!(code("struct A {\n    @packed\n    a: u32,\n}\n"), Some(codes::INVALID_ATTRIBUTE));
+        assert_eq!(code("@assert_layout(offset = 0)\nstruct A {\n    a: u32,\n}\n"), Some(codes::INVALID_ATTRIBUTE));
+
+        // Layout attributes and an extension's meta tag can be mixed in any order
+        let parse = |input: &str| {
+            let path = "attributes.jitter";
+            let parser = Parser::new(path, Lexer::lex_str(path.to_owned(), input.to_owned(), true));
+            let parsed = parser.parse_top_level()
+                .map(|(item, meta)| match item {
+                    ast::TopLevel::Struct(item) => (item.attributes.len(), meta.map(|meta| format!("{:?}", meta))),
+                    _ => unreachable!(),
+                })
+                .ok();
+
+            (parsed, parser.errors())
+        };
+
+        for input in ["@packed\n@extension(x)\n@align(4)\nstruct A {}\n", "@extension(x)\n@packed\n@align(4)\nstruct A {}\n"] {
+            let (parsed, errors) = parse(input);
+            assert!(errors.is_empty(), "{:?}", errors);
+
+            let (attributes, meta) = parsed.unwrap();
+            assert_eq!(attributes, 2);
+            assert!(meta.unwrap().contains("directive: \"extension\""));
+        }
+
+        let (parsed, errors) = parse("@extension(x)\n@packed\n@other(y)\nstruct A {}\n");
+        assert!(parsed.is_none());
+        assert!(errors[0].contains("Only one extension can be applied to an item. Found `@other` after `@extension`"), "{:?}", errors);
+    }
+
+    #[test]
+    fn struct_layout() {
+        mod mismatched {
+            jitter::JitterLayout! {
+                #[allow(dead_code)]
+                #[repr(C)]
+                pub struct Aligned {
+                    a: u8,
+                    b: u16,
+                }
+            }
+        }
+
+        let builder = || JitterContextBuilder::new().with_source_path("./tests/layout_test.jitter");
+
+        let jitter = builder()
+            .with_layout::<Packed>()
+            .with_layout::<Aligned>()
+            .build()
+            .unwrap();
+
+        let layout = jitter.struct_layout("FieldAligned").unwrap();
+        assert_eq!((layout.size, layout.alignment), (16, 8));
+        let fields = layout.fields.iter()
+            .map(|field| (field.name.as_str(), field.offset, field.ty.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec![("a", 0, "u8"), ("b", 8, "u32")]);
+
+        let error = builder().with_layout::<mismatched::Aligned>().build().err().unwrap();
+        assert!(error.contains("alignment is 16 bytes in Jitter but 2 bytes in Rust"), "{}", error);
+        assert!(error.contains("field `b` has type `u32` in Jitter but `u16` in Rust"), "{}", error);
+
+        // No Jitter struct to compare with
+        assert!(JitterContextBuilder::new().with_layout::<Packed>().build().is_err());
+    }
+
+    #[test]
+    fn typed_functions() {
+        let jitter = build("./tests/layout_test.jitter");
+
+        let over_aligned = jitter.get_typed_fn::<(u8,), u8>("over_aligned").unwrap();
+        assert_eq!(over_aligned.as_ptr(), jitter.get_fn("over_aligned"));
+        assert_eq!(over_aligned.call((7,)), 7);
+
+        // Returned structs are copied into (aligned) storage owned by the caller
+        let make_aligned = jitter.get_typed_fn::<(u8, u32), Aligned>("make_aligned").unwrap();
+        let aligned = make_aligned.call((3, 0xDEAD_BEEF));
+        assert_eq!((aligned.a, aligned.b), (3, 0xDEAD_BEEF));
+
+        // Calls between Jitter functions do the same
+        let nested_calls = jitter.get_typed_fn::<(u8,), u8>("nested_calls").unwrap();
+        assert_eq!(nested_calls.call((42,)), 42);
+
+        let error = jitter.get_typed_fn::<(u8, u64), Aligned>("make_aligned").err().unwrap();
+        assert!(error.contains("has signature `fn(u8, u32) -> Aligned`, but was requested as `fn(u8, u64) -> Aligned`"), "{}", error);
+        assert!(error.contains("parameter #1 is `u32`, not `u64`"), "{}", error);
+
+        let error = jitter.get_typed_fn::<(), ()>("over_aligned").err().unwrap();
+        assert!(error.contains("takes 1 parameters, not 0"), "{}", error);
+        assert!(error.contains("returns `u8`, not `()`"), "{}", error);
+
+        assert!(jitter.get_typed_fn::<(), ()>("missing").is_err());
+
+        // Same name, different layout (checked even without `with_layout`)
+        mod swapped {
+            jitter::JitterLayout! {
+                #[repr(C, align(16))]
+                pub struct Aligned {
+                    pub b: u32,
+                    pub a: u8,
+                }
+            }
+        }
+
+        let error = jitter.get_typed_fn::<(u8, u32), swapped::Aligned>("make_aligned").err().unwrap();
+        assert!(error.contains("Layout of struct `Aligned` differs"), "{}", error);
+        assert!(error.contains("field #0 is `a` in Jitter but `b` in Rust"), "{}", error);
+    }
+
+    #[test]
+    fn c_calling_convention() {
+        use jitter::backend::abi::CallingConvention;
+
+        JitterLayout! {
+            #[derive(Debug, PartialEq)]
+            #[repr(C)]
+            struct Mixed {
+                a: i32,
+                b: f32,
+                c: f64,
+            }
+        }
+
+        JitterLayout! {
+            #[repr(C)]
+            struct Floats {
+                x: f32,
+                y: f32,
+                z: f32,
+            }
+        }
+
+        JitterLayout! {
+            #[derive(Debug, PartialEq)]
+            #[repr(C)]
+            struct Three {
+                a: u8,
+                b: u8,
+                c: u8,
+            }
+        }
+
+        JitterLayout! {
+            #[repr(C)]
+            struct Nested {
+                three: Three,
+                d: u8,
+            }
+        }
+
+        JitterLayout! {
+            #[derive(Debug, PartialEq)]
+            #[repr(C)]
+            struct Large {
+                a: u64,
+                b: u64,
+                c: u64,
+            }
+        }
+
+        // Extern functions are plain `extern "C"` functions too
+        extern "C" fn rust_pick(a: i32, b: Mixed) -> Mixed {
+            Mixed { a: b.a + a, ..b }
+        }
+
+        extern "C" fn rust_widen(x: i8) -> i32 {
+            x as i32
+        }
+
+        fn builder(path: &str) -> JitterContextBuilder<'_> {
+            JitterContextBuilder::new()
+                .with_source_path(path)
+                .with_function("rust_pick", rust_pick as *const u8)
+                .with_function("rust_widen", rust_widen as *const u8)
+                .with_calling_convention(CallingConvention::C)
+        }
+
+        let jitter = builder("./tests/c_abi_test.jitter")
+            .with_layout::<Mixed>()
+            .with_layout::<Floats>()
+            .with_layout::<Large>()
+            .emit_clif(true)
+            .build()
+            .unwrap();
+
+        let second: extern "C" fn(i32, f32) -> f32 = unsafe { std::mem::transmute(jitter.get_fn("second")) };
+        assert_eq!(second(1, 2.5), 2.5);
+
+        let identity_bool = jitter.get_typed_fn::<(bool,), bool>("identity_bool").unwrap();
+        assert!(identity_bool.call((true,)));
+        assert!(!identity_bool.call((false,)));
+
+        let identity_i16 = jitter.get_typed_fn::<(i16,), i16>("identity_i16").unwrap();
+        assert_eq!(identity_i16.call((-2,)), -2);
+        let widen = jitter.get_typed_fn::<(i8,), i32>("widen").unwrap();
+        assert_eq!(widen.call((-3,)), -3);
+
+        // Extended to 32 bits, as C compilers expect
+        for (name, signature) in [("identity_bool", "(i8 uext) -> i8 uext"), ("identity_i16", "(i16 sext) -> i16 sext"), ("widen", "(i8 sext) -> i32")] {
+            let clif = jitter.clif_for(name).unwrap();
+            assert!(clif.contains(signature), "{}", clif);
+        }
+
+        let identity_i128 = jitter.get_typed_fn::<(i128,), i128>("identity_i128").unwrap();
+        assert_eq!(identity_i128.call((-(1 << 100),)), -(1 << 100));
+
+        let make_mixed = jitter.get_typed_fn::<(i32, f32, f64), Mixed>("make_mixed").unwrap();
+        assert_eq!(make_mixed.call((-3, 1.5, 2.25)), Mixed { a: -3, b: 1.5, c: 2.25 });
+
+        let floats_y = jitter.get_typed_fn::<(Floats,), f32>("floats_y").unwrap();
+        assert_eq!(floats_y.call((Floats { x: 1.0, y: 2.0, z: 3.0 },)), 2.0);
+
+        let make_large = jitter.get_typed_fn::<(u64, u64, u64), Large>("make_large").unwrap();
+        assert_eq!(make_large.call((1, 2, u64::MAX)), Large { a: 1, b: 2, c: u64::MAX });
+
+        let call_rust = jitter.get_typed_fn::<(i32, f32, f64), Mixed>("call_rust").unwrap();
+        assert_eq!(call_rust.call((4, 0.5, -8.0)), Mixed { a: 8, b: 0.5, c: -8.0 });
+
+        let make_three = jitter.get_typed_fn::<(u8, u8, u8), Three>("make_three").unwrap();
+        assert_eq!(make_three.call((1, 2, 3)), Three { a: 1, b: 2, c: 3 });
+
+        let three_c = jitter.get_typed_fn::<(Three,), u8>("three_c").unwrap();
+        assert_eq!(three_c.call((Three { a: 1, b: 2, c: 3 },)), 3);
+
+        let nested_three = jitter.get_typed_fn::<(Nested,), Three>("nested_three").unwrap();
+        let nested = Nested { three: Three { a: 4, b: 5, c: 6 }, d: 7 };
+        assert_eq!(nested_three.call((nested,)), Three { a: 4, b: 5, c: 6 });
+
+        // Structs passed in memory can't be passed by value
+        let path = std::env::temp_dir().join("jitter_c_abi_memory.jitter");
+        std::fs::write(&path, "struct Large {\n    a: u64,\n    b: u64,\n    c: u64,\n}\n\nfn by_value(v: Large) {}\n").unwrap();
+        let error = builder(path.to_str().unwrap()).build().err().unwrap();
+        assert!(error.contains("Function `by_value` takes a `Large` (24 bytes) by value, which the C calling convention passes on the stack"), "{}", error);
+        assert!(error.contains("Consider taking a `&Large` instead"), "{}", error);
+
+        // As are structs split across registers, once there aren't enough left for the whole struct
+        let path = std::env::temp_dir().join("jitter_c_abi_exhausted.jitter");
+        std::fs::write(&path, "struct Wide {\n    a: u64,\n    b: u64,\n}\n\nfn exhausted(a: u64, b: u64, c: u64, d: u64, e: u64, wide: Wide) {}\n").unwrap();
+        let error = builder(path.to_str().unwrap()).build().err().unwrap();
+        assert!(error.contains("Function `exhausted` takes a `Wide` by value after the argument registers run out"), "{}", error);
+        assert!(error.contains("Consider taking a `&Wide` instead"), "{}", error);
+    }
+
+    #[test]
+    fn scalar_values() {
+        use jitter::backend::abi::CallingConvention;
+
+        JitterLayout! {
+            #[derive(Debug, PartialEq)]
+            #[repr(C)]
+            struct Pair {
+                a: u32,
+                b: u32,
+            }
+        }
+
+        for calling_convention in [CallingConvention::Jitter, CallingConvention::C] {
+            let jitter = JitterContextBuilder::new()
+                .with_source_path("./tests/codegen_test.jitter")
+                .with_calling_convention(calling_convention)
+                .with_layout::<Pair>()
+                .build()
+                .unwrap();
+
+            for name in ["reassign", "deferred", "read_through", "write_through", "field_through", "copy_let", "copy_param", "mut_param", "block_value", "block_pair", "block_tail", "block_return"] {
+                let function = jitter.get_typed_fn::<(u32, u32), u32>(name).unwrap();
+                assert_eq!(function.call((1, 2)), 2, "{} ({:?})", name, calling_convention);
+            }
+
+            let swap = jitter.get_typed_fn::<(Pair,), Pair>("swap").unwrap();
+            assert_eq!(swap.call((Pair { a: 1, b: 2 },)), Pair { a: 2, b: 1 });
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        use jitter::backend::abi::CallingConvention;
+
+        for calling_convention in [CallingConvention::Jitter, CallingConvention::C] {
+            let jitter = JitterContextBuilder::new()
+                .with_source_path("./tests/arithmetic_test.jitter")
+                .with_calling_convention(calling_convention)
+                .build()
+                .unwrap();
+
+            let arithmetic = jitter.get_typed_fn::<(u32, u32), u32>("arithmetic").unwrap();
+            assert_eq!(arithmetic.call((7, 2)), 15, "{:?}", calling_convention);
+
+            let signed_division = jitter.get_typed_fn::<(i32, i32), i32>("signed_division").unwrap();
+            assert_eq!(signed_division.call((7, 2)), -3);
+
+            let float_arithmetic = jitter.get_typed_fn::<(f64, f64), f64>("float_arithmetic").unwrap();
+            assert_eq!(float_arithmetic.call((1.0, 4.0)), 1.5);
+
+            let wide_arithmetic = jitter.get_typed_fn::<(i128, i128), i128>("wide_arithmetic").unwrap();
+            assert_eq!(wide_arithmetic.call((1 << 70, 3)), 1 << 72);
+
+            let not = jitter.get_typed_fn::<(bool,), bool>("not").unwrap();
+            assert!(not.call((false,)));
+            assert!(!not.call((true,)));
+        }
+    }
+
+    #[test]
+    fn compile_options() {
+        use jitter::backend::options::{CompileOptions, OptLevel};
+
+        let build = |options: CompileOptions| JitterContextBuilder::new()
+            .with_source_path("./tests/codegen_test.jitter")
+            .with_compile_options(options)
+            .build();
+
+        let presets = [
+            CompileOptions::default(),
+            CompileOptions::debug(),
+            CompileOptions { opt_level: OptLevel::Speed, nan_canonicalization: true, probestack: false, ..CompileOptions::default() },
+            CompileOptions::default().with_flag("enable_verifier", "false"),
+        ];
+
+        for options in presets {
+            let jitter = build(options.clone()).unwrap();
+            let reassign = jitter.get_typed_fn::<(u32, u32), u32>("reassign").unwrap();
+            assert_eq!(reassign.call((1, 2)), 2, "{:?}", options);
+        }
+
+        let error = build(CompileOptions::default().with_flag("no_such_setting", "true")).err().unwrap();
+        assert!(error.contains("Invalid Cranelift setting `no_such_setting = true`"), "{}", error);
+
+        let error = build(CompileOptions::default().with_flag("opt_level", "fastest")).err().unwrap();
+        assert!(error.contains("Invalid Cranelift setting `opt_level = fastest`"), "{}", error);
+    }
+
+    #[test]
+    fn clif() {
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/codegen_test.jitter")
+            .emit_clif(true)
+            .build()
+            .unwrap();
+
+        assert!(jitter.function_names().contains(&"reassign"));
+
+        let clif = jitter.clif_for("reassign").unwrap();
+        assert!(clif.starts_with("; `reassign` before optimization\nfunction"), "{}", clif);
+        assert!(clif.contains("; `reassign` after optimization\nfunction"), "{}", clif);
+        assert!(clif.contains("return"), "{}", clif);
+
+        assert!(jitter.clif_for("no_such_function").is_none());
+
+        // Nothing is kept unless requested
+        let jitter = build("./tests/codegen_test.jitter");
+        assert!(jitter.clif_for("reassign").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "disasm")]
+    fn disasm() {
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/codegen_test.jitter")
+            .emit_disasm(true)
+            .build()
+            .unwrap();
+
+        // One instruction per line, at the function's address
+        let disasm = jitter.disasm_for("reassign").unwrap();
+        let address = jitter.get_fn("reassign") as u64;
+        assert!(disasm.starts_with(&format!("{:#x}: ", address)), "{}", disasm);
+        assert!(disasm.lines().last().unwrap().ends_with("ret"), "{}", disasm);
+
+        // Nothing is kept unless requested
+        let jitter = build("./tests/codegen_test.jitter");
+        assert!(jitter.disasm_for("reassign").is_none());
+    }
+
+    #[test]
+    fn debug_info() {
+        use object::{Object, ObjectSection};
+
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/debug_test.jitter")
+            .with_debug_info(true)
+            .build()
+            .unwrap();
+
+        let image = object::File::parse(jitter.debug_image().unwrap()).unwrap();
+        let dwarf = gimli::read::Dwarf::load(
+            |id| -> Result<_, gimli::Error> {
+                let data = image.section_by_name(id.name())
+                    .map(|section| section.data().unwrap())
+                    .unwrap_or(&[]);
+
+                Ok(gimli::EndianSlice::new(data, gimli::RunTimeEndian::Little))
+            },
+            |_| Ok(gimli::EndianSlice::new(&[], gimli::RunTimeEndian::Little)),
+        ).unwrap();
+
+        let unit = dwarf.units().next().unwrap().map(|header| dwarf.unit(header).unwrap()).unwrap();
+
+        // Functions are described at their JIT-compiled addresses
+        let pick = jitter.get_fn("pick") as u64;
+        let mut pick_size = 0;
+        let mut variables = Vec::new();
+
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs().unwrap() {
+            let name = entry.attr_value(gimli::DW_AT_name).unwrap()
+                .map(|name| dwarf.attr_string(&unit, name).unwrap().to_string_lossy().into_owned());
+
+            match entry.tag() {
+                gimli::DW_TAG_subprogram if name.as_deref() == Some("pick") => {
+                    assert_eq!(entry.attr_value(gimli::DW_AT_low_pc).unwrap(), Some(gimli::AttributeValue::Addr(pick)));
+                    assert_eq!(entry.attr_value(gimli::DW_AT_decl_line).unwrap().and_then(|line| line.udata_value()), Some(12));
+                    pick_size = entry.attr_value(gimli::DW_AT_high_pc).unwrap().and_then(|size| size.udata_value()).unwrap();
+                }
+
+                gimli::DW_TAG_variable => variables.push(name.unwrap()),
+                _ => (),
+            }
+        }
+
+        assert!(pick_size > 0);
+        for variable in ["a", "b", "x"] {
+            assert!(variables.iter().any(|name| name == variable), "{:?}", variables);
+        }
+
+        // Each statement of `pick` maps back to its line of `debug_test.jitter`
+        let program = unit.line_program.clone().unwrap();
+        let mut rows = program.rows();
+        let mut lines = Vec::new();
+
+        while let Some((header, row)) = rows.next_row().unwrap() {
+            if (pick..pick + pick_size).contains(&row.address()) && !row.end_sequence() {
+                let file = row.file(header).unwrap();
+                let path = dwarf.attr_string(&unit, file.path_name()).unwrap();
+                assert_eq!(path.to_string_lossy(), "debug_test.jitter");
+
+                lines.push(row.line().unwrap());
+            }
+        }
+
+        for line in [13, 17, 18] {
+            assert!(lines.contains(&line), "{:?}", lines);
+        }
+
+        // Nothing is registered unless requested
+        let jitter = build("./tests/debug_test.jitter");
+
+        assert!(jitter.debug_image().is_none());
+    }
+
+    #[test]
+    fn perf_map() {
+        use jitter::backend::perf::perf_map_path;
+
+        let build = || JitterContextBuilder::new()
+            .with_source_path("./tests/codegen_test.jitter")
+            .with_perf_map(true)
+            .build()
+            .unwrap();
+
+        // Rebuilding (reloading) appends entries for the new code
+        let jitters = [build(), build()];
+        let perf_map = std::fs::read_to_string(perf_map_path()).unwrap();
+
+        for jitter in &jitters {
+            for name in ["reassign", "swap"] {
+                let address = format!("{:x}", jitter.get_fn(name) as u64);
+
+                let entry = perf_map.lines()
+                    .find(|line| line.starts_with(&format!("{} ", address)))
+                    .unwrap_or_else(|| panic!("No entry for `{}` at {}:\n{}", name, address, perf_map));
+
+                let parts = entry.split(' ').collect::<Vec<_>>();
+                assert!(u64::from_str_radix(parts[1], 16).unwrap() > 0, "{}", entry);
+                assert_eq!(parts[2], format!("codegen_test::{}", name));
+            }
+        }
+    }
+
+    #[test]
+    fn jitdump() {
+        use jitter::backend::perf::jitdump_path;
+        use std::convert::TryInto;
+
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/codegen_test.jitter")
+            .with_jitdump(true)
+            .build()
+            .unwrap();
+
+        let dump = std::fs::read(jitdump_path()).unwrap();
+        let u32_at = |offset: usize| u32::from_ne_bytes(dump[offset..offset + 4].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_ne_bytes(dump[offset..offset + 8].try_into().unwrap());
+
+        // Header: magic, version, header size, ..., pid
+        assert_eq!(u32_at(0), 0x4A69_5444);
+        assert_eq!(u32_at(4), 1);
+        assert_eq!(u32_at(20), std::process::id());
+
+        let address = jitter.get_fn("reassign") as u64;
+        let mut offset = u32_at(8) as usize;
+        let mut found = false;
+        while offset < dump.len() {
+            let (id, size) = (u32_at(offset), u32_at(offset + 4) as usize);
+            assert_eq!(id, 0, "Only `JIT_CODE_LOAD` records are written");
+
+            let code_size = u64_at(offset + 40) as usize;
+            let name_end = offset + 56 + dump[offset + 56..].iter().position(|&byte| byte == 0).unwrap();
+            let name = std::str::from_utf8(&dump[offset + 56..name_end]).unwrap();
+            let code = &dump[name_end + 1..name_end + 1 + code_size];
+            assert_eq!(size, name_end + 1 + code_size - offset);
+
+            if u64_at(offset + 24) == address {
+                assert_eq!(name, "codegen_test::reassign");
+                assert_eq!(code, unsafe { std::slice::from_raw_parts(address as *const u8, code_size) });
+                found = true;
+            }
+
+            offset += size;
+        }
+
+        assert!(found, "No record for `reassign`");
+    }
+
+    #[test]
+    fn traps() {
+        use jitter::backend::trap::TrapKind;
+
+        // Extern functions take a return buffer under Jitter's calling convention
+        extern "C" fn null_reference(output: *mut *const u32) {
+            unsafe { *output = std::ptr::null() };
+        }
+
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/trap_test.jitter")
+            .with_function("null_reference", null_reference as *const u8)
+            .build()
+            .unwrap();
+
+        let read_null = jitter.get_typed_fn::<(), u32>("read_null").unwrap();
+        let identity = jitter.get_typed_fn::<(u32,), u32>("identity").unwrap();
+
+        // Other contexts coming and going don't affect this one's traps
+        drop(JitterContextBuilder::new()
+            .with_source_path("./tests/trap_test.jitter")
+            .with_function("null_reference", null_reference as *const u8)
+            .build()
+            .unwrap());
+
+        // Traps can be caught repeatedly, and leave other calls unaffected
+        for _ in 0..2 {
+            let trap = read_null.try_call(()).unwrap_err();
+            assert_eq!(trap.kind, TrapKind::MemoryOutOfBounds);
+            assert_eq!(trap.function, "read_null");
+            assert_eq!(trap.span.map(|span| span.start_line), Some(9), "{}", trap);
+
+            assert_eq!(identity.try_call((42,)).unwrap(), 42);
+        }
+
+        let divide = jitter.get_typed_fn::<(u32, u32), u32>("divide").unwrap();
+        assert_eq!(divide.try_call((7, 2)).unwrap(), 3);
+
+        let trap = divide.try_call((1, 0)).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::IntegerDivisionByZero);
+        assert_eq!(trap.function, "divide");
+        assert_eq!(trap.span.map(|span| span.start_line), Some(17), "{}", trap);
+
+        let message = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| read_null.call(())))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.contains("`read_null` trapped"), "{}", message);
+    }
+
+    #[test]
+    fn fuel() {
+        use jitter::backend::trap::TrapKind;
+
+        let jitter = JitterContextBuilder::new()
+            .with_source_path("./tests/fuel_test.jitter")
+            .with_fuel(100)
+            .build()
+            .unwrap();
+
+        let spin = jitter.get_typed_fn::<(), ()>("spin").unwrap();
+        let twice = jitter.get_typed_fn::<(u32,), u32>("twice").unwrap();
+
+        // Each call consumes one unit
+        assert_eq!(twice.try_call((7,)).unwrap(), 7);
+        assert_eq!(jitter.remaining_fuel(), Some(97));
+
+        // Unbounded recursion runs out
+        let trap = spin.try_call(()).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::FuelExhausted);
+        assert_eq!(trap.function, "spin");
+        assert_eq!(jitter.remaining_fuel(), Some(0));
+        assert_eq!(twice.try_call((7,)).unwrap_err().kind, TrapKind::FuelExhausted);
+
+        jitter.set_fuel(10).unwrap();
+        assert_eq!(twice.try_call((7,)).unwrap(), 7);
+        assert_eq!(jitter.remaining_fuel(), Some(7));
+
+        // Per-call budgets leave the context's fuel alone
+        assert_eq!(twice.try_call_with_fuel((7,), 2).unwrap_err().kind, TrapKind::FuelExhausted);
+        assert_eq!(twice.try_call_with_fuel((7,), 3).unwrap(), 7);
+        assert_eq!(spin.try_call_with_fuel((), 1000).unwrap_err().kind, TrapKind::FuelExhausted);
+        assert_eq!(jitter.remaining_fuel(), Some(7));
+
+        // Unmetered by default
+        let unmetered = build("./tests/fuel_test.jitter");
+        assert_eq!(unmetered.remaining_fuel(), None);
+        assert!(unmetered.set_fuel(10).unwrap_err().contains("isn't metered"));
+
+        let identity = unmetered.get_typed_fn::<(u32,), u32>("identity").unwrap();
+        let trap = identity.try_call_with_fuel((7,), 10).unwrap_err();
+        assert_eq!((trap.kind, trap.function.as_str()), (TrapKind::NotMetered, "identity"));
+    }
+
+    #[test]
+    fn object_files() {
+        use jitter::backend::object::JitterObjectBuilder;
+        use std::str::FromStr;
+
+        // (triple, ELF `e_machine`)
+        let targets = [
+            ("x86_64-unknown-linux-gnu", 62u16),
+            ("aarch64-unknown-linux-gnu", 183u16),
+        ];
+
+        for (triple, machine) in targets {
+            let bytes = JitterObjectBuilder::new(target_lexicon::Triple::from_str(triple).unwrap())
+                .with_source_path("./tests/codegen_test.jitter")
+                .build()
+                .unwrap();
+
+            assert_eq!(&bytes[..4], b"\x7fELF", "{}", triple);
+            assert_eq!(u16::from_le_bytes([bytes[18], bytes[19]]), machine, "{}", triple);
+
+            // Compiled functions are exported by name
+            for name in ["reassign", "deferred", "swap"] {
+                assert!(bytes.windows(name.len()).any(|window| window == name.as_bytes()), "{}: {}", triple, name);
+            }
+        }
+
+        let error = JitterObjectBuilder::new(target_lexicon::Triple::from_str("wasm32-unknown-unknown").unwrap())
+            .with_source_path("./tests/codegen_test.jitter")
+            .build()
+            .err()
+            .unwrap();
+        assert!(error.contains("Unsupported target `wasm32-unknown-unknown`"), "{}", error);
+    }
+
+    #[test]
+    fn executables() {
+        use jitter::backend::{object::JitterObjectBuilder, abi::CallingConvention};
+
+        let output_path = std::env::temp_dir().join(format!("jitter_executable_test_{}", std::process::id()));
+        let output_path = output_path.to_str().unwrap();
+
+        for calling_convention in [CallingConvention::Jitter, CallingConvention::C] {
+            JitterObjectBuilder::new(target_lexicon::Triple::host())
+                .with_source_path("./tests/executable_test.jitter")
+                .with_calling_convention(calling_convention)
+                .build_executable(output_path)
+                .unwrap();
+
+            let status = std::process::Command::new(output_path).status().unwrap();
+            assert_eq!(status.code(), Some(42), "{:?}", calling_convention);
+        }
+
+        std::fs::remove_file(output_path).unwrap();
+
+        // Only a parameterless `main` can be called by the C runtime
+        let error = JitterObjectBuilder::new(target_lexicon::Triple::host())
+            .with_source_path("./tests/codegen_test.jitter")
+            .with_entry_point(true)
+            .build()
+            .err()
+            .unwrap();
+        assert!(error.contains("No `main` function to call"), "{}", error);
     }
 
    #[test]